@@ -0,0 +1,66 @@
+/* tests the C interface of bitwister, see Makefile */
+#include <stdio.h>
+#include <string.h>
+
+#include "bitwister.h"
+
+static int failed = 0;
+
+#define CHECK(cond) do { \
+    if (!(cond)) { \
+        fprintf(stderr, "%s:%d: check failed: %s\n", __FILE__, __LINE__, #cond); \
+        failed = 1; \
+    } \
+} while (0)
+
+int main(void) {
+    BwEvaluator *evaluator = bw_evaluator_new();
+    BwResult result;
+    char buf[128];
+
+    CHECK(bw_evaluator_set_width(evaluator, 16) == BW_ERROR_OK);
+    CHECK(bw_evaluator_set_width(evaluator, 12) == BW_ERROR_INVALID_ARGUMENT);
+    CHECK(bw_evaluator_set_var(evaluator, "CLK_DIV", 0x13, 32) == BW_ERROR_OK);
+    CHECK(bw_evaluator_set_var(evaluator, "u8", 1, 8) == BW_ERROR_INVALID_ARGUMENT);
+
+    CHECK(bw_evaluate(evaluator, "(CLK_DIV << 4) + 1", &result) == BW_ERROR_OK);
+    CHECK(result.value == 0x131 && result.width == 32);
+    CHECK(result.overflow == BW_OVERFLOW_NONE);
+    CHECK(bw_render(&result, "hex", false, buf, sizeof buf) == 10);
+    CHECK(strcmp(buf, "0x00000131") == 0);
+
+    /* 64-bit values keep every bit */
+    CHECK(bw_evaluate(evaluator, "0xffffffffffffffffu64 + 1u64", &result) == BW_ERROR_OK);
+    CHECK(result.value == 0 && result.width == 64 && result.overflow == BW_OVERFLOW_OCCURRED);
+
+    /* unsuffixed literals take the width of the evaluator */
+    CHECK(bw_evaluate(evaluator, "0xfffe", &result) == BW_ERROR_OK);
+    CHECK(result.width == 16);
+    CHECK(bw_render(&result, "dec", true, buf, sizeof buf) == 2);
+    CHECK(strcmp(buf, "-2") == 0);
+
+    /* truncated like snprintf */
+    CHECK(bw_render(&result, "bin", false, buf, 5) == 17);
+    CHECK(strcmp(buf, "b111") == 0);
+
+    CHECK(bw_evaluate(evaluator, "1 + (2 ^ nope)", &result) == BW_ERROR_UNKNOWN_VARIABLE);
+    CHECK(result.error_start == 9 && result.error_end == 13);
+    CHECK(strcmp(bw_error_message(result.error), "error: unknown variable") == 0);
+    CHECK(bw_evaluate(evaluator, "1u8 / 0u8", &result) == BW_ERROR_DIVISION_BY_ZERO);
+    CHECK(bw_render(&result, NULL, false, buf, sizeof buf) > 0);
+    CHECK(strcmp(buf, "error: division by zero") == 0);
+    CHECK(bw_evaluate(evaluator, NULL, &result) == BW_ERROR_INVALID_ARGUMENT);
+
+    /* codes that are not a BwError are rejected */
+    CHECK(bw_error_message(1000) == NULL);
+    result.error = 1000;
+    CHECK(bw_render(&result, NULL, false, buf, sizeof buf) == 0);
+
+    bw_evaluator_free(evaluator);
+
+    if (failed) {
+        return 1;
+    }
+    printf("ffi tests passed\n");
+    return 0;
+}
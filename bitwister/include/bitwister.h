@@ -0,0 +1,129 @@
+/* bitwister C interface, see src/ffi.rs */
+
+#ifndef BITWISTER_H
+#define BITWISTER_H
+
+/* generated by cbindgen, do not edit */
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+// why a call failed, `Ok` if it didn't
+typedef enum BwError {
+  BW_ERROR_OK = 0,
+  // a null pointer, a string that is not UTF-8, or an unsupported width
+  BW_ERROR_INVALID_ARGUMENT,
+  BW_ERROR_EMPTY,
+  BW_ERROR_INVALID_TOKEN,
+  BW_ERROR_INVALID_LITERAL,
+  BW_ERROR_UNKNOWN_VARIABLE,
+  BW_ERROR_UNEXPECTED_END,
+  BW_ERROR_UNEXPECTED,
+  BW_ERROR_WIDTH_MISMATCH,
+  BW_ERROR_DIVISION_BY_ZERO,
+  BW_ERROR_OPERATION,
+  BW_ERROR_TOO_DEEP,
+} BwError;
+
+// whether an evaluation overflowed
+typedef enum BwOverflow {
+  // none of the operations can overflow
+  BW_OVERFLOW_NOT_POSSIBLE = 0,
+  BW_OVERFLOW_NONE,
+  BW_OVERFLOW_OCCURRED,
+} BwOverflow;
+
+// an evaluator, created with `bw_evaluator_new`
+typedef struct BwEvaluator BwEvaluator;
+
+// the result of `bw_evaluate`. if `error` is not `Ok`, `value` and
+// `width` are 0 and `error_start..error_end` is the byte range of the
+// expression that caused the error.
+typedef struct BwResult {
+  uint64_t value;
+  // bits of the value: 8, 16, 32 or 64
+  uint32_t width;
+  // a `BwOverflow`, as a plain number because C can store any in it
+  uint32_t overflow;
+  // a `BwError`, as a plain number too
+  uint32_t error;
+  size_t error_start;
+  size_t error_end;
+} BwResult;
+
+
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+// create an evaluator with the default width u32 and no variables, free
+// it with `bw_evaluator_free`
+struct BwEvaluator *bw_evaluator_new(void);
+
+// free an evaluator, null is ignored
+//
+// # Safety
+//
+// `evaluator` is null or was returned by `bw_evaluator_new` and is not
+// used afterwards
+void bw_evaluator_free(struct BwEvaluator *evaluator);
+
+// set the width of literals without a `u` suffix to `bits`
+//
+// # Safety
+//
+// `evaluator` is null or was returned by `bw_evaluator_new`
+enum BwError bw_evaluator_set_width(struct BwEvaluator *evaluator, uint32_t bits);
+
+// set the variable `name` to `value`, truncated to `bits`. a name that is
+// not a word, or is an operation or a width, is an invalid argument.
+//
+// # Safety
+//
+// `evaluator` is null or was returned by `bw_evaluator_new`, `name` is
+// null or a NUL terminated string
+enum BwError bw_evaluator_set_var(struct BwEvaluator *evaluator,
+                                  const char *name,
+                                  uint64_t value,
+                                  uint32_t bits);
+
+// evaluate `expr` into `result`, returns the error of the result
+//
+// # Safety
+//
+// `evaluator` is null or was returned by `bw_evaluator_new`, `expr` is
+// null or a NUL terminated string, `result` is null or points to a
+// `BwResult`
+enum BwError bw_evaluate(const struct BwEvaluator *evaluator,
+                         const char *expr,
+                         struct BwResult *result);
+
+// render `result` in `format` (`hex`, `bin`, `dec`, `oct`, `ascii` or
+// `reg`, null for hex) into `buf`, `is_signed` shows `dec` as two's
+// complement. like `snprintf`, at most `len - 1` bytes and a NUL are
+// written and the length of the whole text is returned.
+// a failed result renders as its error message. returns 0 and writes
+// nothing if `result` is null or invalid, or the format is unknown.
+//
+// # Safety
+//
+// `result` is null or points to a `BwResult`, `format` is null or a NUL
+// terminated string, `buf` is null or points to `len` writable bytes
+size_t bw_render(const struct BwResult *result,
+                 const char *format,
+                 bool is_signed,
+                 char *buf,
+                 size_t len);
+
+// a static description of the `BwError` `error`, null if it is none
+const char *bw_error_message(uint32_t error);
+
+#ifdef __cplusplus
+}  // extern "C"
+#endif  // __cplusplus
+
+#endif  /* BITWISTER_H */
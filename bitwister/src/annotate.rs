@@ -0,0 +1,319 @@
+/// a filter for logs: numbers found in the text get their decoded value
+/// appended, e.g. `SR=0x83` becomes `SR=0x83 [dec=131]`
+/// 0xca7
+use std::io::{self, BufRead, Read, Write};
+
+use bitwister::{
+    format::Format,
+    register::Register,
+    IntType,
+    Width,
+};
+
+use crate::settings::Settings;
+
+/// longest line that is read at once, longer lines are annotated in parts
+/// so memory stays bounded
+pub const MAX_LINE: usize = 64 * 1024;
+
+/// only numbers right after `prefix` are annotated, with the fields of
+/// `register` if given
+pub struct Key {
+    pub prefix: String,
+    pub register: Option<usize>,
+}
+
+impl Key {
+
+    /// parse `KEY` or `KEY:REGISTER`, e.g. `SR=:USART_SR`
+    pub fn parse(s: &str, registers: &[Register]) -> Result<Key, String> {
+        let (prefix, register) = match s.rsplit_once(':') {
+            Some((prefix, name)) if !name.is_empty() => {
+                let idx = registers.iter().position(|r| r.name == name)
+                    .ok_or_else(|| format!("unknown register {name}"))?;
+                (prefix, Some(idx))
+            },
+            _ => (s, None),
+        };
+        if prefix.is_empty() {
+            return Err(String::from("empty key"));
+        }
+        Ok(Key {
+            prefix: prefix.to_string(),
+            register,
+        })
+    }
+}
+
+/// decides which numbers are annotated and how
+pub struct Annotator<'a> {
+    settings: &'a Settings,
+    /// if not empty, only numbers after one of the keys are annotated
+    keys: Vec<Key>,
+    /// the register of numbers without a key
+    register: Option<usize>,
+}
+
+/// a number found in a line: its position, and value and width as written
+struct Number {
+    start: usize,
+    end: usize,
+    value: u64,
+    hex: bool,
+}
+
+fn is_word(c: u8) -> bool {
+    c.is_ascii_alphanumeric() || c == b'_'
+}
+
+/// the hex or decimal number starting at `start` of `line`, if it stands
+/// alone and is not part of a word or a decimal fraction
+fn number_at(line: &[u8], start: usize) -> Option<Number> {
+    if start > 0 && (is_word(line[start - 1]) || line[start - 1] == b'.') {
+        return None;
+    }
+    let hex = line[start..].starts_with(b"0x") || line[start..].starts_with(b"0X");
+    let digits = if hex { start + 2 } else { start };
+    let end = digits + line[digits..].iter()
+        .take_while(|c| if hex { c.is_ascii_hexdigit() } else { c.is_ascii_digit() })
+        .count();
+    if end == digits {
+        return None;
+    }
+    match line.get(end) {
+        Some(c) if is_word(*c) => return None,
+        Some(b'.') if line.get(end + 1).is_some_and(|c| c.is_ascii_digit()) => return None,
+        _ => (),
+    }
+    // SAFETY: only ascii digits in this range
+    let text = std::str::from_utf8(&line[digits..end]).unwrap();
+    let value = u64::from_str_radix(text, if hex { 16 } else { 10 }).ok()?;
+    Some(Number { start, end, value, hex })
+}
+
+impl<'a> Annotator<'a> {
+
+    pub fn new(settings: &'a Settings, keys: Vec<Key>, register: Option<usize>) -> Self {
+        Annotator {
+            settings,
+            keys,
+            register,
+        }
+    }
+
+    /// the width a number is shown in: the register's, the configured one,
+    /// or the smallest that fits the digits that were written
+    fn width(&self, number: &Number, register: Option<&Register>) -> Width {
+        if let Some(register) = register {
+            return register.width;
+        }
+        if let Some(width) = self.settings.width {
+            return width;
+        }
+        let digits = if number.hex {
+            number.end - number.start - 2
+        } else {
+            0
+        };
+        match (digits, number.value) {
+            (1..=2, _) => Width::U8,
+            (3..=4, _) => Width::U16,
+            (5..=8, _) => Width::U32,
+            (0, v) if v <= u32::MAX as u64 => Width::U32,
+            _ => Width::U64,
+        }
+    }
+
+    /// the annotation of a number, without the brackets
+    fn annotation(&self, number: &Number, register: Option<usize>) -> String {
+        let register = register.map(|idx| &self.settings.registers[idx]);
+        let (value, _) = IntType::from_u64_truncating(number.value,
+            self.width(number, register));
+
+        let formats = match self.settings.formats.as_slice() {
+            [] if number.hex => &[Format::Dec][..],
+            [] => &[Format::Hex][..],
+            formats => formats,
+        };
+        let mut parts: Vec<String> = formats.iter()
+            .filter(|fmt| **fmt != Format::Reg)
+            .map(|fmt| format!("{}={}", fmt.name(), fmt.render(&value, self.settings.signed)))
+            .collect();
+
+        if let Some(register) = register {
+            for (field, v) in register.decode(&value) {
+                if field.bits() == 1 {
+                    parts.push(format!("{}={v}", field.name));
+                } else {
+                    parts.push(format!("{}={v:#x}", field.name));
+                }
+            }
+        }
+        parts.join(" ")
+    }
+
+    /// `line` with an annotation after every number that is looked for
+    pub fn annotate_line(&self, line: &str) -> String {
+        let bytes = line.as_bytes();
+        let mut out = String::with_capacity(line.len() * 2);
+        let mut copied = 0;
+        let mut pos = 0;
+
+        while pos < bytes.len() {
+            if !bytes[pos].is_ascii_digit() {
+                pos += 1;
+                continue;
+            }
+            let number = match number_at(bytes, pos) {
+                Some(number) => number,
+                None => {
+                    // skip the rest of the word
+                    pos += bytes[pos..].iter().take_while(|c| is_word(**c)).count();
+                    continue;
+                },
+            };
+
+            let register = if self.keys.is_empty() {
+                Some(self.register)
+            } else {
+                self.keys.iter()
+                    .find(|key| line[..number.start].ends_with(&key.prefix))
+                    .map(|key| key.register.or(self.register))
+            };
+            if let Some(register) = register {
+                out.push_str(&line[copied..number.end]);
+                out.push_str(&format!(" [{}]", self.annotation(&number, register)));
+                copied = number.end;
+            }
+            pos = number.end;
+        }
+
+        out.push_str(&line[copied..]);
+        out
+    }
+}
+
+/// a byte that can't end a part of a long line: part of a word, a number
+/// with a fraction, or a UTF-8 character
+fn in_word(c: u8) -> bool {
+    is_word(c) || c == b'.' || !c.is_ascii()
+}
+
+/// annotate `input` line by line, each line is written and flushed as soon
+/// as it is read, e.g. for `tail -f`. invalid UTF-8 is replaced. the word at
+/// the end of a part of a long line is carried into the next part, so
+/// numbers and characters are not cut.
+pub fn annotate<R: BufRead, W: Write>(mut input: R, out: &mut W,
+    annotator: &Annotator) -> io::Result<()> {
+    let mut buf = Vec::with_capacity(1024);
+    let mut carry = vec![];
+    // the last part was a single word, its rest is not annotated
+    let mut skip_word = false;
+    loop {
+        buf.clear();
+        buf.append(&mut carry);
+        let limit = (MAX_LINE - buf.len()) as u64;
+        let n = Read::take(&mut input, limit).read_until(b'\n', &mut buf)?;
+        if n == 0 && buf.is_empty() {
+            return Ok(());
+        }
+
+        let skip = if skip_word {
+            buf.iter().take_while(|c| in_word(**c)).count()
+        } else {
+            0
+        };
+        skip_word = false;
+        if buf.len() == MAX_LINE && buf.last() != Some(&b'\n') {
+            let word = buf.iter().rev().take_while(|c| in_word(**c)).count();
+            if word == buf.len() {
+                skip_word = true;
+            } else {
+                carry = buf.split_off(buf.len() - word);
+            }
+        }
+
+        let line = String::from_utf8_lossy(&buf[skip..]);
+        let (line, newline) = match line.strip_suffix('\n') {
+            Some(line) => (line, "\n"),
+            None => (line.as_ref(), ""),
+        };
+        write!(out, "{}{}{newline}", String::from_utf8_lossy(&buf[..skip]),
+            annotator.annotate_line(line))?;
+        out.flush()?;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bitwister::register::parse_registers;
+
+    fn settings() -> Settings {
+        let mut settings = Settings::default();
+        settings.registers = parse_registers("register SR u8\nfield RXNE 5\nfield ERR 3:0\n")
+            .unwrap();
+        settings
+    }
+
+    #[test]
+    fn test_annotate_all() {
+        let settings = settings();
+        let annotator = Annotator::new(&settings, vec![], None);
+        assert_eq!(annotator.annotate_line("read 0x83 from 0x4002, 12 bytes"),
+            "read 0x83 [dec=131] from 0x4002 [dec=16386], 12 [hex=0x0000000c] bytes");
+        // parts of words, fractions and bad hex are left alone
+        assert_eq!(annotator.annotate_line("[ 12.345] uart0 x10 0xfg"),
+            "[ 12.345] uart0 x10 0xfg");
+    }
+
+    #[test]
+    fn test_annotate_keys() {
+        let mut settings = settings();
+        settings.formats = vec![Format::Ascii, Format::Dec];
+        settings.signed = true;
+        let keys = vec![
+            Key::parse("SR=:SR", &settings.registers).unwrap(),
+            Key::parse("DR=", &settings.registers).unwrap(),
+        ];
+        let annotator = Annotator::new(&settings, keys, None);
+        assert_eq!(annotator.annotate_line("SR=0x23 DR=0xff CNT=0x41"),
+            "SR=0x23 [ascii=# dec=35 RXNE=1 ERR=0x3] DR=0xff [ascii=. dec=-1] CNT=0x41");
+
+        assert!(Key::parse("SR=:NOPE", &settings.registers).is_err());
+    }
+
+    #[test]
+    fn test_annotate_stream() {
+        let settings = Settings::default();
+        let annotator = Annotator::new(&settings, vec![], None);
+        let mut input = b"a 0x01\n\xffb 2\n".to_vec();
+        input.extend(b"1".repeat(MAX_LINE + 10));
+        input.extend(b"\nlast 3");
+        let mut out = vec![];
+        annotate(input.as_slice(), &mut out, &annotator).unwrap();
+        let out = String::from_utf8(out).unwrap();
+        let lines: Vec<&str> = out.lines().collect();
+        assert_eq!(lines[0], "a 0x01 [dec=1]");
+        assert_eq!(lines[1], "\u{fffd}b 2 [hex=0x00000002]");
+        assert_eq!(lines.last(), Some(&"last 3 [hex=0x00000003]"));
+
+        // a number or a character across the end of a part is not cut
+        for tail in ["0x1234", "12.5", "\u{e9}"] {
+            let mut input = b"a".repeat(MAX_LINE - 2);
+            input.extend(format!(" {tail} 7\n").as_bytes());
+            let mut out = vec![];
+            annotate(input.as_slice(), &mut out, &annotator).unwrap();
+            let line = String::from_utf8(input).unwrap();
+            assert_eq!(String::from_utf8(out).unwrap(), annotator.annotate_line(&line));
+        }
+
+        // the rest of a word longer than a part is not annotated
+        let mut input = b"a".repeat(MAX_LINE);
+        input.extend(b"12 3");
+        let mut out = vec![];
+        annotate(input.as_slice(), &mut out, &annotator).unwrap();
+        assert!(String::from_utf8(out).unwrap().ends_with("a12 3 [hex=0x00000003]"));
+    }
+
+}
@@ -0,0 +1,128 @@
+/// batch evaluation for bitwister, one expression per line
+/// 0xca7
+use std::io::{BufRead, Write};
+
+use crate::settings::Settings;
+
+/// options for a batch run
+pub struct BatchOptions {
+    /// prefix error messages with `<source>:<line>:`
+    pub line_numbers: bool,
+}
+
+/// outcome of a batch run
+pub struct BatchSummary {
+    /// number of expressions evaluated, comments and blank lines excluded
+    pub evaluated: usize,
+    /// line numbers and expressions of every failed evaluation
+    pub failures: Vec<(usize, String)>,
+}
+
+impl BatchSummary {
+
+    pub fn success(&self) -> bool {
+        self.failures.is_empty()
+    }
+
+    /// print the summary of failures to stderr
+    pub fn report(&self, source: &str) {
+        if self.success() {
+            return;
+        }
+        eprintln!("[bt]> {} of {} expressions in {source} failed:",
+            self.failures.len(), self.evaluated);
+        for (lineno, expr) in &self.failures {
+            eprintln!("    line {lineno}: {expr}");
+        }
+    }
+}
+
+/// strip a `#` comment and surrounding whitespace from a line, returns
+/// None if nothing is left to evaluate
+pub fn strip_line(line: &str) -> Option<&str> {
+    let line = match line.find('#') {
+        Some(idx) => &line[..idx],
+        None => line,
+    };
+    let line = line.trim();
+    if line.is_empty() {
+        None
+    } else {
+        Some(line)
+    }
+}
+
+/// evaluate every line of `input`, results go to `out`, errors to stderr.
+/// `source` names the input in error messages, e.g. the file name or stdin
+pub fn batch<R: BufRead, W: Write>(input: R, out: &mut W, source: &str,
+    settings: &mut Settings, opts: &BatchOptions) -> std::io::Result<BatchSummary> {
+
+    let mut summary = BatchSummary {
+        evaluated: 0,
+        failures: vec![],
+    };
+
+    for (idx, line) in input.lines().enumerate() {
+        let line = line?;
+        let lineno = idx + 1;
+
+        let expr = match strip_line(&line) {
+            Some(expr) => expr,
+            None => continue,
+        };
+
+        summary.evaluated += 1;
+
+        match settings.evaluate(expr) {
+            Ok((num, overflow)) => {
+                write!(out, "{}", settings.render(expr, &num, &overflow))?;
+            },
+            Err(failure) => {
+                let location = format!("{source}:{lineno}");
+                let location = opts.line_numbers.then_some(location.as_str());
+                let msg = settings.render_error(expr, &failure, location);
+                if settings.json {
+                    write!(out, "{msg}")?;
+                } else {
+                    eprint!("{msg}");
+                }
+                summary.failures.push((lineno, expr.to_string()));
+            }
+        }
+    }
+
+    Ok(summary)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_strip_line() {
+        assert_eq!(strip_line("1u8 + 1u8"), Some("1u8 + 1u8"));
+        assert_eq!(strip_line("  1u8 + 1u8  # add"), Some("1u8 + 1u8"));
+        assert_eq!(strip_line("# only a comment"), None);
+        assert_eq!(strip_line("   "), None);
+        assert_eq!(strip_line(""), None);
+    }
+
+    #[test]
+    fn test_batch() {
+        let input = "# header\n1u8 + 1u8\n\n1u8 x 1u8\n! 0xffu8 # not\n";
+        let mut out = vec![];
+        let mut settings = Settings::default();
+        let opts = BatchOptions { line_numbers: true };
+        let summary = batch(input.as_bytes(), &mut out, "test", &mut settings,
+            &opts).unwrap();
+
+        assert_eq!(summary.evaluated, 3);
+        assert_eq!(summary.failures, vec![(4, String::from("1u8 x 1u8"))]);
+        assert!(!summary.success());
+
+        let out = String::from_utf8(out).unwrap();
+        assert!(out.contains("[expr]> 1u8 + 1u8"));
+        assert!(out.contains("[expr]> ! 0xffu8"));
+    }
+
+}
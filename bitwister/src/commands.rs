@@ -0,0 +1,431 @@
+/// meta-commands and assignments of the bitwister repl
+/// 0xca7
+use std::fs::{self, File};
+use std::io::{BufRead, BufReader};
+use std::path::Path;
+
+use bitwister::{
+    convert::Notation,
+    format::{parse_formats, Format},
+    Operation,
+    OperationInfo,
+    Overflow,
+    Width,
+};
+
+use crate::batch::strip_line;
+use crate::config::parse_function;
+use crate::session::Outcome;
+use crate::settings::{
+    recall,
+    Settings,
+};
+
+/// what the repl does after a line was handled
+#[derive(Debug, PartialEq)]
+pub enum Action {
+    Continue,
+    Quit,
+}
+
+/// all meta-commands, for completion
+pub const COMMANDS: &[&str] = &[
+    ":width", ":signed", ":format", ":help", ":vars", ":clear", ":load",
+    ":save", ":transcript", ":def", ":history", ":regs", ":decode", ":explain",
+    ":convert", ":quit",
+];
+
+const META_HELP: &str = "\
+:width [u8|u16|u32|u64|off]  default width of literals without a suffix
+:signed [on|off]             show decimals as two's complement
+:format [hex,bin,...|off]    output views: hex, bin, dec, oct, ascii, reg
+:help [op]                   this help, or the help of a single operation
+:vars                        list all variables and constants
+:clear                       remove all variables
+:load <file>                 run a file of expressions and commands
+:save <file>                 save the session as a script that :load replays
+:transcript <file>           export the session as a Markdown table
+:def name params = expr      define a function, e.g. :def bit n = 1u32 << n
+:history [n]                 the last n results, recall one with $N
+:regs [file]                 list registers, or load definitions from a file
+:decode <reg> <expr>         show the fields of a register for a value
+:explain <expr>              show every step of an evaluation as a tree
+:convert <expr>              write expr in infix, prefix and postfix notation
+:quit                        leave the repl
+name = expr                  assign the result of expr to a variable";
+
+/// the help line of an operation, e.g.
+/// `[<<]  shift left      example: 1u32 << 10u32`
+fn operation_help(info: &OperationInfo) -> String {
+    let symbol = format!("[{}]", info.symbol);
+    let mut line = format!("{symbol:<5} {:<15}", info.description);
+    if let Some(example) = &info.example {
+        line.push_str(&format!(" example: {example}"));
+    }
+    line.trim_end().to_string()
+}
+
+/// a table of all operations with an example each, one per line
+pub fn operations_help() -> String {
+    Operation::infos().iter()
+        .map(operation_help)
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// a variable name starts with a letter or `_` and must not shadow the
+/// operations `r` and `e` or a width like `u8`
+pub fn is_var_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    match chars.next() {
+        Some(c) if c.is_ascii_alphabetic() || c == '_' => (),
+        _ => return false,
+    }
+    chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+        && Operation::new_from_str(name).is_err()
+        && name.parse::<Width>().is_err()
+}
+
+/// handle one line of input: a meta-command, an assignment or an expression
+pub fn handle_line(line: &str, settings: &mut Settings) -> Action {
+
+    let line = line.trim();
+
+    if line.is_empty() {
+        return Action::Continue;
+    }
+
+    if let Some(cmd) = line.strip_prefix(':') {
+        return match meta_command(cmd, settings) {
+            Ok(action) => {
+                if changes_session(cmd) {
+                    settings.session.record(line, line, Outcome::Command);
+                }
+                action
+            },
+            Err(e) => {
+                println!("[bt]> error: {e}");
+                Action::Continue
+            }
+        };
+    }
+
+    // name = expr
+    let (name, expr) = match line.split_once('=') {
+        Some((name, expr)) if is_var_name(name.trim()) => {
+            (Some(name.trim()), expr.trim())
+        },
+        _ => (None, line),
+    };
+
+    if let Some(name) = name {
+        if settings.constants().contains_key(name) || settings.aliases.contains_key(name)
+            || settings.functions.contains_key(name) {
+            println!("[bt]> error: {name} is a constant, alias or function");
+            return Action::Continue;
+        }
+    }
+
+    // `$N` depends on the history, a replay uses the recalled value
+    let script = recall(line, &settings.results);
+
+    match settings.evaluate(expr) {
+        Ok((num, overflow)) => {
+            print!("{}", settings.render(expr, &num, &overflow));
+            settings.results.push(line, &num.to_literal());
+            settings.session.record(line, &script, Outcome::Value {
+                literal: num.to_literal(),
+                overflow: matches!(overflow, Overflow::Occured),
+            });
+            // SAFETY: the name was checked by is_var_name
+            settings.set_var(name.unwrap_or("_"), num).unwrap();
+        },
+        Err(failure) => {
+            print!("{}", settings.render_error(expr, &failure, None));
+            settings.session.record(line, &script, Outcome::Error);
+        },
+    }
+
+    Action::Continue
+}
+
+/// whether the meta-command `cmd` changes the session and is replayed by
+/// a saved session, commands that only show something are not
+fn changes_session(cmd: &str) -> bool {
+    let (cmd, arg) = match cmd.split_once(char::is_whitespace) {
+        Some((cmd, arg)) => (cmd, arg.trim()),
+        None => (cmd, ""),
+    };
+    match cmd {
+        "width" | "w" | "signed" | "s" | "format" | "f" | "regs" => !arg.is_empty(),
+        "clear" | "c" | "def" => true,
+        _ => false,
+    }
+}
+
+/// run a meta-command, `cmd` is the line without the leading `:`
+fn meta_command(cmd: &str, settings: &mut Settings) -> Result<Action, String> {
+
+    let (cmd, arg) = match cmd.split_once(char::is_whitespace) {
+        Some((cmd, arg)) => (cmd, arg.trim()),
+        None => (cmd, ""),
+    };
+
+    match cmd {
+        "width" | "w" => match arg {
+            "" => println!("width: {}", settings.width
+                .map_or(String::from("off"), |w| w.to_string())),
+            "off" => settings.width = None,
+            _ => {
+                let width = arg.parse::<Width>()
+                    .map_err(|e| e.to_string())?;
+                settings.width = Some(width);
+            },
+        },
+        "signed" | "s" => match arg {
+            "" => println!("signed: {}", if settings.signed { "on" } else { "off" }),
+            "on" => settings.signed = true,
+            "off" => settings.signed = false,
+            _ => return Err(format!("expected on or off, got {arg}")),
+        },
+        "format" | "f" => match arg {
+            "" => {
+                let formats: Vec<String> = settings.formats.iter()
+                    .map(|f| f.to_string())
+                    .collect();
+                println!("format: {}", if formats.is_empty() {
+                    String::from("default")
+                } else {
+                    formats.join(",")
+                });
+            },
+            "off" | "default" => settings.formats.clear(),
+            _ => {
+                settings.formats = parse_formats(arg)
+                    .map_err(|e| e.to_string())?;
+            },
+        },
+        "help" | "h" => match arg {
+            "" => println!("{META_HELP}\n\navailable operations:\n{}", operations_help()),
+            op => match Operation::infos().iter().find(|info| info.symbol == op) {
+                Some(info) => println!("{}", operation_help(info)),
+                None => return Err(format!("unknown operation {op}")),
+            },
+        },
+        "vars" | "v" => {
+            for (name, value) in &settings.vars() {
+                println!("{name} = {}", value.to_literal());
+            }
+            for (name, value) in settings.constants() {
+                println!("{name} = {} (constant)", value.to_literal());
+            }
+        },
+        "clear" | "c" => settings.clear_vars(),
+        "load" | "l" => {
+            if arg.is_empty() {
+                return Err(String::from(":load requires a file"));
+            }
+            return load(arg, settings);
+        },
+        "save" => {
+            if arg.is_empty() {
+                return Err(String::from(":save requires a file"));
+            }
+            fs::write(arg, settings.session.script(&settings.vars()))
+                .map_err(|e| format!("can't save {arg}: {e}"))?;
+            println!("[bt]> saved session to {arg}");
+        },
+        "transcript" => {
+            if arg.is_empty() {
+                return Err(String::from(":transcript requires a file"));
+            }
+            let transcript = settings.session.transcript(settings.formats(), settings.signed);
+            fs::write(arg, transcript)
+                .map_err(|e| format!("can't write {arg}: {e}"))?;
+            println!("[bt]> wrote transcript to {arg}");
+        },
+        "def" => {
+            let (head, body) = arg.split_once('=')
+                .ok_or_else(|| String::from("expected :def name params = expr"))?;
+            let (name, function) = parse_function(head.trim(), body.trim())?;
+            if settings.constants().contains_key(&name) || settings.aliases.contains_key(&name) {
+                return Err(format!("{name} is a constant or alias"));
+            }
+            settings.functions.insert(name, function);
+        },
+        "history" => {
+            let n = match arg {
+                "" => 20,
+                n => n.parse::<usize>()
+                    .map_err(|_| format!("expected a number, got {n}"))?,
+            };
+            let entries = settings.results.entries();
+            let skip = entries.len().saturating_sub(n);
+            for (idx, entry) in entries.iter().enumerate().skip(skip) {
+                println!("${:<4} {}  ⇒ {}", idx + 1, entry.expr, entry.result);
+            }
+        },
+        "regs" => match arg {
+            "" => {
+                for register in &settings.registers {
+                    let fields: Vec<&str> = register.fields.iter()
+                        .map(|f| f.name.as_str())
+                        .collect();
+                    println!("{} {}: {}", register.name, register.width, fields.join(" "));
+                }
+            },
+            path => {
+                let count = settings.load_registers(Path::new(path))?;
+                println!("[bt]> loaded {count} registers from {path}");
+            },
+        },
+        "decode" | "d" => {
+            let (name, expr) = arg.split_once(char::is_whitespace)
+                .ok_or_else(|| String::from(":decode requires a register and a value"))?;
+            let width = settings.register(name)
+                .ok_or_else(|| format!("unknown register {name}"))?
+                .width;
+            let (num, _) = settings.evaluate_with_width(expr.trim(), Some(width))
+                .map_err(|failure| format!("failed to evaluate expression: {expr}: {failure}"))?;
+            // SAFETY: the register was found above
+            let register = settings.register(name).unwrap();
+            println!("{} = {}", register.name, Format::Hex.render(&num, false));
+            print!("{}", register.describe(&num));
+        },
+        "explain" | "x" => {
+            if arg.is_empty() {
+                return Err(String::from(":explain requires an expression"));
+            }
+            println!("{}", settings.explain(arg)?);
+        },
+        "convert" => {
+            if arg.is_empty() {
+                return Err(String::from(":convert requires an expression"));
+            }
+            println!("{}", settings.convert(arg, Notation::Infix)?);
+        },
+        "quit" | "q" => return Ok(Action::Quit),
+        _ => return Err(format!("unknown command :{cmd}, try :help")),
+    }
+
+    Ok(Action::Continue)
+}
+
+/// run every line of `path` as if it was entered in the repl
+fn load(path: &str, settings: &mut Settings) -> Result<Action, String> {
+    let file = File::open(path)
+        .map_err(|e| format!("can't open {path}: {e}"))?;
+    for line in BufReader::new(file).lines() {
+        let line = line.map_err(|e| format!("can't read {path}: {e}"))?;
+        if let Some(line) = strip_line(&line) {
+            if handle_line(line, settings) == Action::Quit {
+                return Ok(Action::Quit);
+            }
+        }
+    }
+    Ok(Action::Continue)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bitwister::IntType;
+
+    #[test]
+    fn test_is_var_name() {
+        assert!(is_var_name("x"));
+        assert!(is_var_name("CLK_DIV"));
+        assert!(is_var_name("_"));
+        assert!(!is_var_name("r"));
+        assert!(!is_var_name("e"));
+        assert!(!is_var_name("1x"));
+        assert!(!is_var_name("0xffu8"));
+        assert!(!is_var_name(""));
+        assert!(!is_var_name("u8"));
+    }
+
+    #[test]
+    fn test_operations_help() {
+        let help = operations_help();
+        assert_eq!(help.lines().count(), Operation::symbols().len());
+        assert_eq!(help.lines().nth(7),
+            Some("[<<]  shift left      example: 1u32 << 10u32"));
+    }
+
+    #[test]
+    fn test_meta_commands() {
+        let mut settings = Settings::default();
+        assert_eq!(handle_line(":width u16", &mut settings), Action::Continue);
+        assert_eq!(settings.width, Some(Width::U16));
+        assert_eq!(settings.prompt(), "[bt u16]> ");
+
+        handle_line(":signed on", &mut settings);
+        assert!(settings.signed);
+        assert_eq!(settings.prompt(), "[bt u16 signed]> ");
+
+        handle_line(":format dec,hex", &mut settings);
+        assert_eq!(settings.formats.len(), 2);
+
+        handle_line(":width u7", &mut settings);
+        assert_eq!(settings.width, Some(Width::U16));
+
+        assert_eq!(handle_line(":quit", &mut settings), Action::Quit);
+    }
+
+    #[test]
+    fn test_vars() {
+        let mut settings = Settings::default();
+        handle_line("x = 0xf0u8 | 0x0fu8", &mut settings);
+        assert_eq!(settings.vars().get("x").unwrap().to_literal(), "0xffu8");
+
+        handle_line("x & 0x0fu8", &mut settings);
+        assert_eq!(settings.vars().get("_").unwrap().to_literal(), "0x0fu8");
+
+        handle_line("$1 + 1", &mut settings);
+        assert_eq!(settings.vars().get("_").unwrap().to_literal(), "0x00u8");
+
+        handle_line(":clear", &mut settings);
+        assert!(settings.vars().is_empty());
+    }
+
+    #[test]
+    fn test_save_load() {
+        let mut settings = Settings::default();
+        for line in [":width u8", ":def low x = x & 0x0f", "x = 0xf3", "low x",
+            "$2 << 4", ":signed on", "_ + 0xff", "1 +"] {
+            handle_line(line, &mut settings);
+        }
+
+        let path = std::env::temp_dir()
+            .join(format!("bitwister-test-{}.bw", std::process::id()));
+        let path = path.to_str().unwrap();
+        handle_line(&format!(":save {path}"), &mut settings);
+
+        // a new session with an empty history replays to the same state
+        let mut replay = Settings::default();
+        handle_line(&format!(":load {path}"), &mut replay);
+        fs::remove_file(path).unwrap();
+
+        assert_eq!(replay.vars(), settings.vars());
+        assert_eq!(replay.vars().get("_"), Some(&IntType::U8(0x2f)));
+        assert_eq!(replay.width, Some(Width::U8));
+        assert!(replay.signed);
+        assert_eq!(replay.functions, settings.functions);
+    }
+
+    #[test]
+    fn test_constants() {
+        let mut settings = Settings::default();
+        settings.set_constant("EN", IntType::U8(1)).unwrap();
+
+        handle_line("EN = 2u8", &mut settings);
+        assert_eq!(settings.constants().get("EN"), Some(&IntType::U8(1)));
+        assert!(settings.vars().is_empty());
+
+        handle_line("EN | 2u8", &mut settings);
+        handle_line(":clear", &mut settings);
+        assert_eq!(settings.constants().len(), 1);
+        assert_eq!(settings.evaluate("EN").unwrap().0, IntType::U8(1));
+    }
+
+}
@@ -0,0 +1,397 @@
+/// the user configuration of bitwister, read from
+/// `$XDG_CONFIG_HOME/bitwister/config` or the file given with `--config`
+/// 0xca7
+use std::collections::BTreeMap;
+use std::env;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use clap::ValueEnum;
+
+use bitwister::{
+    format::{parse_formats, Format},
+    Operation,
+    Width,
+};
+
+use crate::commands::is_var_name;
+use crate::settings::{
+    map_words,
+    Settings,
+};
+use crate::ColorChoice;
+
+/// a problem in the config or a file it references, with its location.
+/// the offending line is skipped, the rest of the config still applies.
+#[derive(Debug, PartialEq)]
+pub struct ConfigError {
+    pub path: String,
+    /// 1-based line number
+    pub line: usize,
+    pub msg: String,
+}
+
+impl fmt::Display for ConfigError {
+
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}: {}", self.path, self.line, self.msg)
+    }
+
+}
+
+/// a user function, a call like `bit 4` is replaced by its body with the
+/// parameters replaced by the arguments
+#[derive(Debug, Clone, PartialEq)]
+pub struct Function {
+    pub params: Vec<String>,
+    pub body: String,
+}
+
+impl Function {
+
+    /// the body of the function called with `args`, None if the number of
+    /// arguments does not match
+    pub fn call(&self, args: &[&str]) -> Option<String> {
+        if args.len() != self.params.len() {
+            return None;
+        }
+        Some(map_words(&self.body, |word| {
+            let idx = self.params.iter().position(|p| p == word)?;
+            Some(args[idx].to_string())
+        }))
+    }
+}
+
+/// parse the definition of a function, `head` is its name followed by its
+/// parameters, `body` the expression it stands for
+pub fn parse_function(head: &str, body: &str) -> Result<(String, Function), String> {
+    let mut words = head.split_whitespace();
+    let name = words.next().unwrap_or_default();
+    let params: Vec<String> = words.map(|w| w.to_string()).collect();
+    if !is_var_name(name) {
+        return Err(format!("invalid function name {name}"));
+    }
+    if let Some(param) = params.iter().find(|p| !is_var_name(p)) {
+        return Err(format!("invalid parameter {param}"));
+    }
+    if body.is_empty() {
+        return Err(format!("function {name} has no body"));
+    }
+    Ok((name.to_string(), Function {
+        params,
+        body: body.to_string(),
+    }))
+}
+
+/// the sections of the config file
+enum Section {
+    Defaults,
+    Aliases,
+    Constants,
+    Functions,
+    Registers,
+    /// lines of an unknown section are skipped, its header was reported
+    Unknown,
+}
+
+/// a parsed config file. defaults are overridden by command line flags,
+/// constants and register files are resolved by `apply`.
+#[derive(Default)]
+pub struct Config {
+    pub width: Option<Width>,
+    pub signed: Option<bool>,
+    pub formats: Vec<Format>,
+    pub color: Option<ColorChoice>,
+    /// replaces `bt` in the repl prompt
+    pub prompt: Option<String>,
+    pub history_size: Option<usize>,
+    /// alias names and the operation symbols they stand for
+    pub aliases: BTreeMap<String, String>,
+    pub functions: BTreeMap<String, Function>,
+    /// line, name and expression of each constant
+    constants: Vec<(usize, String, String)>,
+    /// line and path of each register definition file
+    registers: Vec<(usize, PathBuf)>,
+    path: String,
+}
+
+/// `$XDG_CONFIG_HOME/bitwister/config`, or `~/.config/bitwister/config`
+pub fn config_path() -> Option<PathBuf> {
+    let base = match env::var_os("XDG_CONFIG_HOME") {
+        Some(dir) if !dir.is_empty() => PathBuf::from(dir),
+        _ => PathBuf::from(env::var_os("HOME")?).join(".config"),
+    };
+    Some(base.join("bitwister").join("config"))
+}
+
+/// `on`/`off` and their friends
+fn parse_bool(s: &str) -> Option<bool> {
+    match s {
+        "on" | "true" | "yes" => Some(true),
+        "off" | "false" | "no" => Some(false),
+        _ => None,
+    }
+}
+
+impl Config {
+
+    /// read and parse the config at `path`
+    pub fn load(path: &Path) -> io::Result<(Config, Vec<ConfigError>)> {
+        let input = fs::read_to_string(path)?;
+        Ok(Config::parse(&input, path))
+    }
+
+    /// parse a config, `path` is used for error locations and to resolve
+    /// relative register files. invalid lines are reported and skipped.
+    ///
+    /// ```text
+    /// [defaults]
+    /// width = u16
+    /// format = hex,bin
+    ///
+    /// [aliases]
+    /// xor = ^
+    ///
+    /// [constants]
+    /// EN = 0x01u32
+    ///
+    /// [functions]
+    /// bit n = 1u32 << n
+    ///
+    /// [registers]
+    /// stm32/rcc.regs
+    /// ```
+    pub fn parse(input: &str, path: &Path) -> (Config, Vec<ConfigError>) {
+
+        let mut config = Config {
+            path: path.display().to_string(),
+            ..Config::default()
+        };
+        let mut errors = vec![];
+        let mut section = None;
+
+        for (idx, line) in input.lines().enumerate() {
+            let line = match line.split_once('#') {
+                Some((line, _)) => line.trim(),
+                None => line.trim(),
+            };
+            if line.is_empty() {
+                continue;
+            }
+
+            if let Some(name) = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+                section = Some(match name.trim() {
+                    "defaults" => Section::Defaults,
+                    "aliases" => Section::Aliases,
+                    "constants" => Section::Constants,
+                    "functions" => Section::Functions,
+                    "registers" => Section::Registers,
+                    name => {
+                        errors.push(config.error(idx, format!("unknown section [{name}]")));
+                        Section::Unknown
+                    },
+                });
+                continue;
+            }
+
+            let result = match &section {
+                None => Err(String::from("expected a [section] first")),
+                Some(Section::Unknown) => Ok(()),
+                Some(Section::Registers) => {
+                    config.registers.push((idx, config.resolve(line)));
+                    Ok(())
+                },
+                Some(section) => match line.split_once('=') {
+                    Some((key, value)) => {
+                        config.parse_entry(idx, section, key.trim(), value.trim())
+                    },
+                    None => Err(format!("expected key = value, got {line}")),
+                },
+            };
+            if let Err(msg) = result {
+                errors.push(config.error(idx, msg));
+            }
+        }
+
+        (config, errors)
+    }
+
+    fn error(&self, idx: usize, msg: String) -> ConfigError {
+        ConfigError {
+            path: self.path.clone(),
+            line: idx + 1,
+            msg,
+        }
+    }
+
+    /// a register file path, relative to the directory of the config
+    fn resolve(&self, file: &str) -> PathBuf {
+        if let Some(rest) = file.strip_prefix("~/") {
+            if let Some(home) = env::var_os("HOME") {
+                return PathBuf::from(home).join(rest);
+            }
+        }
+        match Path::new(&self.path).parent() {
+            Some(dir) => dir.join(file),
+            None => PathBuf::from(file),
+        }
+    }
+
+    fn parse_entry(&mut self, idx: usize, section: &Section, key: &str, value: &str)
+        -> Result<(), String> {
+        match section {
+            Section::Defaults => match key {
+                "width" => {
+                    self.width = Some(value.parse::<Width>().map_err(|e| e.to_string())?);
+                },
+                "signed" => {
+                    self.signed = Some(parse_bool(value)
+                        .ok_or_else(|| format!("expected on or off, got {value}"))?);
+                },
+                "format" => {
+                    self.formats = parse_formats(value).map_err(|e| e.to_string())?;
+                },
+                "color" => {
+                    self.color = Some(ColorChoice::from_str(value, true)
+                        .map_err(|_| format!("expected auto, always or never, got {value}"))?);
+                },
+                "prompt" => {
+                    self.prompt = Some(value.trim_matches('"').to_string());
+                },
+                "history_size" => {
+                    self.history_size = Some(value.parse::<usize>()
+                        .map_err(|_| format!("expected a number, got {value}"))?);
+                },
+                _ => return Err(format!("unknown setting {key}")),
+            },
+            Section::Aliases => {
+                if Operation::new_from_str(value).is_err() {
+                    return Err(format!("{value} is not an operation"));
+                }
+                if key.is_empty() || key.contains(char::is_whitespace)
+                    || Operation::new_from_str(key).is_ok() {
+                    return Err(format!("invalid alias {key}"));
+                }
+                self.aliases.insert(key.to_string(), value.to_string());
+            },
+            Section::Constants => {
+                if !is_var_name(key) {
+                    return Err(format!("invalid constant name {key}"));
+                }
+                self.constants.push((idx, key.to_string(), value.to_string()));
+            },
+            Section::Functions => {
+                let (name, function) = parse_function(key, value)?;
+                self.functions.insert(name, function);
+            },
+            Section::Registers | Section::Unknown => (),
+        }
+        Ok(())
+    }
+
+    /// define the aliases, functions and constants of the config in
+    /// `settings` and load its register files. constants are evaluated in
+    /// order, so they can use the ones defined before.
+    pub fn apply(&self, settings: &mut Settings) -> Vec<ConfigError> {
+        let mut errors = vec![];
+
+        settings.aliases.extend(self.aliases.clone());
+        settings.functions.extend(self.functions.clone());
+
+        for (idx, name, expr) in &self.constants {
+            match settings.evaluate(expr) {
+                Ok((value, _)) => if let Err(e) = settings.set_constant(name, value) {
+                    errors.push(self.error(*idx, format!("failed to define constant {name}: {e}")));
+                },
+                Err(failure) => errors.push(self.error(*idx,
+                    format!("failed to evaluate constant {name}: {expr}: {failure}"))),
+            }
+        }
+
+        for (idx, path) in &self.registers {
+            if let Err(msg) = settings.load_registers(path) {
+                errors.push(self.error(*idx, msg));
+            }
+        }
+
+        errors
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CONFIG: &str = "\
+# team defaults
+[defaults]
+width = u16
+signed = on
+format = hex,dec
+prompt = \"fw\"
+colour = never
+
+[aliases]
+xor = ^
+shl = <<
+nand = !&
+
+[constants]
+EN = 0x01
+CLK_DIV = 0x13 | EN
+BROKEN = 1 +
+
+[functions]
+bit n = 1u32 << n
+[vendor]
+foo = bar
+";
+
+    #[test]
+    fn test_parse_config() {
+        let (config, errors) = Config::parse(CONFIG, Path::new("/etc/bt/config"));
+        assert_eq!(config.width, Some(Width::U16));
+        assert_eq!(config.signed, Some(true));
+        assert_eq!(config.formats, vec![Format::Hex, Format::Dec]);
+        assert_eq!(config.prompt.as_deref(), Some("fw"));
+        assert_eq!(config.aliases.get("xor").map(String::as_str), Some("^"));
+        assert_eq!(config.constants.len(), 3);
+
+        let errors: Vec<String> = errors.iter().map(|e| e.to_string()).collect();
+        assert_eq!(errors, vec![
+            "/etc/bt/config:7: unknown setting colour",
+            "/etc/bt/config:12: !& is not an operation",
+            "/etc/bt/config:21: unknown section [vendor]",
+        ]);
+    }
+
+    #[test]
+    fn test_apply_config() {
+        let (config, _) = Config::parse(CONFIG, Path::new("config"));
+        let mut settings = Settings::default();
+        settings.width = config.width;
+        let errors = config.apply(&mut settings);
+
+        assert_eq!(settings.constants().get("CLK_DIV").unwrap().to_literal(), "0x0013u16");
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].to_string(), "config:17: failed to evaluate constant BROKEN: 1 +: unexpected end of expression at offset 3");
+
+        let (num, _) = settings.evaluate("CLK_DIV xor EN").unwrap();
+        assert_eq!(num.to_literal(), "0x0012u16");
+        let (num, _) = settings.evaluate("bit 4").unwrap();
+        assert_eq!(num.to_literal(), "0x00000010u32");
+    }
+
+    #[test]
+    fn test_function_call() {
+        let f = Function {
+            params: vec![String::from("x"), String::from("n")],
+            body: String::from("(x >> n) & 0xf"),
+        };
+        assert_eq!(f.call(&["0xf0u8", "4u8"]).unwrap(), "(0xf0u8 >> 4u8) & 0xf");
+        assert!(f.call(&["1"]).is_none());
+    }
+
+}
@@ -0,0 +1,421 @@
+/// a C interface to the evaluator, for test harnesses in C or Python that
+/// can't link a Rust crate. the header is include/bitwister.h, generated
+/// with `cbindgen --config cbindgen.toml --output include/bitwister.h`.
+/// 0xca7
+use std::ffi::{c_char, CStr};
+use std::ptr;
+
+use crate::{
+    format::Format,
+    EvalError,
+    Evaluator,
+    IntType,
+    Overflow,
+    Width,
+};
+
+/// why a call failed, `Ok` if it didn't
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BwError {
+    Ok = 0,
+    /// a null pointer, a string that is not UTF-8, or an unsupported width
+    InvalidArgument,
+    Empty,
+    InvalidToken,
+    InvalidLiteral,
+    UnknownVariable,
+    UnexpectedEnd,
+    Unexpected,
+    WidthMismatch,
+    DivisionByZero,
+    Operation,
+    TooDeep,
+}
+
+impl BwError {
+
+    /// every error, in the order of their codes
+    const ALL: [BwError; 12] = [
+        BwError::Ok,
+        BwError::InvalidArgument,
+        BwError::Empty,
+        BwError::InvalidToken,
+        BwError::InvalidLiteral,
+        BwError::UnknownVariable,
+        BwError::UnexpectedEnd,
+        BwError::Unexpected,
+        BwError::WidthMismatch,
+        BwError::DivisionByZero,
+        BwError::Operation,
+        BwError::TooDeep,
+    ];
+
+}
+
+impl TryFrom<u32> for BwError {
+    type Error = u32;
+
+    /// the error of a code from C, which can be any number
+    fn try_from(code: u32) -> Result<Self, u32> {
+        BwError::ALL.get(code as usize).copied().ok_or(code)
+    }
+}
+
+impl From<&EvalError> for BwError {
+    fn from(error: &EvalError) -> Self {
+        match error {
+            EvalError::Empty => BwError::Empty,
+            EvalError::InvalidToken(_) => BwError::InvalidToken,
+            EvalError::InvalidLiteral(_) => BwError::InvalidLiteral,
+            EvalError::UnknownVariable(_) => BwError::UnknownVariable,
+            EvalError::InvalidName(_) => BwError::InvalidArgument,
+            EvalError::UnexpectedEnd => BwError::UnexpectedEnd,
+            EvalError::Unexpected(_) => BwError::Unexpected,
+            EvalError::WidthMismatch(..) => BwError::WidthMismatch,
+            EvalError::DivisionByZero => BwError::DivisionByZero,
+            EvalError::Operation { .. } => BwError::Operation,
+            EvalError::TooDeep => BwError::TooDeep,
+        }
+    }
+}
+
+/// whether an evaluation overflowed
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BwOverflow {
+    /// none of the operations can overflow
+    NotPossible = 0,
+    None,
+    Occurred,
+}
+
+impl From<Overflow> for BwOverflow {
+    fn from(overflow: Overflow) -> Self {
+        match overflow {
+            Overflow::NotPossible => BwOverflow::NotPossible,
+            Overflow::NoOverflow => BwOverflow::None,
+            Overflow::Occured => BwOverflow::Occurred,
+        }
+    }
+}
+
+/// the result of `bw_evaluate`. if `error` is not `Ok`, `value` and
+/// `width` are 0 and `error_start..error_end` is the byte range of the
+/// expression that caused the error.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BwResult {
+    pub value: u64,
+    /// bits of the value: 8, 16, 32 or 64
+    pub width: u32,
+    /// a `BwOverflow`, as a plain number because C can store any in it
+    pub overflow: u32,
+    /// a `BwError`, as a plain number too
+    pub error: u32,
+    pub error_start: usize,
+    pub error_end: usize,
+}
+
+impl BwResult {
+
+    fn error(error: BwError, start: usize, end: usize) -> BwResult {
+        BwResult {
+            value: 0,
+            width: 0,
+            overflow: BwOverflow::NotPossible as u32,
+            error: error as u32,
+            error_start: start,
+            error_end: end,
+        }
+    }
+}
+
+/// an evaluator, created with `bw_evaluator_new`
+pub struct BwEvaluator(Evaluator);
+
+/// the width of `bits`, if it is 8, 16, 32 or 64
+fn width(bits: u32) -> Option<Width> {
+    match bits {
+        8 => Some(Width::U8),
+        16 => Some(Width::U16),
+        32 => Some(Width::U32),
+        64 => Some(Width::U64),
+        _ => None,
+    }
+}
+
+/// the UTF-8 string `s` points to
+///
+/// # Safety
+///
+/// `s` is null or points to a NUL terminated string that outlives `'a`
+unsafe fn str<'a>(s: *const c_char) -> Option<&'a str> {
+    if s.is_null() {
+        return None;
+    }
+    // SAFETY: not null and NUL terminated, see above
+    unsafe { CStr::from_ptr(s) }.to_str().ok()
+}
+
+/// create an evaluator with the default width u32 and no variables, free
+/// it with `bw_evaluator_free`
+#[no_mangle]
+pub extern "C" fn bw_evaluator_new() -> *mut BwEvaluator {
+    Box::into_raw(Box::new(BwEvaluator(Evaluator::new())))
+}
+
+/// free an evaluator, null is ignored
+///
+/// # Safety
+///
+/// `evaluator` is null or was returned by `bw_evaluator_new` and is not
+/// used afterwards
+#[no_mangle]
+pub unsafe extern "C" fn bw_evaluator_free(evaluator: *mut BwEvaluator) {
+    if !evaluator.is_null() {
+        // SAFETY: created by `Box::into_raw` in `bw_evaluator_new`
+        drop(unsafe { Box::from_raw(evaluator) });
+    }
+}
+
+/// set the width of literals without a `u` suffix to `bits`
+///
+/// # Safety
+///
+/// `evaluator` is null or was returned by `bw_evaluator_new`
+#[no_mangle]
+pub unsafe extern "C" fn bw_evaluator_set_width(evaluator: *mut BwEvaluator, bits: u32)
+    -> BwError {
+    // SAFETY: null or a valid evaluator, see above
+    match (unsafe { evaluator.as_mut() }, width(bits)) {
+        (Some(evaluator), Some(width)) => {
+            evaluator.0.set_width(width);
+            BwError::Ok
+        },
+        _ => BwError::InvalidArgument,
+    }
+}
+
+/// set the variable `name` to `value`, truncated to `bits`. a name that is
+/// not a word, or is an operation or a width, is an invalid argument.
+///
+/// # Safety
+///
+/// `evaluator` is null or was returned by `bw_evaluator_new`, `name` is
+/// null or a NUL terminated string
+#[no_mangle]
+pub unsafe extern "C" fn bw_evaluator_set_var(evaluator: *mut BwEvaluator, name: *const c_char,
+    value: u64, bits: u32) -> BwError {
+    // SAFETY: null or valid pointers, see above
+    match (unsafe { evaluator.as_mut() }, unsafe { str(name) }, width(bits)) {
+        (Some(evaluator), Some(name), Some(width)) => {
+            match evaluator.0.set_var(name, IntType::from_u64_truncating(value, width).0) {
+                Ok(()) => BwError::Ok,
+                Err(e) => BwError::from(&e),
+            }
+        },
+        _ => BwError::InvalidArgument,
+    }
+}
+
+/// evaluate `expr` into `result`, returns the error of the result
+///
+/// # Safety
+///
+/// `evaluator` is null or was returned by `bw_evaluator_new`, `expr` is
+/// null or a NUL terminated string, `result` is null or points to a
+/// `BwResult`
+#[no_mangle]
+pub unsafe extern "C" fn bw_evaluate(evaluator: *const BwEvaluator, expr: *const c_char,
+    result: *mut BwResult) -> BwError {
+    // SAFETY: null or valid pointers, see above
+    let (evaluator, expr, result) = match unsafe {
+        (evaluator.as_ref(), str(expr), result.as_mut())
+    } {
+        (Some(evaluator), Some(expr), Some(result)) => (evaluator, expr, result),
+        (_, _, Some(result)) => {
+            *result = BwResult::error(BwError::InvalidArgument, 0, 0);
+            return BwError::InvalidArgument;
+        },
+        _ => return BwError::InvalidArgument,
+    };
+
+    *result = match evaluator.0.evaluate_with_span(expr) {
+        Ok(evaluation) => BwResult {
+            value: evaluation.value.as_u64(),
+            width: evaluation.value.width().bits() as u32,
+            overflow: BwOverflow::from(evaluation.overflow) as u32,
+            error: BwError::Ok as u32,
+            error_start: 0,
+            error_end: 0,
+        },
+        Err((error, span)) => BwResult::error((&error).into(), span.start, span.end),
+    };
+    // SAFETY: written above
+    BwError::try_from(result.error).unwrap()
+}
+
+/// render `result` in `format` (`hex`, `bin`, `dec`, `oct`, `ascii` or
+/// `reg`, null for hex) into `buf`, `is_signed` shows `dec` as two's
+/// complement. like `snprintf`, at most `len - 1` bytes and a NUL are
+/// written and the length of the whole text is returned.
+/// a failed result renders as its error message. returns 0 and writes
+/// nothing if `result` is null or invalid, or the format is unknown.
+///
+/// # Safety
+///
+/// `result` is null or points to a `BwResult`, `format` is null or a NUL
+/// terminated string, `buf` is null or points to `len` writable bytes
+#[no_mangle]
+pub unsafe extern "C" fn bw_render(result: *const BwResult, format: *const c_char,
+    is_signed: bool, buf: *mut c_char, len: usize) -> usize {
+    // SAFETY: null or a valid result, see above
+    let result = match unsafe { result.as_ref() } {
+        Some(result) => result,
+        None => return 0,
+    };
+    let format = match unsafe { str(format) } {
+        Some(format) => match format.parse::<Format>() {
+            Ok(format) => format,
+            Err(_) => return 0,
+        },
+        None if format.is_null() => Format::Hex,
+        None => return 0,
+    };
+
+    let text = match (BwError::try_from(result.error), width(result.width)) {
+        (Ok(BwError::Ok), Some(width)) => {
+            format.render(&IntType::from_u64_truncating(result.value, width).0, is_signed)
+        },
+        (Ok(BwError::Ok), None) | (Err(_), _) => return 0,
+        (Ok(error), _) => error_message(error).to_string_lossy().into_owned(),
+    };
+    if !buf.is_null() && len > 0 {
+        let n = text.len().min(len - 1);
+        // SAFETY: `buf` has room for `len` bytes, see above
+        unsafe {
+            ptr::copy_nonoverlapping(text.as_ptr(), buf.cast(), n);
+            *buf.add(n) = 0;
+        }
+    }
+    text.len()
+}
+
+fn error_message(error: BwError) -> &'static CStr {
+    match error {
+        BwError::Ok => c"ok",
+        BwError::InvalidArgument => c"error: invalid argument",
+        BwError::Empty => c"error: empty expression",
+        BwError::InvalidToken => c"error: invalid token",
+        BwError::InvalidLiteral => c"error: invalid number",
+        BwError::UnknownVariable => c"error: unknown variable",
+        BwError::UnexpectedEnd => c"error: unexpected end of expression",
+        BwError::Unexpected => c"error: unexpected token",
+        BwError::WidthMismatch => c"error: operands of different widths",
+        BwError::DivisionByZero => c"error: division by zero",
+        BwError::Operation => c"error: operation failed",
+        BwError::TooDeep => c"error: nested too deep",
+    }
+}
+
+/// a static description of the `BwError` `error`, null if it is none
+#[no_mangle]
+pub extern "C" fn bw_error_message(error: u32) -> *const c_char {
+    match BwError::try_from(error) {
+        Ok(error) => error_message(error).as_ptr(),
+        Err(_) => ptr::null(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn render(result: &BwResult, format: &CStr) -> String {
+        let mut buf = [0 as c_char; 64];
+        // SAFETY: valid pointers and the length of `buf`
+        let n = unsafe { bw_render(result, format.as_ptr(), false, buf.as_mut_ptr(), buf.len()) };
+        // SAFETY: `bw_render` terminates the text
+        let text = unsafe { CStr::from_ptr(buf.as_ptr()) }.to_str().unwrap().to_string();
+        assert_eq!(n, text.len());
+        text
+    }
+
+    #[test]
+    fn test_evaluate() {
+        let evaluator = bw_evaluator_new();
+        let mut result = BwResult::error(BwError::Ok, 0, 0);
+        // SAFETY: all pointers are valid
+        unsafe {
+            assert_eq!(bw_evaluator_set_var(evaluator, c"EN".as_ptr(), 0x101, 8), BwError::Ok);
+            for name in [c"+", c"0x1", c"u8", c""] {
+                assert_eq!(bw_evaluator_set_var(evaluator, name.as_ptr(), 1, 8),
+                    BwError::InvalidArgument);
+            }
+            assert_eq!(bw_evaluator_set_width(evaluator, 12), BwError::InvalidArgument);
+            assert_eq!(bw_evaluate(evaluator, c"0xffu8 + EN".as_ptr(), &mut result), BwError::Ok);
+        }
+        assert_eq!((result.value, result.width, result.overflow),
+            (0, 8, BwOverflow::Occurred as u32));
+        assert_eq!(render(&result, c"bin"), "b00000000");
+
+        // SAFETY: all pointers are valid
+        unsafe {
+            assert_eq!(bw_evaluate(evaluator, c"1 + (2 ^ x)".as_ptr(), &mut result),
+                BwError::UnknownVariable);
+        }
+        assert_eq!((result.error_start, result.error_end), (9, 10));
+
+        // SAFETY: all pointers but `expr` are valid
+        unsafe {
+            assert_eq!(bw_evaluate(evaluator, ptr::null(), &mut result), BwError::InvalidArgument);
+            bw_evaluator_free(evaluator);
+        }
+        assert_eq!((result.error_start, result.error_end), (0, 0));
+        assert_eq!(render(&result, c"hex"), "error: invalid argument");
+    }
+
+    #[test]
+    fn test_render() {
+        let result = BwResult {
+            value: 0xcafe,
+            width: 16,
+            overflow: BwOverflow::NotPossible as u32,
+            error: BwError::Ok as u32,
+            error_start: 0,
+            error_end: 0,
+        };
+        assert_eq!(render(&result, c"hex"), "0xcafe");
+        let mut buf = [0x7f as c_char; 4];
+        // SAFETY: valid pointers and the length of `buf`
+        unsafe {
+            assert_eq!(bw_render(&result, ptr::null(), false, buf.as_mut_ptr(), buf.len()), 6);
+            assert_eq!(CStr::from_ptr(buf.as_ptr()), c"0xc");
+            assert_eq!(bw_render(&result, c"nope".as_ptr(), false, buf.as_mut_ptr(), 4), 0);
+            assert_eq!(bw_render(&result, c"dec".as_ptr(), true, ptr::null_mut(), 0), 6);
+        }
+
+        // codes from C are checked
+        let mut buf = [0 as c_char; 64];
+        for (error, width) in [(BwError::ALL.len() as u32, 16), (u32::MAX, 16), (0, 12)] {
+            let invalid = BwResult { error, width, ..result };
+            // SAFETY: valid pointers and the length of `buf`
+            let n = unsafe { bw_render(&invalid, ptr::null(), false, buf.as_mut_ptr(), 64) };
+            assert_eq!(n, 0);
+        }
+    }
+
+    #[test]
+    fn test_error_codes() {
+        for (code, error) in BwError::ALL.iter().enumerate() {
+            assert_eq!(*error as u32, code as u32);
+            assert_eq!(BwError::try_from(code as u32), Ok(*error));
+            assert!(!bw_error_message(code as u32).is_null());
+        }
+        assert_eq!(BwError::try_from(12), Err(12));
+        assert!(bw_error_message(12).is_null());
+        assert!(bw_error_message(u32::MAX).is_null());
+    }
+
+}
@@ -0,0 +1,133 @@
+/// output formats for evaluated values
+/// 0xca7
+use std::fmt;
+use std::str::FromStr;
+
+use crate::IntType;
+
+/// when an unknown output format is requested
+#[derive(Debug)]
+pub enum FormatError {
+    UnknownFormat,
+}
+
+impl fmt::Display for FormatError {
+
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FormatError::UnknownFormat => {
+                write!(f, "error: format is not hex, bin, dec, oct, ascii or reg")
+            },
+        }
+    }
+
+}
+
+impl std::error::Error for FormatError {}
+
+/// a view of a value, for example hex or binary
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Format {
+    Hex,
+    Bin,
+    Dec,
+    Oct,
+    Ascii,
+    Reg, // register view
+}
+
+impl Format {
+
+    /// the views shown when no format is selected
+    pub const DEFAULT: [Format; 4] = [
+        Format::Hex, Format::Bin, Format::Dec, Format::Ascii
+    ];
+
+    /// the name of this format, as accepted by `from_str`
+    pub fn name(&self) -> &'static str {
+        match self {
+            Format::Hex => "hex",
+            Format::Bin => "bin",
+            Format::Dec => "dec",
+            Format::Oct => "oct",
+            Format::Ascii => "ascii",
+            Format::Reg => "reg",
+        }
+    }
+
+    /// render `value` in this format. if `signed` is set, the decimal view
+    /// interprets the value as two's complement.
+    pub fn render(&self, value: &IntType, signed: bool) -> String {
+        let bits = value.width().bits();
+        let v = value.as_u64();
+        match self {
+            Format::Hex => format!("0x{:0w$x}", v, w = bits / 4),
+            Format::Bin => format!("b{:0w$b}", v, w = bits),
+            Format::Dec if signed => format!("{}", value.as_i64()),
+            Format::Dec => format!("{}", v),
+            Format::Oct => format!("0o{:o}", v),
+            Format::Ascii => value.to_ascii(),
+            Format::Reg => value.register_view(),
+        }
+    }
+}
+
+impl FromStr for Format {
+    type Err = FormatError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "hex" | "x" => Ok(Format::Hex),
+            "bin" | "b" => Ok(Format::Bin),
+            "dec" | "d" => Ok(Format::Dec),
+            "oct" | "o" => Ok(Format::Oct),
+            "ascii" | "a" => Ok(Format::Ascii),
+            "reg" | "r" => Ok(Format::Reg),
+            _ => Err(FormatError::UnknownFormat),
+        }
+    }
+}
+
+impl fmt::Display for Format {
+
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+
+}
+
+/// parse a comma separated list of formats, e.g. `hex,bin`
+pub fn parse_formats(s: &str) -> Result<Vec<Format>, FormatError> {
+    s.split(',')
+        .map(|f| f.trim().parse::<Format>())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn format_render() {
+        let v = IntType::U8(0xfe);
+        assert_eq!(Format::Hex.render(&v, false), "0xfe");
+        assert_eq!(Format::Bin.render(&v, false), "b11111110");
+        assert_eq!(Format::Dec.render(&v, false), "254");
+        assert_eq!(Format::Dec.render(&v, true), "-2");
+        assert_eq!(Format::Oct.render(&v, false), "0o376");
+
+        let v = IntType::U32(0x41);
+        assert_eq!(Format::Hex.render(&v, false), "0x00000041");
+        assert_eq!(Format::Ascii.render(&v, false), "...A");
+    }
+
+    #[test]
+    fn format_parse() {
+        assert_eq!(parse_formats("hex,bin").unwrap(),
+            vec![Format::Hex, Format::Bin]);
+        assert_eq!(parse_formats("dec").unwrap(), vec![Format::Dec]);
+        assert!(parse_formats("hex,foo").is_err());
+        assert!(parse_formats("").is_err());
+    }
+
+}
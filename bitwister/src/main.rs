@@ -1,69 +1,592 @@
-// FIXME: remove all panic!("error") in lib.rs, replace with "return None;"
 
 use bitwister::{
-    evaluate,
-    show_help
+    convert::{ConvertError, Notation},
+    format::Format,
+    IntType,
+    Overflow,
+    Width,
 };
 
 mod repl;
 use repl::repl;
 
-use std::env;
+mod batch;
+use batch::{
+    batch,
+    BatchOptions,
+};
 
-fn logo() {
+mod settings;
+use settings::Settings;
+
+mod commands;
+
+mod annotate;
+use annotate::{
+    annotate,
+    Annotator,
+    Key,
+};
+
+mod tui;
+
+mod server;
+use server::{
+    serve,
+    Server,
+};
+
+mod script;
+use script::run_script;
 
-    let logo = r#"
-  ___ _ _____        _    _           
- | _ |_)_   _|_ __ _(_)__| |_ ___ _ _ 
+mod session;
+
+mod config;
+use config::{
+    config_path,
+    Config,
+};
+
+use btrepl::history::HistoryConfig;
+
+use clap::{
+    Args,
+    CommandFactory,
+    FromArgMatches,
+    Parser,
+    Subcommand,
+    ValueEnum,
+};
+
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, IsTerminal};
+use std::path::PathBuf;
+use std::process::exit;
+
+/// everything evaluated fine
+const EXIT_SUCCESS: i32 = 0;
+/// at least one expression failed to evaluate
+const EXIT_EVAL_ERROR: i32 = 1;
+/// invalid arguments or unreadable input, same code clap uses
+const EXIT_USAGE_ERROR: i32 = 2;
+
+const LOGO: &str = r#"
+  ___ _ _____        _    _
+ | _ |_)_   _|_ __ _(_)__| |_ ___ _ _
  | _ \ | | | \ V  V / (_-<  _/ -_) '_|
- |___/_| |_|  \_/\_/|_/__/\__\___|_|  
+ |___/_| |_|  \_/\_/|_/__/\__\___|_|
  - the simple bit calculator for your
    bit twisting needs.
     "#;
 
-    println!("{logo}");
+fn logo() {
+    println!("{LOGO}");
 }
 
-fn main() {
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
+pub enum ColorChoice {
+    Auto,
+    Always,
+    Never,
+}
+
+#[derive(Parser)]
+#[command(name = "bitwister", version, before_help = LOGO,
+    about = "the simple bit calculator for your bit twisting needs")]
+struct Cli {
+
+    #[command(flatten)]
+    opts: Opts,
+
+    #[command(subcommand)]
+    command: Option<Command>,
+
+    /// enter interactive mode, same as `bitwister repl`
+    #[arg(short, long)]
+    interactive: bool,
+
+    /// evaluate a file, same as `bitwister batch FILE`
+    #[arg(short, long, value_name = "FILE")]
+    file: Option<PathBuf>,
+
+    /// the expression to evaluate, same as `bitwister eval EXPR`. words
+    /// that start with `-` follow a `--`
+    expr: Vec<String>,
+}
 
-    let mut args: Vec<String> = env::args().collect();
+#[derive(Args)]
+struct Opts {
 
-    if args.len() < 2 {
-        logo();
-        eprintln!("bitwister: not enough args, run [bitwister h] for help");
-        std::process::exit(1);
+    /// default width of unsuffixed literals: u8, u16, u32 (default) or u64
+    #[arg(short, long, global = true)]
+    width: Option<Width>,
+
+    /// show decimals as two's complement signed values
+    #[arg(short, long, global = true)]
+    signed: bool,
+
+    /// comma separated output views: hex, bin, dec, oct, ascii, reg
+    #[arg(short = 'F', long = "format", value_delimiter = ',', global = true)]
+    formats: Vec<Format>,
+
+    /// print only the value in the first format, e.g. for $(...)
+    #[arg(short, long, global = true)]
+    quiet: bool,
+
+    /// when to colour the output [default: auto]
+    #[arg(long, value_enum, global = true)]
+    color: Option<ColorChoice>,
+
+    /// print each result as a JSON object
+    #[arg(long, global = true)]
+    json: bool,
+
+    /// prefix batch errors with the line number
+    #[arg(short = 'n', long, global = true)]
+    line_numbers: bool,
+
+    /// number of repl history entries to keep, 0 disables the history
+    #[arg(long, value_name = "N", global = true)]
+    history_size: Option<usize>,
+
+    /// read the config from FILE instead of ~/.config/bitwister/config
+    #[arg(long, value_name = "FILE", global = true)]
+    config: Option<PathBuf>,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// enter interactive mode
+    Repl,
+    /// evaluate a single expression
+    Eval {
+        #[arg(required = true, trailing_var_arg = true, allow_hyphen_values = true)]
+        expr: Vec<String>,
+    },
+    /// show every step of evaluating an expression as a tree
+    Explain {
+        #[arg(required = true, trailing_var_arg = true, allow_hyphen_values = true)]
+        expr: Vec<String>,
+    },
+    /// write an expression in infix, prefix and postfix notation
+    Convert {
+        /// the notation of the expression: infix, prefix or postfix
+        #[arg(long, default_value = "infix", value_parser = parse_notation)]
+        from: Notation,
+        #[arg(required = true, trailing_var_arg = true, allow_hyphen_values = true)]
+        expr: Vec<String>,
+    },
+    /// evaluate one expression per line, reads stdin without FILE or with -
+    Batch {
+        file: Option<PathBuf>,
+    },
+    /// run .bw scripts with let, assert and expect_overflow statements
+    Run {
+        #[arg(required = true)]
+        files: Vec<PathBuf>,
+    },
+    /// toggle the bits of a value in a full-screen view
+    Tui {
+        /// the value to start with, an expression, 0 if not given
+        value: Option<String>,
+        /// load register definitions from FILE
+        #[arg(long, value_name = "FILE")]
+        regs: Option<PathBuf>,
+        /// show the fields of this register
+        #[arg(long, value_name = "NAME")]
+        register: Option<String>,
+    },
+    /// copy stdin to stdout with the numbers in it decoded, e.g. for logs
+    Annotate {
+        /// only decode numbers right after KEY, e.g. SR=, with the fields
+        /// of REGISTER if given
+        #[arg(short, long = "key", value_name = "KEY[:REGISTER]")]
+        keys: Vec<String>,
+        /// load register definitions from FILE
+        #[arg(long, value_name = "FILE")]
+        regs: Option<PathBuf>,
+        /// show the fields of this register for every number
+        #[arg(long, value_name = "NAME")]
+        register: Option<String>,
+    },
+    /// answer JSON-RPC requests on stdin, e.g. for hover-to-decode in an
+    /// editor
+    Server,
+    /// show a number as a register
+    Reg {
+        /// e.g. 0xcafeu16, or 0xcafe with --width
+        value: String,
+    },
+}
+
+impl Opts {
+
+    /// the settings of the flags, defaults not given as a flag are taken
+    /// from `config`
+    fn settings(&self, config: &Config) -> Settings {
+        let color = match self.color.or(config.color).unwrap_or(ColorChoice::Auto) {
+            ColorChoice::Always => true,
+            ColorChoice::Never => false,
+            ColorChoice::Auto => {
+                io::stdout().is_terminal()
+                    && std::env::var_os("NO_COLOR").is_none()
+            },
+        };
+        let mut settings = Settings::default();
+        settings.width = self.width.or(config.width);
+        settings.signed = self.signed || config.signed.unwrap_or(false);
+        settings.formats = if self.formats.is_empty() {
+            config.formats.clone()
+        } else {
+            self.formats.clone()
+        };
+        settings.quiet = self.quiet;
+        settings.color = color;
+        settings.json = self.json;
+        settings.prompt = config.prompt.clone();
+        settings
     }
+}
 
-    if args[1] == "-h" {
-        logo();
-        show_help();
-        std::process::exit(1);
+/// read the config given with `--config`, or the default one if it exists.
+/// errors in the config are reported and skipped, a config that can't be
+/// read at all is only an error if it was given explicitly.
+fn load_config(path: Option<&PathBuf>) -> Result<Config, i32> {
+    let (config, errors) = match path {
+        Some(path) => Config::load(path).map_err(|e| {
+            eprintln!("bitwister: can't read config {}: {e}", path.display());
+            EXIT_USAGE_ERROR
+        })?,
+        None => match config_path() {
+            Some(path) if path.exists() => match Config::load(&path) {
+                Ok(config) => config,
+                Err(e) => {
+                    eprintln!("bitwister: can't read config {}: {e}", path.display());
+                    (Config::default(), vec![])
+                }
+            },
+            _ => (Config::default(), vec![]),
+        },
+    };
+    for e in errors {
+        eprintln!("bitwister: {e}");
     }
+    Ok(config)
+}
 
-    if args[1] == "-i" {
-        match repl() {
-            Ok(()) => {
-                std::process::exit(0);
+/// evaluate `input` line by line, returns the exit code
+fn run_batch<R: BufRead>(input: R, source: &str, settings: &mut Settings,
+    opts: &BatchOptions) -> i32 {
+    let mut stdout = io::stdout().lock();
+    match batch(input, &mut stdout, source, settings, opts) {
+        Ok(summary) => {
+            summary.report(source);
+            if summary.success() { EXIT_SUCCESS } else { EXIT_EVAL_ERROR }
+        },
+        Err(e) => {
+            eprintln!("bitwister: error reading {source}: {e}");
+            EXIT_USAGE_ERROR
+        }
+    }
+}
+
+/// evaluate a file, `-` or no file reads stdin. returns the exit code
+fn run_batch_file(path: Option<&PathBuf>, settings: &mut Settings,
+    opts: &BatchOptions) -> i32 {
+    match path {
+        Some(path) if path.as_os_str() != "-" => {
+            let source = path.display().to_string();
+            match File::open(path) {
+                Ok(file) => run_batch(BufReader::new(file), &source, settings, opts),
+                Err(e) => {
+                    eprintln!("bitwister: can't open {source}: {e}");
+                    EXIT_USAGE_ERROR
+                }
+            }
+        },
+        _ => run_batch(io::stdin().lock(), "stdin", settings, opts),
+    }
+}
+
+/// evaluate a single expression, returns the exit code
+fn run_eval(expr: &[String], settings: &mut Settings) -> i32 {
+    let expr = expr.join(" ");
+    match settings.evaluate(&expr) {
+        Ok((num, overflow)) => {
+            print!("{}", settings.render(&expr, &num, &overflow));
+            EXIT_SUCCESS
+        },
+        Err(failure) => {
+            let msg = settings.render_error(&expr, &failure, None);
+            if settings.json {
+                print!("{msg}");
+            } else {
+                eprint!("{msg}");
+            }
+            EXIT_EVAL_ERROR
+        }
+    }
+}
+
+/// explain a single expression, returns the exit code
+fn run_explain(expr: &[String], settings: &mut Settings) -> i32 {
+    match settings.explain(&expr.join(" ")) {
+        Ok(tree) => {
+            println!("{tree}");
+            EXIT_SUCCESS
+        },
+        Err(e) => {
+            eprintln!("bitwister: error: {e}");
+            EXIT_EVAL_ERROR
+        }
+    }
+}
+
+/// a notation for clap, without the `error:` prefix that clap adds itself
+fn parse_notation(s: &str) -> Result<Notation, String> {
+    s.parse().map_err(|e: ConvertError| e.to_string().trim_start_matches("error: ").to_string())
+}
+
+/// convert a single expression to every notation, returns the exit code
+fn run_convert(expr: &[String], from: Notation, settings: &Settings) -> i32 {
+    match settings.convert(&expr.join(" "), from) {
+        Ok(lines) => {
+            println!("{lines}");
+            EXIT_SUCCESS
+        },
+        Err(e) => {
+            eprintln!("bitwister: error: {e}");
+            EXIT_EVAL_ERROR
+        }
+    }
+}
+
+/// run script files, each starts without variables. returns the exit code
+fn run_scripts(files: &[PathBuf], settings: &mut Settings) -> i32 {
+    let mut code = EXIT_SUCCESS;
+    for path in files {
+        let source = path.display().to_string();
+        let file = match File::open(path) {
+            Ok(file) => file,
+            Err(e) => {
+                eprintln!("bitwister: can't open {source}: {e}");
+                return EXIT_USAGE_ERROR;
+            }
+        };
+        settings.clear_vars();
+        let mut stdout = io::stdout().lock();
+        match run_script(BufReader::new(file), &mut stdout, &source, settings,
+            settings.quiet) {
+            Ok(summary) => {
+                println!("{}", summary.report(&source));
+                if !summary.success() {
+                    code = EXIT_EVAL_ERROR;
+                }
             },
             Err(e) => {
-                eprintln!("bitwister, exiting with error: {e}");
-                std::process::exit(1);
+                eprintln!("bitwister: error reading {source}: {e}");
+                return EXIT_USAGE_ERROR;
+            }
+        }
+    }
+    code
+}
+
+/// run the full-screen mode, prints the final value. returns the exit code
+fn run_tui(value: Option<&str>, regs: Option<&PathBuf>, register: Option<&str>,
+    settings: &mut Settings) -> i32 {
+    if let Some(path) = regs {
+        if let Err(e) = settings.load_registers(path) {
+            eprintln!("bitwister: {e}");
+            return EXIT_USAGE_ERROR;
+        }
+    }
+    let register = match register {
+        Some(name) => match settings.registers.iter().position(|r| r.name == name) {
+            Some(idx) => Some(idx),
+            None => {
+                eprintln!("bitwister: unknown register {name}");
+                return EXIT_USAGE_ERROR;
+            }
+        },
+        None => None,
+    };
+    // a register decides the width if the value doesn't
+    let width = register.map(|idx| settings.registers[idx].width).or(settings.width);
+    let value = match value {
+        Some(expr) => match settings.evaluate_with_width(expr, width) {
+            Ok((num, _)) => num,
+            Err(failure) => {
+                eprint!("{}", settings.render_error(expr, &failure, None));
+                return EXIT_EVAL_ERROR;
+            }
+        },
+        None => IntType::from_u64_truncating(0, width.unwrap_or_default()).0,
+    };
+    let app = tui::App::new(value, settings.registers.clone(), register);
+    match tui::run(app, settings) {
+        Ok(num) => {
+            println!("{}", num.to_literal());
+            EXIT_SUCCESS
+        },
+        Err(e) => {
+            eprintln!("bitwister: {e}");
+            EXIT_USAGE_ERROR
+        }
+    }
+}
+
+/// annotate stdin, returns the exit code
+fn run_annotate(keys: &[String], regs: Option<&PathBuf>, register: Option<&str>,
+    settings: &mut Settings) -> i32 {
+    if let Some(path) = regs {
+        if let Err(e) = settings.load_registers(path) {
+            eprintln!("bitwister: {e}");
+            return EXIT_USAGE_ERROR;
+        }
+    }
+    let register = match register {
+        Some(name) => match settings.registers.iter().position(|r| r.name == name) {
+            Some(idx) => Some(idx),
+            None => {
+                eprintln!("bitwister: unknown register {name}");
+                return EXIT_USAGE_ERROR;
+            }
+        },
+        None => None,
+    };
+    let keys: Result<Vec<Key>, String> = keys.iter()
+        .map(|key| Key::parse(key, &settings.registers))
+        .collect();
+    let keys = match keys {
+        Ok(keys) => keys,
+        Err(e) => {
+            eprintln!("bitwister: invalid key: {e}");
+            return EXIT_USAGE_ERROR;
+        }
+    };
+    let annotator = Annotator::new(settings, keys, register);
+    match annotate(io::stdin().lock(), &mut io::stdout().lock(), &annotator) {
+        Ok(()) => EXIT_SUCCESS,
+        // the reader went away, e.g. `| head`
+        Err(e) if e.kind() == io::ErrorKind::BrokenPipe => EXIT_SUCCESS,
+        Err(e) => {
+            eprintln!("bitwister: {e}");
+            EXIT_USAGE_ERROR
+        }
+    }
+}
+
+/// serve JSON-RPC requests on stdin until it is closed, returns the exit
+/// code
+fn run_server(settings: Settings) -> i32 {
+    let mut server = Server::new(settings);
+    match serve(io::stdin().lock(), &mut io::stdout().lock(), &mut server) {
+        Ok(()) => EXIT_SUCCESS,
+        Err(e) if e.kind() == io::ErrorKind::BrokenPipe => EXIT_SUCCESS,
+        Err(e) => {
+            eprintln!("bitwister: {e}");
+            EXIT_USAGE_ERROR
+        }
+    }
+}
+
+/// show a single number in register view, returns the exit code
+fn run_reg(value: &str, settings: &Settings) -> i32 {
+    match IntType::new_from_str_with_width(value, settings.width) {
+        Ok(num) => {
+            print!("{}", settings.render(value, &num, &Overflow::NotPossible));
+            EXIT_SUCCESS
+        },
+        Err(e) => {
+            eprintln!("bitwister: invalid register value {value}: {e}");
+            EXIT_EVAL_ERROR
+        }
+    }
+}
+
+fn run_repl(settings: &mut Settings, history_size: Option<usize>) -> i32 {
+    let history = HistoryConfig::for_tool("bitwister")
+        .map(|mut history| {
+            if let Some(size) = history_size {
+                history.size = size;
             }
+            history
+        })
+        .filter(|history| history.size > 0);
+    match repl(settings, history.as_ref()) {
+        Ok(()) => EXIT_SUCCESS,
+        Err(e) => {
+            eprintln!("bitwister, exiting with error: {e}");
+            EXIT_EVAL_ERROR
         }
     }
+}
 
-    args.remove(0);
+fn main() {
 
-    let expr = args.join(" ");
+    let mut cmd = Cli::command().after_help(format!("available operations:\n{}",
+        commands::operations_help()));
+    let cli = match Cli::from_arg_matches(&cmd.get_matches_mut()) {
+        Ok(cli) => cli,
+        Err(e) => e.format(&mut cmd).exit(),
+    };
 
-    let result = evaluate(&expr);
-    if result.is_some() {
-        // SAFETY: checked above
-        let (num, overflow) = result.unwrap();
-        println!("[expr]> {expr}");
-        println!("[eval]> {} {}", num, overflow);
-    } else {
-        println!("> failed to evaluate expression: {expr}");
+    let config = match load_config(cli.opts.config.as_ref()) {
+        Ok(config) => config,
+        Err(code) => exit(code),
+    };
+    let mut settings = cli.opts.settings(&config);
+    for e in config.apply(&mut settings) {
+        eprintln!("bitwister: {e}");
     }
-    
-}
\ No newline at end of file
+    let history_size = cli.opts.history_size.or(config.history_size);
+    let batch_opts = BatchOptions { line_numbers: cli.opts.line_numbers };
+
+    let code = match cli.command {
+        Some(Command::Repl) => {
+            logo();
+            run_repl(&mut settings, history_size)
+        },
+        Some(Command::Eval { expr }) => run_eval(&expr, &mut settings),
+        Some(Command::Explain { expr }) => run_explain(&expr, &mut settings),
+        Some(Command::Convert { from, expr }) => run_convert(&expr, from, &settings),
+        Some(Command::Batch { file }) => {
+            run_batch_file(file.as_ref(), &mut settings, &batch_opts)
+        },
+        Some(Command::Run { files }) => run_scripts(&files, &mut settings),
+        Some(Command::Tui { value, regs, register }) => {
+            run_tui(value.as_deref(), regs.as_ref(), register.as_deref(), &mut settings)
+        },
+        Some(Command::Annotate { keys, regs, register }) => {
+            run_annotate(&keys, regs.as_ref(), register.as_deref(), &mut settings)
+        },
+        Some(Command::Server) => run_server(settings),
+        Some(Command::Reg { value }) => {
+            if settings.formats.is_empty() {
+                settings.formats = vec![Format::Reg, Format::Hex,
+                    Format::Bin, Format::Dec];
+            }
+            run_reg(&value, &settings)
+        },
+        None if cli.interactive => {
+            logo();
+            run_repl(&mut settings, history_size)
+        },
+        None if cli.file.is_some() => {
+            run_batch_file(cli.file.as_ref(), &mut settings, &batch_opts)
+        },
+        None if !cli.expr.is_empty() => run_eval(&cli.expr, &mut settings),
+        // piped input, e.g. cat exprs | bitwister
+        None if !io::stdin().is_terminal() => {
+            run_batch_file(None, &mut settings, &batch_opts)
+        },
+        None => {
+            logo();
+            eprintln!("bitwister: not enough args, run [bitwister -h] for help");
+            EXIT_USAGE_ERROR
+        }
+    };
+
+    exit(code);
+}
@@ -1,22 +1,99 @@
 /// repl for bitwister
 /// 0xca7
+use std::collections::BTreeMap;
 
 use rustyline::error::ReadlineError;
-use rustyline::{DefaultEditor, Result};
+use rustyline::history::DefaultHistory;
+use rustyline::{Editor, Result};
+
+use btrepl::{
+    editor,
+    history::{HistoryConfig, ResultLog},
+    save_history,
+    BtHelper,
+    WordKind,
+};
 
 use bitwister::{
-    evaluate
+    evaluate_with_width,
+    format::Format,
+    Operation,
+    Width,
+};
+
+use crate::commands::{
+    handle_line,
+    Action,
+    COMMANDS,
+};
+use crate::config::Function;
+use crate::session::Session;
+use crate::settings::{
+    expand,
+    Settings,
 };
 
-pub fn repl() -> Result<()> {
+/// the live result of `line` for the hint, nothing for commands and the
+/// register view, which prints. `words` maps aliases, variables and
+/// constants to what they stand for.
+fn hint(line: &str, width: Option<Width>, words: &BTreeMap<String, String>,
+    functions: &BTreeMap<String, Function>, results: &ResultLog) -> Option<String> {
+    let line = line.trim();
+    if line.starts_with(':') || line.starts_with("r ") {
+        return None;
+    }
+    let expr = match line.split_once('=') {
+        Some((_, expr)) => expr.trim(),
+        None => line,
+    };
+    let expr = expand(expr, results, functions, |word| words.get(word).cloned());
+    let (num, _, _) = evaluate_with_width(&expr, width)?;
+    Some(Format::Hex.render(&num, false))
+}
 
-    let mut rl = DefaultEditor::new()?;
-    #[cfg(feature = "with-file-history")]
-    if rl.load_history("/tmp/history.txt").is_err() {
-        println!("No previous history.");
+/// bring completion and hints up to date with the session
+fn update_helper(rl: &mut Editor<BtHelper, DefaultHistory>, settings: &Settings) {
+    let width = settings.width;
+    let values: BTreeMap<String, String> = settings.constants().iter()
+        .chain(settings.vars().iter())
+        .map(|(name, value)| (name.clone(), value.to_literal()))
+        .collect();
+    let mut words = values.clone();
+    words.extend(settings.aliases.clone());
+    let functions = settings.functions.clone();
+    let results = settings.results.clone();
+    if let Some(helper) = rl.helper_mut() {
+        helper.add_words(WordKind::Operator, settings.aliases.keys().cloned());
+        helper.add_words(WordKind::Function, functions.keys().cloned());
+        for register in &settings.registers {
+            helper.add_words(WordKind::Register, std::iter::once(register.name.clone())
+                .chain(register.fields.iter().map(|f| f.name.clone())));
+        }
+        helper.set_variables(values.into_keys());
+        helper.set_hinter(move |line| hint(line, width, &words, &functions, &results));
     }
+}
+
+/// run the repl, `history` stores the input and results across sessions
+pub fn repl(settings: &mut Settings, history: Option<&HistoryConfig>) -> Result<()> {
+
+    let mut helper = BtHelper::new()
+        .with_words(WordKind::Operator, Operation::symbols())
+        .with_words(WordKind::Operator, &["="])
+        .with_words(WordKind::Command, COMMANDS);
+    helper.set_color(settings.color);
+
+    let mut rl = editor(helper, history)?;
+    if let Some(history) = history {
+        match ResultLog::load(&history.results, history.size) {
+            Ok(results) => settings.results = results,
+            Err(e) => eprintln!("[bt]> error loading results: {e}"),
+        }
+    }
+    settings.session = Session::start(settings);
+    update_helper(&mut rl, settings);
     loop {
-        let readline = rl.readline("[bt]> ");
+        let readline = rl.readline(&settings.prompt());
         match readline {
             Ok(line) => {
                 match rl.add_history_entry(line.as_str()) {
@@ -25,15 +102,11 @@ pub fn repl() -> Result<()> {
                         eprintln!("[bt]> error adding to history: {e}");
                     },
                 }
-                let result = evaluate(&line);
-                if result.is_some() {
-                    // SAFETY: checked above
-                    let (num, overflow) = result.unwrap();
-                    println!("[expr]> {line}");
-                    println!("[eval]> {} {}", num, overflow);
-                } else {
-                    println!("> failed to evaluate expression: {line}");
+                if handle_line(&line, settings) == Action::Quit {
+                    println!("[bt]> shutting down, goodbye :^)");
+                    break
                 }
+                update_helper(&mut rl, settings);
             },
             Err(ReadlineError::Interrupted) | Err(ReadlineError::Eof) => {
                 println!("[bt]> shutting down, goodbye :^)");
@@ -45,8 +118,14 @@ pub fn repl() -> Result<()> {
             }
         }
     } // loop
-    #[cfg(feature = "with-file-history")]
-    rl.save_history("/tmp/history.txt");
+    if let Some(history) = history {
+        if let Err(e) = save_history(&mut rl, history) {
+            eprintln!("[bt]> error saving history: {e}");
+        }
+        if let Err(e) = settings.results.save(&history.results) {
+            eprintln!("[bt]> error saving results: {e}");
+        }
+    }
     Ok(())
 
 }
\ No newline at end of file
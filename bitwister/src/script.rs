@@ -0,0 +1,201 @@
+/// script files with assertions, e.g. to check register constants of a
+/// datasheet in a test job
+/// 0xca7
+use std::io::{BufRead, Write};
+
+use crate::batch::strip_line;
+use crate::commands::is_var_name;
+use crate::settings::{EvalFailure, Settings, GREEN, RED};
+
+use bitwister::Overflow;
+
+/// outcome of a script run
+pub struct ScriptSummary {
+    /// number of statements that passed
+    pub passed: usize,
+    /// line numbers and statements of every failure
+    pub failures: Vec<(usize, String)>,
+}
+
+impl ScriptSummary {
+
+    pub fn success(&self) -> bool {
+        self.failures.is_empty()
+    }
+
+    /// the last line of a run, e.g. `test.bw: 3 passed, 1 failed`
+    pub fn report(&self, source: &str) -> String {
+        format!("{source}: {} passed, {} failed", self.passed, self.failures.len())
+    }
+}
+
+/// a statement of a script
+enum Statement<'a> {
+    /// `let NAME = EXPR`
+    Let(&'a str, &'a str),
+    /// `assert EXPR == EXPR`, `assert EXPR != EXPR` or `assert EXPR`, which
+    /// holds if the value is not zero
+    Assert(&'a str, Option<(bool, &'a str)>),
+    /// `expect_overflow EXPR`
+    ExpectOverflow(&'a str),
+}
+
+fn parse_statement(line: &str) -> Result<Statement<'_>, String> {
+    let (keyword, rest) = line.split_once(char::is_whitespace)
+        .map(|(keyword, rest)| (keyword, rest.trim()))
+        .unwrap_or((line, ""));
+    match keyword {
+        "let" => {
+            let (name, expr) = rest.split_once('=')
+                .ok_or_else(|| String::from("expected let NAME = EXPR"))?;
+            let name = name.trim();
+            if !is_var_name(name) {
+                return Err(format!("invalid variable name {name}"));
+            }
+            Ok(Statement::Let(name, expr.trim()))
+        },
+        "assert" => {
+            let compare = |op: &str, equal: bool| rest.split_once(op)
+                .map(|(lhs, rhs)| Statement::Assert(lhs.trim(), Some((equal, rhs.trim()))));
+            Ok(compare("==", true)
+                .or_else(|| compare("!=", false))
+                .unwrap_or(Statement::Assert(rest, None)))
+        },
+        "expect_overflow" => Ok(Statement::ExpectOverflow(rest)),
+        _ => Err(format!("unknown statement {keyword}, expected let, assert \
+            or expect_overflow")),
+    }
+}
+
+/// run a statement, returns why it failed
+fn run_statement(line: &str, settings: &mut Settings) -> Result<(), String> {
+    let failed = |expr: &str, failure: EvalFailure| {
+        format!("failed to evaluate expression: {expr}: {failure}")
+    };
+
+    match parse_statement(line)? {
+        Statement::Let(name, expr) => {
+            if settings.constants().contains_key(name) {
+                return Err(format!("{name} is a constant"));
+            }
+            let (num, _) = settings.evaluate(expr).map_err(|e| failed(expr, e))?;
+            // SAFETY: parse_statement checked the name with is_var_name
+            settings.set_var(name, num).unwrap();
+        },
+        Statement::Assert(lhs, rhs) => {
+            let (left, _) = settings.evaluate(lhs).map_err(|e| failed(lhs, e))?;
+            match rhs {
+                Some((equal, rhs)) => {
+                    // an unsuffixed right side takes the width of the left
+                    let (right, _) = settings.evaluate_with_width(rhs, Some(left.width()))
+                        .map_err(|e| failed(rhs, e))?;
+                    if (left == right) != equal {
+                        return Err(format!("{} {} {}", left.to_literal(),
+                            if equal { "!=" } else { "==" }, right.to_literal()));
+                    }
+                },
+                None if left.as_u64() == 0 => {
+                    return Err(format!("{lhs} is {}", left.to_literal()));
+                },
+                None => (),
+            }
+        },
+        Statement::ExpectOverflow(expr) => {
+            let (num, overflow) = settings.evaluate(expr).map_err(|e| failed(expr, e))?;
+            if !matches!(overflow, Overflow::Occured) {
+                return Err(format!("no overflow, result {}", num.to_literal()));
+            }
+        },
+    }
+    Ok(())
+}
+
+/// run every statement of `input` and report each on `out`, blank lines and
+/// comments are skipped. variables set with `let` stay in `settings`.
+/// `quiet` only reports failures.
+pub fn run_script<R: BufRead, W: Write>(input: R, out: &mut W, source: &str,
+    settings: &mut Settings, quiet: bool) -> std::io::Result<ScriptSummary> {
+
+    let mut summary = ScriptSummary {
+        passed: 0,
+        failures: vec![],
+    };
+
+    for (idx, line) in input.lines().enumerate() {
+        let line = line?;
+        let lineno = idx + 1;
+
+        let statement = match strip_line(&line) {
+            Some(statement) => statement,
+            None => continue,
+        };
+
+        match run_statement(statement, settings) {
+            Ok(()) => {
+                summary.passed += 1;
+                if !quiet {
+                    writeln!(out, "{} {source}:{lineno}: {statement}",
+                        settings.paint(GREEN, "PASS"))?;
+                }
+            },
+            Err(e) => {
+                writeln!(out, "{} {source}:{lineno}: {statement}: {e}",
+                    settings.paint(RED, "FAIL"))?;
+                summary.failures.push((lineno, statement.to_string()));
+            },
+        }
+    }
+
+    Ok(summary)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_run_script() {
+        let input = "\
+# clock setup of the datasheet
+let CLK_DIV = 0x13u32
+let EN = 1
+assert (CLK_DIV << 4) | EN == 0x0131u32
+assert (CLK_DIV << 4) | EN == 0x0131
+assert CLK_DIV & 0x10 != 0
+assert CLK_DIV & 0x100
+expect_overflow 0xffu8 + 1u8
+expect_overflow 0xfeu8 + 1u8
+let 1x = 2
+frobnicate 1
+";
+        let mut settings = Settings::default();
+        let mut out = vec![];
+        let summary = run_script(input.as_bytes(), &mut out, "clk.bw",
+            &mut settings, false).unwrap();
+
+        assert_eq!(summary.passed, 6);
+        assert_eq!(summary.failures.iter().map(|(line, _)| *line).collect::<Vec<_>>(),
+            vec![7, 9, 10, 11]);
+        assert_eq!(summary.report("clk.bw"), "clk.bw: 6 passed, 4 failed");
+
+        let out = String::from_utf8(out).unwrap();
+        let lines: Vec<&str> = out.lines().collect();
+        assert_eq!(lines[2], "PASS clk.bw:4: assert (CLK_DIV << 4) | EN == 0x0131u32");
+        assert_eq!(lines[5], "FAIL clk.bw:7: assert CLK_DIV & 0x100: CLK_DIV & 0x100 \
+            is 0x00000000u32");
+        assert_eq!(lines[7], "FAIL clk.bw:9: expect_overflow 0xfeu8 + 1u8: no overflow, \
+            result 0xffu8");
+    }
+
+    #[test]
+    fn test_assert_mismatch() {
+        let mut settings = Settings::default();
+        assert_eq!(run_statement("assert 2u8 == 3u8", &mut settings),
+            Err(String::from("0x02u8 != 0x03u8")));
+        assert_eq!(run_statement("assert 2u8 == 2u16", &mut settings),
+            Err(String::from("0x02u8 != 0x0002u16")));
+        assert_eq!(run_statement("assert 2u8 != 2u8", &mut settings),
+            Err(String::from("0x02u8 == 0x02u8")));
+    }
+
+}
@@ -0,0 +1,649 @@
+/// a JSON-RPC 2.0 server on stdin and stdout, e.g. for hover-to-decode in
+/// an editor. each connection keeps its own variables and registers.
+/// 0xca7
+use std::collections::BTreeMap;
+use std::io::{self, BufRead, Read, Write};
+use std::path::Path;
+
+use serde_json::{json, Value};
+
+use bitwister::{
+    format::{Format, parse_formats},
+    IntType,
+    Operation,
+    Overflow,
+    Width,
+};
+
+use crate::commands::is_var_name;
+use crate::config::{parse_function, Function};
+use crate::settings::{map_words, EvalFailure, Settings};
+
+/// largest message that is read, longer ones are answered with an error
+/// and skipped so memory stays bounded
+pub const MAX_MESSAGE: usize = 1024 * 1024;
+
+const PARSE_ERROR: i64 = -32700;
+const INVALID_REQUEST: i64 = -32600;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+/// an expression failed to evaluate or a register is unknown
+const EVAL_ERROR: i64 = -32000;
+
+/// the C macros that are defined for every connection, so hovering them in
+/// C code shows their value
+const C_MACROS: &[(&str, &str)] = &[
+    ("BIT n", "1u64 << n"),
+    ("GENMASK h l", "(0xffffffffffffffffu64 >> (63u64 - h)) & (0xffffffffffffffffu64 << l)"),
+];
+
+/// the error of a request, as sent in the response
+#[derive(Debug, PartialEq)]
+pub struct RpcError {
+    pub code: i64,
+    pub message: String,
+    /// details of the error, e.g. where an expression failed
+    pub data: Option<Value>,
+}
+
+impl RpcError {
+
+    fn new(code: i64, message: impl Into<String>) -> RpcError {
+        RpcError {
+            code,
+            message: message.into(),
+            data: None,
+        }
+    }
+
+    /// the error of an expression, with the range of the expression as
+    /// evaluated that caused it
+    fn eval(failure: EvalFailure) -> RpcError {
+        RpcError {
+            data: Some(json!({
+                "expr": failure.expr,
+                "start": failure.span.start,
+                "end": failure.span.end,
+            })),
+            ..RpcError::new(EVAL_ERROR, failure.message())
+        }
+    }
+
+    fn to_json(&self) -> Value {
+        let mut error = json!({
+            "code": self.code,
+            "message": self.message,
+        });
+        if let Some(data) = &self.data {
+            error["data"] = data.clone();
+        }
+        error
+    }
+}
+
+/// the parameters of a request
+struct Params<'a>(Option<&'a Value>);
+
+impl<'a> Params<'a> {
+
+    fn get(&self, name: &str) -> Option<&'a Value> {
+        self.0.and_then(|params| params.get(name))
+    }
+
+    fn str(&self, name: &str) -> Result<&'a str, RpcError> {
+        self.get(name)
+            .and_then(Value::as_str)
+            .ok_or_else(|| RpcError::new(INVALID_PARAMS, format!("expected string {name}")))
+    }
+
+    fn optional_str(&self, name: &str) -> Result<Option<&'a str>, RpcError> {
+        match self.get(name) {
+            None | Some(Value::Null) => Ok(None),
+            Some(_) => self.str(name).map(Some),
+        }
+    }
+
+    fn width(&self) -> Result<Option<Width>, RpcError> {
+        self.optional_str("width")?
+            .map(|w| w.parse::<Width>()
+                .map_err(|e| RpcError::new(INVALID_PARAMS, e.to_string())))
+            .transpose()
+    }
+}
+
+/// the arguments of a call, `args` starts after the `(`. returns them and
+/// the length up to and including the closing `)`.
+fn call_args(args: &str) -> Option<(Vec<&str>, usize)> {
+    let mut depth = 0;
+    let mut parts = vec![];
+    let mut start = 0;
+    for (i, c) in args.char_indices() {
+        match c {
+            '(' => depth += 1,
+            ')' if depth == 0 => {
+                parts.push(args[start..i].trim());
+                return Some((parts, i + 1));
+            },
+            ')' => depth -= 1,
+            ',' if depth == 0 => {
+                parts.push(args[start..i].trim());
+                start = i + 1;
+            },
+            _ => (),
+        }
+    }
+    None
+}
+
+/// replace every call `NAME(a, b)` of one of the `functions` by its body in
+/// parentheses, calls in the arguments are replaced first
+fn expand_calls(expr: &str, functions: &BTreeMap<String, Function>) -> String {
+    let mut out = String::with_capacity(expr.len());
+    let mut rest = expr;
+    loop {
+        let call = functions.iter()
+            .filter_map(|(name, function)| {
+                let start = find_call(rest, name)?;
+                let (args, len) = call_args(&rest[start + name.len() + 1..])?;
+                Some((start, start + name.len() + 1 + len, function, args))
+            })
+            .min_by_key(|(start, ..)| *start);
+        let (start, end, function, args) = match call {
+            Some(call) => call,
+            None => break,
+        };
+        let args: Vec<String> = args.iter()
+            .map(|arg| format!("({})", expand_calls(arg, functions)))
+            .collect();
+        let args: Vec<&str> = args.iter().map(|arg| arg.as_str()).collect();
+        out.push_str(&rest[..start]);
+        match function.call(&args) {
+            Some(body) => out.push_str(&format!("({body})")),
+            None => out.push_str(&rest[start..end]),
+        }
+        rest = &rest[end..];
+    }
+    out.push_str(rest);
+    out
+}
+
+/// the position of the first call `name(` in `s`
+fn find_call(s: &str, name: &str) -> Option<usize> {
+    let is_word = |c: char| c.is_ascii_alphanumeric() || c == '_' || c == '$';
+    let pattern = format!("{name}(");
+    s.match_indices(&pattern)
+        .map(|(idx, _)| idx)
+        .find(|idx| !s[..*idx].ends_with(is_word))
+}
+
+/// rewrite the C syntax of a hovered expression: integer suffixes like
+/// `UL` are dropped and calls like `GENMASK(7, 4)` of a function are
+/// replaced by its body
+fn from_c(expr: &str, functions: &BTreeMap<String, Function>) -> String {
+    let expr = map_words(expr.trim(), |word| {
+        if !word.starts_with(|c: char| c.is_ascii_digit()) {
+            return None;
+        }
+        let hex = word.replacen("0X", "0x", 1);
+        let digits = hex.trim_end_matches(['u', 'U', 'l', 'L']);
+        (digits != word && hex.len() - digits.len() <= 3).then(|| digits.to_string())
+    });
+    expand_calls(&expr, functions)
+}
+
+/// the state of a connection and the methods it answers
+pub struct Server {
+    settings: Settings,
+}
+
+impl Server {
+
+    /// a server for one connection, starting with the constants, functions
+    /// and registers of `settings`
+    pub fn new(mut settings: Settings) -> Server {
+        for (head, body) in C_MACROS {
+            // SAFETY: the macros are valid definitions
+            let (name, function) = parse_function(head, body).unwrap();
+            settings.functions.entry(name).or_insert(function);
+        }
+        Server { settings }
+    }
+
+    /// evaluate `expr` as C or bitwister syntax
+    fn evaluate(&mut self, expr: &str, width: Option<Width>)
+        -> Result<(IntType, Overflow, Vec<String>), RpcError> {
+        let expr = from_c(expr, &self.settings.functions);
+        let evaluation = self.settings.evaluation(&expr, width).map_err(RpcError::eval)?;
+        let notes = evaluation.truncations.iter().map(|t| t.to_string()).collect();
+        Ok((evaluation.value, evaluation.overflow, notes))
+    }
+
+    /// the views of `num` in `formats`, an object of name and text
+    fn views(&self, num: &IntType, formats: &[Format], signed: bool) -> Value {
+        Value::Object(formats.iter()
+            .map(|fmt| (fmt.name().to_string(), Value::from(fmt.render(num, signed))))
+            .collect())
+    }
+
+    /// `evaluate {expr, width?}`, `name = expr` assigns a variable
+    fn method_evaluate(&mut self, params: &Params) -> Result<Value, RpcError> {
+        let line = params.str("expr")?;
+        let width = params.width()?.or(self.settings.width);
+
+        let (name, expr) = match line.split_once('=') {
+            Some((name, expr)) if is_var_name(name.trim()) => (Some(name.trim()), expr),
+            _ => (None, line),
+        };
+        if let Some(name) = name {
+            if self.settings.constants().contains_key(name)
+                || self.settings.aliases.contains_key(name)
+                || self.settings.functions.contains_key(name) {
+                return Err(RpcError::new(EVAL_ERROR,
+                    format!("{name} is a constant, alias or function")));
+            }
+        }
+
+        let (num, overflow, notes) = self.evaluate(expr, width)?;
+        let formats: Vec<Format> = self.settings.formats().iter()
+            .filter(|fmt| **fmt != Format::Reg)
+            .copied()
+            .collect();
+        let registers: Vec<&str> = self.settings.registers.iter()
+            .filter(|r| r.address == Some(num.as_u64()))
+            .map(|r| r.name.as_str())
+            .collect();
+
+        let result = json!({
+            "value": num.to_literal(),
+            "width": num.width().bits(),
+            "overflow": match overflow {
+                Overflow::Occured => Some(true),
+                Overflow::NoOverflow => Some(false),
+                Overflow::NotPossible => None,
+            },
+            "views": self.views(&num, &formats, self.settings.signed),
+            "notes": notes,
+            "registers": registers,
+        });
+
+        let name = name.unwrap_or("_");
+        // SAFETY: the name was checked by is_var_name
+        self.settings.set_var(name, num).unwrap();
+        Ok(result)
+    }
+
+    /// `decode-register {register, value}`, the value is evaluated with the
+    /// width of the register
+    fn method_decode_register(&mut self, params: &Params) -> Result<Value, RpcError> {
+        let name = params.str("register")?;
+        let width = self.settings.register(name)
+            .ok_or_else(|| RpcError::new(EVAL_ERROR, format!("unknown register {name}")))?
+            .width;
+        let (num, _, _) = self.evaluate(params.str("value")?, Some(width))?;
+        // SAFETY: the register was found above
+        let register = self.settings.register(name).unwrap();
+
+        let fields: Vec<Value> = register.decode(&num).into_iter()
+            .map(|(field, v)| json!({
+                "name": field.name,
+                "range": field.range(),
+                "value": v,
+                "hex": format!("{v:#x}"),
+                "description": field.description,
+            }))
+            .collect();
+        Ok(json!({
+            "register": register.name,
+            "width": register.width.bits(),
+            "address": register.address.map(|a| format!("{a:#x}")),
+            "value": Format::Hex.render(&num, false),
+            "fields": fields,
+        }))
+    }
+
+    /// `load-registers {path}`, returns the names of all loaded registers
+    fn method_load_registers(&mut self, params: &Params) -> Result<Value, RpcError> {
+        let path = params.str("path")?;
+        let count = self.settings.load_registers(Path::new(path))
+            .map_err(|e| RpcError::new(EVAL_ERROR, e))?;
+        let registers: Vec<&str> = self.settings.registers.iter()
+            .map(|r| r.name.as_str())
+            .collect();
+        Ok(json!({
+            "loaded": count,
+            "registers": registers,
+        }))
+    }
+
+    /// `format {value, formats?, signed?, width?}`, every view of the value
+    fn method_format(&mut self, params: &Params) -> Result<Value, RpcError> {
+        let width = params.width()?.or(self.settings.width);
+        let (num, _, _) = self.evaluate(params.str("value")?, width)?;
+        let formats = match params.get("formats") {
+            None | Some(Value::Null) => self.settings.formats().to_vec(),
+            Some(formats) => {
+                let names: Option<Vec<&str>> = formats.as_array()
+                    .map(|items| items.iter().filter_map(Value::as_str).collect());
+                names.and_then(|names| parse_formats(&names.join(",")).ok())
+                    .ok_or_else(|| RpcError::new(INVALID_PARAMS,
+                        "formats is not a list of hex, bin, dec, oct, ascii or reg"))?
+            },
+        };
+        let signed = match params.get("signed") {
+            None | Some(Value::Null) => self.settings.signed,
+            Some(signed) => signed.as_bool()
+                .ok_or_else(|| RpcError::new(INVALID_PARAMS, "expected bool signed"))?,
+        };
+        Ok(self.views(&num, &formats, signed))
+    }
+
+    /// `complete {prefix}`, every known word that starts with the prefix
+    fn method_complete(&self, params: &Params) -> Result<Value, RpcError> {
+        let prefix = params.optional_str("prefix")?.unwrap_or("");
+        let settings = &self.settings;
+
+        let mut words: Vec<(&str, &str)> = vec![];
+        words.extend(Operation::symbols().iter().map(|s| (*s, "operation")));
+        words.extend(settings.aliases.keys().map(|s| (s.as_str(), "operation")));
+        let vars = settings.vars();
+        words.extend(vars.keys().map(|s| (s.as_str(), "variable")));
+        words.extend(settings.constants().keys().map(|s| (s.as_str(), "constant")));
+        words.extend(settings.functions.keys().map(|s| (s.as_str(), "function")));
+        for register in &settings.registers {
+            words.push((&register.name, "register"));
+            words.extend(register.fields.iter().map(|f| (f.name.as_str(), "field")));
+        }
+
+        let items: Vec<Value> = words.into_iter()
+            .filter(|(word, _)| word.starts_with(prefix))
+            .map(|(word, kind)| json!({
+                "label": word,
+                "kind": kind,
+            }))
+            .collect();
+        Ok(json!({ "items": items }))
+    }
+
+    fn call(&mut self, method: &str, params: Option<&Value>) -> Result<Value, RpcError> {
+        if !matches!(params, None | Some(Value::Object(_))) {
+            return Err(RpcError::new(INVALID_PARAMS, "params must be an object"));
+        }
+        let params = Params(params);
+        match method {
+            "evaluate" => self.method_evaluate(&params),
+            "decode-register" => self.method_decode_register(&params),
+            "load-registers" => self.method_load_registers(&params),
+            "format" => self.method_format(&params),
+            "complete" => self.method_complete(&params),
+            _ => Err(RpcError::new(METHOD_NOT_FOUND, format!("unknown method {method}"))),
+        }
+    }
+
+    /// answer a single request, None for a notification
+    fn request(&mut self, request: &Value) -> Option<Value> {
+        let id = request.get("id").cloned();
+        let method = match (request.get("jsonrpc").and_then(Value::as_str),
+            request.get("method").and_then(Value::as_str)) {
+            (Some("2.0"), Some(method)) => method,
+            _ => return Some(response(id.unwrap_or(Value::Null),
+                Err(RpcError::new(INVALID_REQUEST, "invalid request")))),
+        };
+        let result = self.call(method, request.get("params"));
+        id.map(|id| response(id, result))
+    }
+
+    /// answer a message, a request or a batch of requests. returns None if
+    /// nothing is answered, e.g. for notifications.
+    pub fn handle(&mut self, message: &str) -> Option<String> {
+        let message = match serde_json::from_str::<Value>(message) {
+            Ok(message) => message,
+            Err(e) => {
+                return Some(response(Value::Null,
+                    Err(RpcError::new(PARSE_ERROR, format!("parse error: {e}")))).to_string());
+            },
+        };
+        match &message {
+            Value::Array(requests) if requests.is_empty() => {
+                Some(response(Value::Null,
+                    Err(RpcError::new(INVALID_REQUEST, "empty batch"))).to_string())
+            },
+            Value::Array(requests) => {
+                let responses: Vec<Value> = requests.iter()
+                    .filter_map(|request| self.request(request))
+                    .collect();
+                (!responses.is_empty()).then(|| Value::Array(responses).to_string())
+            },
+            request => self.request(request).map(|response| response.to_string()),
+        }
+    }
+}
+
+fn response(id: Value, result: Result<Value, RpcError>) -> Value {
+    let (name, value) = match result {
+        Ok(result) => ("result", result),
+        Err(e) => ("error", e.to_json()),
+    };
+    let mut response = json!({
+        "jsonrpc": "2.0",
+        "id": id,
+    });
+    response[name] = value;
+    response
+}
+
+/// read a line of at most `MAX_MESSAGE` bytes into `line`, the rest of a
+/// longer line is skipped. returns None at the end of the input, else
+/// whether the line was read completely.
+fn read_line<R: BufRead>(input: &mut R, line: &mut Vec<u8>) -> io::Result<Option<bool>> {
+    line.clear();
+    if Read::take(&mut *input, MAX_MESSAGE as u64).read_until(b'\n', line)? == 0 {
+        return Ok(None);
+    }
+    if line.ends_with(b"\n") {
+        return Ok(Some(true));
+    }
+    let mut skipped = 0;
+    loop {
+        let buf = input.fill_buf()?;
+        if buf.is_empty() {
+            break;
+        }
+        match buf.iter().position(|b| *b == b'\n') {
+            Some(idx) => {
+                skipped += idx;
+                input.consume(idx + 1);
+                break;
+            },
+            None => {
+                let len = buf.len();
+                skipped += len;
+                input.consume(len);
+            },
+        }
+    }
+    Ok(Some(skipped == 0))
+}
+
+/// read messages from `input` and write the answers to `out` until the
+/// input ends. a message is either a single line, or framed with a
+/// `Content-Length` header as in LSP; the answer is framed alike. a line
+/// longer than `MAX_MESSAGE` is skipped and answered with one error.
+pub fn serve<R: BufRead, W: Write>(mut input: R, out: &mut W, server: &mut Server)
+    -> io::Result<()> {
+    let mut line = Vec::new();
+    loop {
+        match read_line(&mut input, &mut line)? {
+            None => return Ok(()),
+            Some(true) => (),
+            Some(false) => {
+                writeln!(out, "{}", response(Value::Null, Err(RpcError::new(INVALID_REQUEST,
+                    "message too long"))))?;
+                out.flush()?;
+                continue;
+            },
+        }
+        let text = String::from_utf8_lossy(&line);
+        let text = text.trim();
+        if text.is_empty() {
+            continue;
+        }
+
+        let length = text.split_once(':')
+            .filter(|(name, _)| name.eq_ignore_ascii_case("content-length"))
+            .map(|(_, length)| length.trim().parse::<usize>());
+        let answer = match length {
+            None => {
+                if let Some(answer) = server.handle(text) {
+                    writeln!(out, "{answer}")?;
+                }
+                out.flush()?;
+                continue;
+            },
+            Some(length) => {
+                // skip the other headers up to the empty line
+                loop {
+                    if read_line(&mut input, &mut line)?.is_none() {
+                        return Ok(());
+                    }
+                    if line.trim_ascii().is_empty() {
+                        break;
+                    }
+                }
+                match length {
+                    Ok(length) if length <= MAX_MESSAGE => {
+                        let mut body = vec![0; length];
+                        input.read_exact(&mut body)?;
+                        server.handle(&String::from_utf8_lossy(&body))
+                    },
+                    Ok(length) => {
+                        io::copy(&mut Read::take(&mut input, length as u64), &mut io::sink())?;
+                        Some(response(Value::Null, Err(RpcError::new(INVALID_REQUEST,
+                            "message too long"))).to_string())
+                    },
+                    Err(_) => Some(response(Value::Null, Err(RpcError::new(PARSE_ERROR,
+                        "invalid Content-Length"))).to_string()),
+                }
+            },
+        };
+        if let Some(answer) = answer {
+            write!(out, "Content-Length: {}\r\n\r\n{answer}", answer.len())?;
+        }
+        out.flush()?;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// run `script`, lines starting with `>` are sent and the lines starting
+    /// with `<` are the expected answers
+    fn check(server: &mut Server, script: &str) {
+        let requests: String = script.lines()
+            .filter_map(|line| line.strip_prefix("> "))
+            .map(|line| format!("{line}\n"))
+            .collect();
+        let expected: Vec<&str> = script.lines()
+            .filter_map(|line| line.strip_prefix("< "))
+            .collect();
+        let mut out = vec![];
+        serve(requests.as_bytes(), &mut out, server).unwrap();
+        let out = String::from_utf8(out).unwrap();
+        assert_eq!(out.lines().collect::<Vec<_>>(), expected);
+    }
+
+    fn server() -> Server {
+        let mut settings = Settings::default();
+        settings.formats = vec![Format::Hex, Format::Dec];
+        settings.registers = bitwister::register::parse_registers("\
+register RCC_CR u32 0x40021000
+field HSIRDY 1 internal oscillator ready
+field HSION 0
+").unwrap();
+        Server::new(settings)
+    }
+
+    #[test]
+    fn test_evaluate() {
+        check(&mut server(), r#"
+> {"jsonrpc":"2.0","id":1,"method":"evaluate","params":{"expr":"x = 0x13 << 4"}}
+< {"jsonrpc":"2.0","id":1,"result":{"value":"0x00000130u32","width":32,"overflow":null,"views":{"hex":"0x00000130","dec":"304"},"notes":[],"registers":[]}}
+> {"jsonrpc":"2.0","id":"a","method":"evaluate","params":{"expr":"0x1ff","width":"u8"}}
+< {"jsonrpc":"2.0","id":"a","result":{"value":"0xffu8","width":8,"overflow":null,"views":{"hex":"0xff","dec":"255"},"notes":["literal 0x1ff does not fit u8, truncated to 0xff"],"registers":[]}}
+> {"jsonrpc":"2.0","id":"b","method":"format","params":{"value":"x","formats":["hex"]}}
+< {"jsonrpc":"2.0","id":"b","result":{"hex":"0x00000130"}}
+> {"jsonrpc":"2.0","id":2,"method":"evaluate","params":{"expr":"0x40021000UL"}}
+< {"jsonrpc":"2.0","id":2,"result":{"value":"0x40021000u32","width":32,"overflow":null,"views":{"hex":"0x40021000","dec":"1073876992"},"notes":[],"registers":["RCC_CR"]}}
+> {"jsonrpc":"2.0","id":3,"method":"evaluate","params":{"expr":"GENMASK(7, 4)"}}
+< {"jsonrpc":"2.0","id":3,"result":{"value":"0x00000000000000f0u64","width":64,"overflow":false,"views":{"hex":"0x00000000000000f0","dec":"240"},"notes":[],"registers":[]}}
+> {"jsonrpc":"2.0","id":"c","method":"evaluate","params":{"expr":"BIT(GENMASK(1, 0)) | 0x10UL"}}
+< {"jsonrpc":"2.0","id":"c","result":{"value":"0x0000000000000018u64","width":64,"overflow":false,"views":{"hex":"0x0000000000000018","dec":"24"},"notes":[],"registers":[]}}
+> {"jsonrpc":"2.0","id":4,"method":"evaluate","params":{"expr":"1 +"}}
+< {"jsonrpc":"2.0","id":4,"error":{"code":-32000,"message":"unexpected end of expression","data":{"expr":"1 +","start":3,"end":3}}}
+> {"jsonrpc":"2.0","id":5,"method":"evaluate","params":{"expr":"x / (1UL - 1)"}}
+< {"jsonrpc":"2.0","id":5,"error":{"code":-32000,"message":"division by zero","data":{"expr":"x / (1 - 1)","start":2,"end":3}}}
+"#);
+    }
+
+    #[test]
+    fn test_decode_format_complete() {
+        check(&mut server(), r#"
+> {"jsonrpc":"2.0","id":1,"method":"decode-register","params":{"register":"RCC_CR","value":"0x3"}}
+< {"jsonrpc":"2.0","id":1,"result":{"register":"RCC_CR","width":32,"address":"0x40021000","value":"0x00000003","fields":[{"name":"HSIRDY","range":"[1]","value":1,"hex":"0x1","description":"internal oscillator ready"},{"name":"HSION","range":"[0]","value":1,"hex":"0x1","description":null}]}}
+> {"jsonrpc":"2.0","id":2,"method":"decode-register","params":{"register":"NOPE","value":"1"}}
+< {"jsonrpc":"2.0","id":2,"error":{"code":-32000,"message":"unknown register NOPE"}}
+> {"jsonrpc":"2.0","id":3,"method":"format","params":{"value":"0xfe","width":"u8","formats":["bin","dec"],"signed":true}}
+< {"jsonrpc":"2.0","id":3,"result":{"bin":"b11111110","dec":"-2"}}
+> {"jsonrpc":"2.0","id":4,"method":"format","params":{"value":"1","formats":["nope"]}}
+< {"jsonrpc":"2.0","id":4,"error":{"code":-32602,"message":"formats is not a list of hex, bin, dec, oct, ascii or reg"}}
+> {"jsonrpc":"2.0","id":5,"method":"evaluate","params":{"expr":"HSI = 1"}}
+< {"jsonrpc":"2.0","id":5,"result":{"value":"0x00000001u32","width":32,"overflow":null,"views":{"hex":"0x00000001","dec":"1"},"notes":[],"registers":[]}}
+> {"jsonrpc":"2.0","id":6,"method":"complete","params":{"prefix":"HSI"}}
+< {"jsonrpc":"2.0","id":6,"result":{"items":[{"label":"HSI","kind":"variable"},{"label":"HSIRDY","kind":"field"},{"label":"HSION","kind":"field"}]}}
+"#);
+    }
+
+    #[test]
+    fn test_protocol_errors() {
+        check(&mut server(), r#"
+> {"jsonrpc":"2.0","id":1,"method":"frobnicate"}
+< {"jsonrpc":"2.0","id":1,"error":{"code":-32601,"message":"unknown method frobnicate"}}
+> {"jsonrpc":"2.0","id":2,"method":"evaluate","params":[1]}
+< {"jsonrpc":"2.0","id":2,"error":{"code":-32602,"message":"params must be an object"}}
+> {"jsonrpc":"2.0","id":3,"method":"evaluate","params":{}}
+< {"jsonrpc":"2.0","id":3,"error":{"code":-32602,"message":"expected string expr"}}
+> {"id":4,"method":"evaluate"}
+< {"jsonrpc":"2.0","id":4,"error":{"code":-32600,"message":"invalid request"}}
+> {"jsonrpc":"2.0","method":"evaluate","params":{"expr":"y = 2"}}
+> {"jsonrpc":"2.0","id":5
+< {"jsonrpc":"2.0","id":null,"error":{"code":-32700,"message":"parse error: EOF while parsing an object at line 1 column 23"}}
+> [{"jsonrpc":"2.0","id":6,"method":"format","params":{"value":"y","formats":["dec"]}},{"jsonrpc":"2.0","method":"complete"}]
+< [{"jsonrpc":"2.0","id":6,"result":{"dec":"2"}}]
+"#);
+    }
+
+    #[test]
+    fn test_long_line() {
+        let request = r#"{"jsonrpc":"2.0","id":1,"method":"format","params":{"value":"1","formats":["dec"]}}"#;
+        let input = format!("{}\n{request}\n", "x".repeat(3 * MAX_MESSAGE));
+        let mut out = vec![];
+        serve(input.as_bytes(), &mut out, &mut server()).unwrap();
+        assert_eq!(String::from_utf8(out).unwrap(), "\
+{\"jsonrpc\":\"2.0\",\"id\":null,\"error\":{\"code\":-32600,\"message\":\"message too long\"}}
+{\"jsonrpc\":\"2.0\",\"id\":1,\"result\":{\"dec\":\"1\"}}
+");
+    }
+
+    #[test]
+    fn test_content_length() {
+        let body = r#"{"jsonrpc":"2.0","id":1,"method":"format","params":{"value":"0x41u8","formats":["ascii"]}}"#;
+        let input = format!("Content-Length: {}\r\nContent-Type: application/json\r\n\r\n{body}",
+            body.len());
+        let mut out = vec![];
+        serve(input.as_bytes(), &mut out, &mut server()).unwrap();
+        let answer = r#"{"jsonrpc":"2.0","id":1,"result":{"ascii":"A"}}"#;
+        assert_eq!(String::from_utf8(out).unwrap(),
+            format!("Content-Length: {}\r\n\r\n{answer}", answer.len()));
+    }
+
+}
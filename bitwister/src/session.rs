@@ -0,0 +1,201 @@
+/// the record of a repl session, saved as a replayable script or exported
+/// as a Markdown transcript
+/// 0xca7
+use std::collections::BTreeMap;
+
+use bitwister::{
+    format::Format,
+    IntType,
+    Width,
+};
+
+use crate::config::Function;
+use crate::settings::Settings;
+
+/// what a line of the session did
+#[derive(Debug, Clone, PartialEq)]
+pub enum Outcome {
+    /// a meta-command that changed the session
+    Command,
+    /// an expression or assignment and its result as a literal
+    Value { literal: String, overflow: bool },
+    /// an expression that failed to evaluate
+    Error,
+}
+
+/// a line of the session
+#[derive(Debug, Clone, PartialEq)]
+pub struct Entry {
+    /// the line as it was entered
+    pub input: String,
+    /// the line as it is replayed, results recalled with `$N` are resolved
+    pub script: String,
+    pub outcome: Outcome,
+}
+
+/// the lines that changed the session, and the settings it started with so
+/// a replay starts alike
+#[derive(Default)]
+pub struct Session {
+    width: Option<Width>,
+    signed: bool,
+    formats: Vec<Format>,
+    functions: BTreeMap<String, Function>,
+    entries: Vec<Entry>,
+}
+
+/// a function as the `:def` command defines it
+fn def_line(name: &str, function: &Function) -> String {
+    let mut line = format!(":def {name}");
+    for param in &function.params {
+        line.push(' ');
+        line.push_str(param);
+    }
+    format!("{line} = {}", function.body)
+}
+
+/// escape the cell of a Markdown table
+fn cell(s: &str) -> String {
+    s.replace('|', "\\|")
+}
+
+impl Session {
+
+    /// start recording with the current `settings`
+    pub fn start(settings: &Settings) -> Session {
+        Session {
+            width: settings.width,
+            signed: settings.signed,
+            formats: settings.formats.clone(),
+            functions: settings.functions.clone(),
+            entries: vec![],
+        }
+    }
+
+    pub fn record(&mut self, input: &str, script: &str, outcome: Outcome) {
+        self.entries.push(Entry {
+            input: input.to_string(),
+            script: script.to_string(),
+            outcome,
+        });
+    }
+
+    /// a script that `:load` replays to the same results: the settings and
+    /// functions the session started with, then every line in order.
+    /// failed lines are kept as comments, `vars` are listed at the end.
+    pub fn script(&self, vars: &BTreeMap<String, IntType>) -> String {
+        let mut s = String::from("# bitwister session\n");
+        s.push_str(&format!(":width {}\n", self.width
+            .map_or(String::from("off"), |w| w.to_string())));
+        s.push_str(&format!(":signed {}\n", if self.signed { "on" } else { "off" }));
+        if !self.formats.is_empty() {
+            let formats: Vec<&str> = self.formats.iter().map(|f| f.name()).collect();
+            s.push_str(&format!(":format {}\n", formats.join(",")));
+        }
+        for (name, function) in &self.functions {
+            s.push_str(&def_line(name, function));
+            s.push('\n');
+        }
+        for entry in &self.entries {
+            match entry.outcome {
+                Outcome::Error => s.push_str(&format!("# failed: {}\n", entry.script)),
+                _ => s.push_str(&format!("{}\n", entry.script)),
+            }
+        }
+        let vars: Vec<String> = vars.iter()
+            .filter(|(name, _)| name.as_str() != "_")
+            .map(|(name, value)| format!("# {name} = {}\n", value.to_literal()))
+            .collect();
+        if !vars.is_empty() {
+            s.push_str("\n# variables at the end of the session\n");
+            s.push_str(&vars.concat());
+        }
+        s
+    }
+
+    /// a Markdown table of the inputs and their results in `formats`, the
+    /// register view is left out
+    pub fn transcript(&self, formats: &[Format], signed: bool) -> String {
+        let formats: Vec<Format> = formats.iter()
+            .filter(|f| **f != Format::Reg)
+            .copied()
+            .collect();
+
+        let mut s = String::from("# bitwister transcript\n\n| # | input |");
+        for fmt in &formats {
+            s.push_str(&format!(" {} |", fmt.name()));
+        }
+        s.push_str(" note |\n|---|---|");
+        s.push_str(&"---|".repeat(formats.len()));
+        s.push_str("---|\n");
+
+        for (idx, entry) in self.entries.iter().enumerate() {
+            s.push_str(&format!("| {} | `{}` |", idx + 1, cell(&entry.input)));
+            let (value, note) = match &entry.outcome {
+                Outcome::Value { literal, overflow } => {
+                    (IntType::new_from_str(literal).ok(), if *overflow { "overflow" } else { "" })
+                },
+                Outcome::Command => (None, ""),
+                Outcome::Error => (None, "error"),
+            };
+            for fmt in &formats {
+                let value = value.as_ref()
+                    .map_or(String::new(), |v| format!("`{}`", cell(&fmt.render(v, signed))));
+                s.push_str(&format!(" {value} |"));
+            }
+            s.push_str(&format!(" {note} |\n"));
+        }
+        s
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn session() -> Session {
+        let mut session = Session::default();
+        session.record(":width u8", ":width u8", Outcome::Command);
+        session.record("x = 0xf0 | 0x0f", "x = 0xf0 | 0x0f", Outcome::Value {
+            literal: String::from("0xffu8"),
+            overflow: false,
+        });
+        session.record("$1 + 1", "0xffu8 + 1", Outcome::Value {
+            literal: String::from("0x00u8"),
+            overflow: true,
+        });
+        session.record("1 +", "1 +", Outcome::Error);
+        session
+    }
+
+    #[test]
+    fn test_script() {
+        let mut vars = BTreeMap::new();
+        vars.insert(String::from("x"), IntType::U8(0xff));
+        vars.insert(String::from("_"), IntType::U8(0));
+        assert_eq!(session().script(&vars), "\
+# bitwister session
+:width off
+:signed off
+:width u8
+x = 0xf0 | 0x0f
+0xffu8 + 1
+# failed: 1 +
+
+# variables at the end of the session
+# x = 0xffu8
+");
+    }
+
+    #[test]
+    fn test_transcript() {
+        let transcript = session().transcript(&[Format::Hex, Format::Dec], false);
+        let lines: Vec<&str> = transcript.lines().collect();
+        assert_eq!(lines[2], "| # | input | hex | dec | note |");
+        assert_eq!(lines[4], "| 1 | `:width u8` |  |  |  |");
+        assert_eq!(lines[5], "| 2 | `x = 0xf0 \\| 0x0f` | `0xff` | `255` |  |");
+        assert_eq!(lines[6], "| 3 | `$1 + 1` | `0x00` | `0` | overflow |");
+        assert_eq!(lines[7], "| 4 | `1 +` |  |  | error |");
+    }
+
+}
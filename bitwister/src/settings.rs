@@ -0,0 +1,474 @@
+/// settings shared by all front-ends of bitwister: cli, batch and repl
+/// 0xca7
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs;
+use std::ops::Range;
+use std::path::Path;
+
+use btrepl::history::ResultLog;
+
+use bitwister::{
+    convert::{Expression, Notation},
+    format::Format,
+    register::{parse_registers, Register},
+    EvalError,
+    Evaluation,
+    Evaluator,
+    IntType,
+    Overflow,
+    Width,
+};
+
+use crate::config::Function;
+use crate::session::Session;
+
+const RESET: &str = "\x1b[0m";
+const BOLD: &str = "\x1b[1m";
+pub const RED: &str = "\x1b[31m";
+pub const GREEN: &str = "\x1b[32m";
+const YELLOW: &str = "\x1b[33m";
+const CYAN: &str = "\x1b[36m";
+
+/// how an evaluation is performed and how its result is shown
+#[derive(Default)]
+pub struct Settings {
+    /// width of literals without a `u` suffix if it can't be inferred from
+    /// the other operand, u32 if not set
+    pub width: Option<Width>,
+    /// show the decimal view as two's complement
+    pub signed: bool,
+    /// views to show, empty selects the default views
+    pub formats: Vec<Format>,
+    /// print only the value in the first format
+    pub quiet: bool,
+    /// use ANSI colours
+    pub color: bool,
+    /// print results as JSON, one object per line
+    pub json: bool,
+    /// evaluates the expressions of the session, with its variables and
+    /// the constants bound
+    evaluator: Evaluator,
+    /// results of this and past sessions, recalled with `$N`
+    pub results: ResultLog,
+    /// replaces `bt` in the prompt
+    pub prompt: Option<String>,
+    /// alias names of operations, e.g. `xor` for `^`
+    pub aliases: BTreeMap<String, String>,
+    /// named values of the config, kept by `:clear`
+    constants: BTreeMap<String, IntType>,
+    /// user functions of the config
+    pub functions: BTreeMap<String, Function>,
+    /// loaded register definitions
+    pub registers: Vec<Register>,
+    /// the lines of the repl session, for `:save` and `:transcript`
+    pub session: Session,
+}
+
+/// why an expression failed to evaluate
+#[derive(Debug)]
+pub struct EvalFailure {
+    pub error: EvalError,
+    /// the expression as it was evaluated, with results, function calls
+    /// and aliases expanded
+    pub expr: String,
+    /// the byte range of `expr` that caused the error
+    pub span: Range<usize>,
+}
+
+impl EvalFailure {
+
+    /// the error without the `error:` prefix
+    pub fn message(&self) -> String {
+        self.error.to_string().trim_start_matches("error: ").to_string()
+    }
+
+}
+
+impl fmt::Display for EvalFailure {
+
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} at offset {}", self.message(), self.span.start)
+    }
+
+}
+
+/// replace the words of `s` for which `f` returns a value, a word is a run
+/// of letters, digits, `_` and `$`. everything else is kept as it is.
+pub fn map_words<F>(s: &str, mut f: F) -> String
+    where F: FnMut(&str) -> Option<String> {
+    let is_word = |c: char| c.is_ascii_alphanumeric() || c == '_' || c == '$';
+    let mut out = String::with_capacity(s.len());
+    let mut rest = s;
+    while let Some(start) = rest.find(is_word) {
+        out.push_str(&rest[..start]);
+        rest = &rest[start..];
+        let end = rest.find(|c| !is_word(c)).unwrap_or(rest.len());
+        let word = &rest[..end];
+        match f(word) {
+            Some(value) => out.push_str(&value),
+            None => out.push_str(word),
+        }
+        rest = &rest[end..];
+    }
+    out.push_str(rest);
+    out
+}
+
+/// replace every `$N` in `expr` by result N of `results`
+pub fn recall(expr: &str, results: &ResultLog) -> String {
+    map_words(expr, |word| {
+        let n = word.strip_prefix('$')?.parse::<usize>().ok()?;
+        results.get(n).map(|entry| entry.result.clone())
+    })
+}
+
+/// expand `expr` before it is evaluated: `$N` results, a call of one of
+/// the `functions` and every word that `lookup` knows, in this order
+pub fn expand<F>(expr: &str, results: &ResultLog,
+    functions: &BTreeMap<String, Function>, lookup: F) -> String
+    where F: Fn(&str) -> Option<String> {
+
+    let expr = recall(expr, results);
+
+    // a function call, e.g. `bit 4`
+    let words: Vec<&str> = expr.split_whitespace().collect();
+    let expr = match words.split_first() {
+        Some((name, args)) => functions.get(*name)
+            .and_then(|f| f.call(args))
+            .unwrap_or_else(|| expr.clone()),
+        None => expr.clone(),
+    };
+
+    map_words(&expr, lookup)
+}
+
+impl Settings {
+
+    /// the value a word stands for: an alias, a variable or a constant
+    pub fn lookup(&self, word: &str) -> Option<String> {
+        if let Some(symbol) = self.aliases.get(word) {
+            return Some(symbol.clone());
+        }
+        self.evaluator.var(word).map(|value| value.to_literal())
+    }
+
+    /// replace results, function calls, aliases, variables and constants
+    /// in `expr`, see `expand`
+    pub fn substitute(&self, expr: &str) -> String {
+        expand(expr, &self.results, &self.functions, |word| self.lookup(word))
+    }
+
+    /// replace results, function calls and aliases in `expr`, variables and
+    /// constants are left to the evaluator
+    fn expand(&self, expr: &str) -> String {
+        expand(expr, &self.results, &self.functions, |word| self.aliases.get(word).cloned())
+    }
+
+    /// the variables of the session, `_` holds the last result
+    pub fn vars(&self) -> BTreeMap<String, IntType> {
+        self.evaluator.vars().iter()
+            .filter(|(name, _)| !self.constants.contains_key(*name))
+            .map(|(name, value)| (name.clone(), *value))
+            .collect()
+    }
+
+    pub fn set_var(&mut self, name: &str, value: IntType) -> Result<(), EvalError> {
+        self.evaluator.set_var(name, value)
+    }
+
+    /// remove every variable, the constants are kept
+    pub fn clear_vars(&mut self) {
+        for name in self.vars().keys() {
+            self.evaluator.remove_var(name);
+        }
+    }
+
+    pub fn constants(&self) -> &BTreeMap<String, IntType> {
+        &self.constants
+    }
+
+    /// define the constant `name`, it can't be assigned or cleared
+    pub fn set_constant(&mut self, name: &str, value: IntType) -> Result<(), EvalError> {
+        self.evaluator.set_var(name, value)?;
+        self.constants.insert(name.to_string(), value);
+        Ok(())
+    }
+
+    /// evaluate `expr`. truncated literals are reported on stderr.
+    pub fn evaluate(&mut self, expr: &str) -> Result<(IntType, Overflow), EvalFailure> {
+        self.evaluate_with_width(expr, self.width)
+    }
+
+    /// like `evaluate`, with another default width
+    pub fn evaluate_with_width(&mut self, expr: &str, width: Option<Width>)
+        -> Result<(IntType, Overflow), EvalFailure> {
+        let evaluation = self.evaluation(expr, width)?;
+        for truncation in evaluation.truncations {
+            eprintln!("{}", self.paint(YELLOW, &format!("[note]> {truncation}")));
+        }
+        // `r` shows the register view of its operand
+        for value in evaluation.register_views {
+            print!("{}", value.register_view());
+        }
+        Ok((evaluation.value, evaluation.overflow))
+    }
+
+    /// evaluate `expr` with the default width `width` without printing
+    /// anything
+    pub fn evaluation(&mut self, expr: &str, width: Option<Width>)
+        -> Result<Evaluation, EvalFailure> {
+        let expr = self.expand(expr);
+        self.evaluator.set_width(width.unwrap_or_default());
+        self.evaluator.evaluate_with_span(&expr)
+            .map_err(|(error, span)| EvalFailure { error, expr, span })
+    }
+
+    /// every step of evaluating `expr` as an indented tree, with the
+    /// operands of each operation below it
+    pub fn explain(&mut self, expr: &str) -> Result<String, String> {
+        let expr = self.expand(expr);
+        self.evaluator.set_width(self.width.unwrap_or_default());
+        let (_, step) = self.evaluator.explain(&expr)
+            .map_err(|e| e.to_string().trim_start_matches("error: ").to_string())?;
+        Ok(step.to_string())
+    }
+
+    /// `expr` written in every notation, one per line. variables and
+    /// function calls are replaced in infix expressions first.
+    pub fn convert(&self, expr: &str, from: Notation) -> Result<String, String> {
+        let expr = match from {
+            Notation::Infix => self.substitute(expr),
+            _ => expr.to_string(),
+        };
+        let expr = Expression::parse(&expr, from)
+            .map_err(|e| e.to_string().trim_start_matches("error: ").to_string())?;
+        Ok(Notation::ALL.iter()
+            .map(|notation| format!("{:<8} {}", format!("{notation}:"), expr.render(*notation)))
+            .collect::<Vec<_>>()
+            .join("\n"))
+    }
+
+    /// the register called `name`
+    pub fn register(&self, name: &str) -> Option<&Register> {
+        self.registers.iter().find(|r| r.name == name)
+    }
+
+    /// load the register definitions of `path`, registers of the same name
+    /// are replaced. returns the number of registers.
+    pub fn load_registers(&mut self, path: &Path) -> Result<usize, String> {
+        let input = fs::read_to_string(path)
+            .map_err(|e| format!("can't read {}: {e}", path.display()))?;
+        let registers = parse_registers(&input)
+            .map_err(|e| format!("{}:{}: {}", path.display(), e.line, e.msg))?;
+        let count = registers.len();
+        for register in registers {
+            self.registers.retain(|r| r.name != register.name);
+            self.registers.push(register);
+        }
+        Ok(count)
+    }
+
+    /// the repl prompt, shows the active width and signedness
+    pub fn prompt(&self) -> String {
+        let mut prompt = format!("[{}", self.prompt.as_deref().unwrap_or("bt"));
+        if let Some(width) = self.width {
+            prompt.push_str(&format!(" {width}"));
+        }
+        if self.signed {
+            prompt.push_str(" signed");
+        }
+        prompt.push_str("]> ");
+        prompt
+    }
+
+    /// the selected views, or the default views
+    pub fn formats(&self) -> &[Format] {
+        if self.formats.is_empty() {
+            &Format::DEFAULT
+        } else {
+            &self.formats
+        }
+    }
+
+    /// `s` in `color`, if colours are enabled
+    pub fn paint(&self, color: &str, s: &str) -> String {
+        if self.color {
+            format!("{color}{s}{RESET}")
+        } else {
+            s.to_string()
+        }
+    }
+
+    /// render the result of evaluating `expr`, ends with a newline
+    pub fn render(&self, expr: &str, num: &IntType, overflow: &Overflow) -> String {
+
+        if self.json {
+            return self.render_json(expr, num, overflow);
+        }
+
+        if self.quiet {
+            let fmt = self.formats.first().unwrap_or(&Format::Hex);
+            return format!("{}\n", fmt.render(num, self.signed));
+        }
+
+        let mut s = String::new();
+        s.push_str(&format!("{} {expr}\n", self.paint(CYAN, "[expr]>")));
+        for (i, fmt) in self.formats().iter().enumerate() {
+            let prefix = if i == 0 {
+                self.paint(CYAN, "[eval]>")
+            } else {
+                String::from("       ")
+            };
+            let value = fmt.render(num, self.signed);
+            if *fmt == Format::Reg {
+                s.push_str(&format!("{prefix} reg:\n{value}"));
+            } else {
+                let label = format!("{}:", fmt.name());
+                s.push_str(&format!("{prefix} {label:<6} {}\n",
+                    self.paint(BOLD, &value)));
+            }
+        }
+        match overflow {
+            Overflow::Occured => {
+                s.push_str(&format!("        {}\n", self.paint(RED, &overflow.to_string())));
+            },
+            Overflow::NoOverflow => {
+                s.push_str(&format!("        {}\n", self.paint(GREEN, &overflow.to_string())));
+            },
+            Overflow::NotPossible => (),
+        }
+        s
+    }
+
+    fn render_json(&self, expr: &str, num: &IntType, overflow: &Overflow) -> String {
+        let overflow = match overflow {
+            Overflow::Occured => "true",
+            Overflow::NoOverflow => "false",
+            Overflow::NotPossible => "null",
+        };
+        let mut s = format!("{{\"expr\":{},\"width\":{},\"value\":{},\"overflow\":{}",
+            json_string(expr), num.width().bits(),
+            json_string(&Format::Hex.render(num, false)), overflow);
+        for fmt in self.formats() {
+            s.push_str(&format!(",\"{}\":{}", fmt.name(),
+                json_string(&fmt.render(num, self.signed))));
+        }
+        s.push_str("}\n");
+        s
+    }
+
+    /// render why `expr` failed to evaluate, ends with a newline. the span
+    /// is given in the expression as evaluated if it differs from `expr`.
+    /// `location` is an optional prefix like `file:3`
+    pub fn render_error(&self, expr: &str, failure: &EvalFailure,
+        location: Option<&str>) -> String {
+        if self.json {
+            return format!("{{\"expr\":{},\"error\":{},\"evaluated\":{},\"start\":{},\"end\":{}}}\n",
+                json_string(expr), json_string(&failure.message()),
+                json_string(&failure.expr), failure.span.start, failure.span.end);
+        }
+        let mut reason = failure.to_string();
+        if failure.expr != expr {
+            reason.push_str(&format!(" of {}", failure.expr));
+        }
+        let msg = match location {
+            Some(location) => format!("{location}: failed to evaluate expression: {expr}: {reason}"),
+            None => format!("> failed to evaluate expression: {expr}: {reason}"),
+        };
+        format!("{}\n", self.paint(RED, &msg))
+    }
+}
+
+/// quote and escape `s` as a JSON string
+fn json_string(s: &str) -> String {
+    let mut out = String::from("\"");
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            c if (c as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render() {
+        let mut s = Settings::default();
+        let (num, overflow) = s.evaluate("0xffu8 + 1u8").unwrap();
+        let out = s.render("0xffu8 + 1u8", &num, &overflow);
+        assert!(out.starts_with("[expr]> 0xffu8 + 1u8\n[eval]> hex:   0x00\n"));
+        assert!(out.ends_with("[overflow occured]\n"));
+
+        s.quiet = true;
+        s.formats = vec![Format::Dec];
+        assert_eq!(s.render("", &num, &overflow), "0\n");
+
+        s.quiet = false;
+        s.json = true;
+        assert_eq!(s.render("0xffu8 + 1u8", &num, &overflow),
+            "{\"expr\":\"0xffu8 + 1u8\",\"width\":8,\"value\":\"0x00\",\
+            \"overflow\":true,\"dec\":\"0\"}\n");
+
+        let failure = s.evaluate("1 / (1 - 1)").unwrap_err();
+        assert_eq!(failure.error, EvalError::DivisionByZero);
+        assert_eq!(failure.span, 2..3);
+        assert_eq!(s.render_error("1 / (1 - 1)", &failure, None),
+            "{\"expr\":\"1 / (1 - 1)\",\"error\":\"division by zero\",\
+            \"evaluated\":\"1 / (1 - 1)\",\"start\":2,\"end\":3}\n");
+
+        s.json = false;
+        s.aliases.insert(String::from("div"), String::from("/"));
+        let failure = s.evaluate("1 div 0").unwrap_err();
+        assert_eq!(s.render_error("1 div 0", &failure, Some("test:1")),
+            "test:1: failed to evaluate expression: 1 div 0: division by zero at offset 2 of 1 / 0\n");
+    }
+
+    #[test]
+    fn test_explain() {
+        let mut s = Settings { width: Some(Width::U8), ..Default::default() };
+        s.set_var("x", IntType::U8(0xff)).unwrap();
+        assert_eq!(s.explain("x + 1").unwrap(), "\
++ = 0x00 0b00000000 (u8, overflow (carry out))
+  x = 0xff 0b11111111 (u8)
+  1 = 0x01 0b00000001 (u8)");
+        assert_eq!(s.explain("1 / 0").unwrap_err(), "division by zero");
+    }
+
+    #[test]
+    fn test_convert() {
+        let mut s = Settings::default();
+        s.set_var("x", IntType::U8(0xff)).unwrap();
+        assert_eq!(s.convert("(x+1)*2", Notation::Infix).unwrap(), "\
+infix:   (0xffu8 + 1u8) * 2u8
+prefix:  u8 * + 0xff 1 2
+postfix: u8 0xff 1 + 2 *");
+        assert_eq!(s.convert("1 +", Notation::Postfix).unwrap_err(),
+            "+ takes 2 operands, found 1 at offset 2");
+    }
+
+    #[test]
+    fn test_substitute() {
+        let mut s = Settings::default();
+        s.set_var("CLK_DIV", IntType::U32(0x13)).unwrap();
+        s.aliases.insert(String::from("or"), String::from("|"));
+        s.results.push("1u8 + 1u8", "0x02u8");
+        assert_eq!(s.substitute("(CLK_DIV << 4) or $1"),
+            "(0x00000013u32 << 4) | 0x02u8");
+        assert_eq!(s.substitute("$2+CLK_DIVx"), "$2+CLK_DIVx");
+    }
+
+    #[test]
+    fn test_json_string() {
+        assert_eq!(json_string("a\"b\\c"), "\"a\\\"b\\\\c\"");
+    }
+
+}
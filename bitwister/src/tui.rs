@@ -0,0 +1,421 @@
+/// full-screen mode of bitwister: the value as a grid of bits that can be
+/// toggled, with live views of the value
+/// 0xca7
+use std::io;
+
+use ratatui::{
+    backend::Backend,
+    crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind},
+    layout::{Constraint, Layout},
+    style::{Color, Modifier, Style},
+    text::{Line, Span},
+    widgets::{Block, Borders, Paragraph},
+    Frame,
+    Terminal,
+};
+
+use bitwister::{
+    format::Format,
+    register::Register,
+    IntType,
+    Width,
+};
+
+use crate::settings::Settings;
+
+const HELP: &str = "←/→ move  ↑/↓ byte  space toggle  1/0 set/clear  v select  \
+    w/W width  : operation  f fields  c clear  q quit";
+
+/// what the keys do
+#[derive(Debug, PartialEq)]
+enum Mode {
+    /// keys move the cursor and edit bits
+    Normal,
+    /// keys are typed into an operation, e.g. `<< 4`
+    Operation(String),
+}
+
+/// the state of the full-screen mode
+pub struct App {
+    value: u64,
+    width: Width,
+    /// the bit under the cursor
+    cursor: u32,
+    /// the other end of a selection, the cursor is one end
+    anchor: Option<u32>,
+    registers: Vec<Register>,
+    /// the register whose fields are shown
+    register: Option<usize>,
+    mode: Mode,
+    /// the result of the last action, shown in the status line
+    message: Option<String>,
+    pub quit: bool,
+}
+
+/// the next width, from u8 to u64 and back
+fn next_width(width: Width, up: bool) -> Width {
+    match (width, up) {
+        (Width::U8, true) | (Width::U32, false) => Width::U16,
+        (Width::U16, true) | (Width::U64, false) => Width::U32,
+        (Width::U32, true) | (Width::U8, false) => Width::U64,
+        (Width::U64, true) | (Width::U16, false) => Width::U8,
+    }
+}
+
+impl App {
+
+    /// start with `value`, `registers` can be shown with their fields,
+    /// `register` is shown first
+    pub fn new(value: IntType, registers: Vec<Register>, register: Option<usize>) -> App {
+        App {
+            value: value.as_u64(),
+            width: value.width(),
+            cursor: 0,
+            anchor: None,
+            registers,
+            register,
+            mode: Mode::Normal,
+            message: None,
+            quit: false,
+        }
+    }
+
+    pub fn value(&self) -> IntType {
+        IntType::from_u64_truncating(self.value, self.width).0
+    }
+
+    fn bits(&self) -> u32 {
+        self.width.bits() as u32
+    }
+
+    /// the lowest and highest bit of the selection, or the cursor
+    fn selection(&self) -> (u32, u32) {
+        let anchor = self.anchor.unwrap_or(self.cursor);
+        (anchor.min(self.cursor), anchor.max(self.cursor))
+    }
+
+    fn selection_mask(&self) -> u64 {
+        let (lo, hi) = self.selection();
+        (u64::MAX >> (63 - (hi - lo))) << lo
+    }
+
+    fn move_cursor(&mut self, by: i64) {
+        let max = self.bits() as i64 - 1;
+        self.cursor = (self.cursor as i64 + by).clamp(0, max) as u32;
+    }
+
+    fn set_width(&mut self, width: Width) {
+        self.width = width;
+        self.value = self.value().as_u64();
+        self.cursor = self.cursor.min(self.bits() - 1);
+        self.anchor = self.anchor.map(|a| a.min(self.bits() - 1));
+    }
+
+    /// apply the operation typed by the user: a unary operation alone, or
+    /// the rest of an expression with the value on the left, e.g. `<< 4`.
+    /// nothing is printed while the screen is in use, truncated literals
+    /// are shown in the message.
+    fn apply(&mut self, op: &str, settings: &mut Settings) {
+        let op = op.trim();
+        let literal = self.value().to_literal();
+        // the register view of `r` is the grid itself
+        let expr = if matches!(op, "~" | "!" | "e" | "r") {
+            format!("{op} {literal}")
+        } else {
+            format!("{literal} {op}")
+        };
+        match settings.evaluation(&expr, Some(self.width)) {
+            Ok(evaluation) => {
+                self.width = evaluation.value.width();
+                self.value = evaluation.value.as_u64();
+                let mut message = format!("{expr} {}", evaluation.overflow).trim_end().to_string();
+                for truncation in &evaluation.truncations {
+                    message.push_str(&format!(", {truncation}"));
+                }
+                self.message = Some(message);
+            },
+            Err(failure) => self.message = Some(format!("failed to evaluate expression: \
+                {expr}: {failure}")),
+        }
+    }
+
+    /// react to a key press
+    pub fn handle_key(&mut self, key: KeyEvent, settings: &mut Settings) {
+        if let Mode::Operation(op) = &mut self.mode {
+            match key.code {
+                KeyCode::Enter => {
+                    let op = std::mem::take(op);
+                    self.mode = Mode::Normal;
+                    self.apply(&op, settings);
+                },
+                KeyCode::Esc => self.mode = Mode::Normal,
+                KeyCode::Backspace => { op.pop(); },
+                KeyCode::Char(c) => op.push(c),
+                _ => (),
+            }
+            return;
+        }
+
+        self.message = None;
+        match key.code {
+            KeyCode::Left | KeyCode::Char('h') => self.move_cursor(1),
+            KeyCode::Right | KeyCode::Char('l') => self.move_cursor(-1),
+            KeyCode::Up | KeyCode::Char('k') => self.move_cursor(8),
+            KeyCode::Down | KeyCode::Char('j') => self.move_cursor(-8),
+            KeyCode::Home => self.cursor = self.bits() - 1,
+            KeyCode::End => self.cursor = 0,
+            KeyCode::Char(' ') | KeyCode::Enter => self.value ^= self.selection_mask(),
+            KeyCode::Char('1') => self.value |= self.selection_mask(),
+            KeyCode::Char('0') => self.value &= !self.selection_mask(),
+            KeyCode::Char('c') => self.value = 0,
+            KeyCode::Char('v') => {
+                self.anchor = match self.anchor {
+                    Some(_) => None,
+                    None => Some(self.cursor),
+                };
+            },
+            KeyCode::Char('w') => self.set_width(next_width(self.width, true)),
+            KeyCode::Char('W') => self.set_width(next_width(self.width, false)),
+            KeyCode::Char(':') | KeyCode::Char('o') => self.mode = Mode::Operation(String::new()),
+            KeyCode::Char('f') if !self.registers.is_empty() => {
+                self.register = match self.register {
+                    Some(idx) if idx + 1 < self.registers.len() => Some(idx + 1),
+                    Some(_) => None,
+                    None => Some(0),
+                };
+            },
+            KeyCode::Esc if self.anchor.is_some() => self.anchor = None,
+            KeyCode::Esc | KeyCode::Char('q') => self.quit = true,
+            _ => (),
+        }
+    }
+
+    /// the lines of the bit grid as `regprint` lays them out: the bit
+    /// numbers above the bits, 64-bit values in two rows of 32
+    fn grid(&self) -> Vec<Line<'_>> {
+        let (lo, hi) = self.selection();
+        let selected = self.anchor.is_some();
+        let mut lines = vec![];
+        let rows: Vec<(u32, u32)> = if self.bits() == 64 {
+            vec![(32, 64), (0, 32)]
+        } else {
+            vec![(0, self.bits())]
+        };
+        for (start, end) in rows {
+            let mut numbers = vec![];
+            let mut bits = vec![];
+            for i in (start..end).rev() {
+                let mut style = Style::default();
+                if selected && (lo..=hi).contains(&i) {
+                    style = style.bg(Color::Blue);
+                }
+                if i == self.cursor {
+                    style = style.add_modifier(Modifier::REVERSED);
+                }
+                let bit = (self.value >> i) & 1;
+                numbers.push(Span::styled(format!("{i:<3}"), Style::default().fg(Color::DarkGray)));
+                bits.push(Span::styled(format!("{bit}"), style));
+                bits.push(Span::raw("  "));
+            }
+            lines.push(Line::from(numbers));
+            lines.push(Line::from(bits));
+        }
+        lines
+    }
+
+    /// hex, dec, signed and bin of the value
+    fn views(&self) -> Vec<Line<'_>> {
+        let value = self.value();
+        let (lo, hi) = self.selection();
+        let mut lines: Vec<Line> = [
+            ("hex", Format::Hex.render(&value, false)),
+            ("dec", Format::Dec.render(&value, false)),
+            ("signed", Format::Dec.render(&value, true)),
+            ("bin", Format::Bin.render(&value, false)),
+        ].into_iter()
+            .map(|(name, view)| Line::from(vec![
+                Span::raw(format!("{name:<8}")),
+                Span::styled(view, Style::default().add_modifier(Modifier::BOLD)),
+            ]))
+            .collect();
+        let bits = if lo == hi { format!("[{lo}]") } else { format!("[{hi}:{lo}]") };
+        let selection = (self.value & self.selection_mask()) >> lo;
+        lines.push(Line::from(format!("{:<8}{bits} = 0x{selection:x} ({selection})", "bits")));
+        lines
+    }
+
+    /// the fields of the shown register, the one under the cursor stands out
+    fn fields(&self) -> Option<(String, Vec<Line<'_>>)> {
+        let register = self.registers.get(self.register?)?;
+        let value = self.value();
+        let lines = register.describe(&value)
+            .lines()
+            .zip(register.fields.iter())
+            .map(|(line, field)| {
+                let style = if (field.lsb..=field.msb).contains(&self.cursor) {
+                    Style::default().add_modifier(Modifier::REVERSED)
+                } else {
+                    Style::default()
+                };
+                Line::styled(line.to_string(), style)
+            })
+            .collect();
+        Some((register.name.clone(), lines))
+    }
+
+    fn status(&self) -> Line<'_> {
+        match (&self.mode, &self.message) {
+            (Mode::Operation(op), _) => Line::from(format!("operation> {op}")),
+            (Mode::Normal, Some(message)) => Line::from(message.as_str()),
+            (Mode::Normal, None) => Line::styled(HELP, Style::default().fg(Color::DarkGray)),
+        }
+    }
+}
+
+/// draw the app
+pub fn draw(frame: &mut Frame, app: &App) {
+    let grid = app.grid();
+    let fields = app.fields();
+    let field_height = fields.as_ref().map_or(0, |(_, lines)| lines.len() as u16 + 2);
+
+    let [grid_area, views_area, fields_area, status_area] = Layout::vertical([
+        Constraint::Length(grid.len() as u16 + 2),
+        Constraint::Length(7),
+        Constraint::Length(field_height),
+        Constraint::Min(1),
+    ]).areas(frame.area());
+
+    let title = format!(" bitwister {} ", app.width);
+    frame.render_widget(Paragraph::new(grid)
+        .block(Block::default().borders(Borders::ALL).title(title)), grid_area);
+    frame.render_widget(Paragraph::new(app.views())
+        .block(Block::default().borders(Borders::ALL)), views_area);
+    if let Some((name, lines)) = fields {
+        frame.render_widget(Paragraph::new(lines)
+            .block(Block::default().borders(Borders::ALL).title(format!(" {name} "))),
+            fields_area);
+    }
+    frame.render_widget(Paragraph::new(app.status()), status_area);
+}
+
+/// handle key presses until the user quits
+fn event_loop<B: Backend>(terminal: &mut Terminal<B>, app: &mut App,
+    settings: &mut Settings) -> io::Result<()> {
+    while !app.quit {
+        terminal.draw(|frame| draw(frame, app))?;
+        if let Event::Key(key) = event::read()? {
+            if key.kind == KeyEventKind::Press {
+                app.handle_key(key, settings);
+            }
+        }
+    }
+    Ok(())
+}
+
+/// run the full-screen mode on the terminal, returns the final value
+pub fn run(mut app: App, settings: &mut Settings) -> io::Result<IntType> {
+    let mut terminal = ratatui::try_init()?;
+    let result = event_loop(&mut terminal, &mut app, settings);
+    ratatui::restore();
+    result.map(|()| app.value())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ratatui::backend::TestBackend;
+    use bitwister::register::parse_registers;
+
+    fn press(app: &mut App, keys: &str) {
+        let mut settings = Settings::default();
+        for c in keys.chars() {
+            app.handle_key(KeyEvent::from(KeyCode::Char(c)), &mut settings);
+        }
+    }
+
+    fn screen(app: &App) -> String {
+        let mut terminal = Terminal::new(TestBackend::new(110, 20)).unwrap();
+        terminal.draw(|frame| draw(frame, app)).unwrap();
+        let buffer = terminal.backend().buffer();
+        let mut s = String::new();
+        for y in 0..buffer.area.height {
+            for x in 0..buffer.area.width {
+                s.push_str(buffer[(x, y)].symbol());
+            }
+            s.push('\n');
+        }
+        s
+    }
+
+    #[test]
+    fn test_toggle_bits() {
+        let mut app = App::new(IntType::U8(0), vec![], None);
+        press(&mut app, " hh ");
+        assert_eq!(app.value(), IntType::U8(0b101));
+
+        // select bits 2..=5 and set them
+        press(&mut app, "vhhh1v");
+        assert_eq!(app.value(), IntType::U8(0b111101));
+        assert_eq!(app.cursor, 5);
+
+        press(&mut app, "c");
+        assert_eq!(app.value(), IntType::U8(0));
+    }
+
+    #[test]
+    fn test_width_and_operations() {
+        let mut settings = Settings::default();
+        let mut app = App::new(IntType::U16(0x1ff), vec![], None);
+        press(&mut app, "W");
+        assert_eq!(app.value(), IntType::U8(0xff));
+        press(&mut app, "w");
+        assert_eq!(app.value(), IntType::U16(0xff));
+
+        press(&mut app, ":<< 4");
+        app.handle_key(KeyEvent::from(KeyCode::Enter), &mut settings);
+        assert_eq!(app.value(), IntType::U16(0xff0));
+
+        press(&mut app, ":!");
+        app.handle_key(KeyEvent::from(KeyCode::Enter), &mut settings);
+        assert_eq!(app.value(), IntType::U16(0xf00f));
+
+        press(&mut app, ":& 1u8");
+        app.handle_key(KeyEvent::from(KeyCode::Enter), &mut settings);
+        assert_eq!(app.value(), IntType::U16(0xf00f));
+        assert!(app.message.as_ref().unwrap().starts_with("failed"));
+
+        press(&mut app, "W:& 0x1ff");
+        app.handle_key(KeyEvent::from(KeyCode::Enter), &mut settings);
+        assert_eq!(app.value(), IntType::U8(0x0f));
+        assert_eq!(app.message.as_deref(), Some("0x0fu8 & 0x1ff, \
+            literal 0x1ff does not fit u8, truncated to 0xff"));
+
+        press(&mut app, ":r");
+        app.handle_key(KeyEvent::from(KeyCode::Enter), &mut settings);
+        assert_eq!(app.value(), IntType::U8(0x0f));
+        assert_eq!(app.message.as_deref(), Some("r 0x0fu8"));
+
+        press(&mut app, "q");
+        assert!(app.quit);
+    }
+
+    #[test]
+    fn test_draw() {
+        let registers = parse_registers("register CR u32\nfield EN 0\nfield DIV 7:4\n")
+            .unwrap();
+        let mut app = App::new(IntType::U32(0x131), registers, Some(0));
+        let s = screen(&app);
+        assert!(s.contains("bitwister u32"));
+        assert!(s.contains("31 30 29"));
+        assert!(s.contains("hex     0x00000131"));
+        assert!(s.contains("dec     305"));
+        assert!(s.contains("DIV [7:4]   = 0x3 (3)"));
+
+        press(&mut app, "w");
+        let s = screen(&app);
+        assert!(s.contains("63 62"));
+        assert!(s.contains("31 30"));
+        assert!(s.contains("hex     0x0000000000000131"));
+    }
+
+}
@@ -0,0 +1,40 @@
+/// exit codes and argument handling of the bitwister command line
+/// 0xca7
+use std::process::{Command, Output};
+
+fn bitwister(args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_bitwister"))
+        .args(args)
+        .output()
+        .unwrap()
+}
+
+#[test]
+fn test_exit_codes() {
+    let output = bitwister(&["-q", "1u8", "+", "1u8"]);
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "0x02\n");
+
+    let output = bitwister(&["-q", "1", "/", "0"]);
+    assert_eq!(output.status.code(), Some(1));
+    assert!(String::from_utf8_lossy(&output.stderr).contains("division by zero at offset 2"));
+    let output = bitwister(&["--json", "1", "+"]);
+    assert_eq!(output.status.code(), Some(1));
+    assert!(String::from_utf8_lossy(&output.stdout)
+        .contains("\"error\":\"unexpected end of expression\""));
+
+    // an unknown flag is a usage error, not an expression
+    let output = bitwister(&["--bogus"]);
+    assert_eq!(output.status.code(), Some(2));
+    assert!(String::from_utf8_lossy(&output.stderr).contains("--bogus"));
+    assert_eq!(bitwister(&["-q", "1", "--bogus"]).status.code(), Some(2));
+}
+
+#[test]
+fn test_flags_and_expression() {
+    // flags may follow the expression, `--` passes one that starts with `-`
+    let output = bitwister(&["1", "-", "2", "-w", "u8", "-q"]);
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "0xff\n");
+    let output = bitwister(&["-q", "--", "1 - 2"]);
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "0xffffffff\n");
+}
@@ -0,0 +1,530 @@
+/// translate expressions between the infix notation of bitwister, the
+/// prefix notation of pn_bitwister and the postfix notation of its stack
+/// 0xca7
+use alloc::{
+    boxed::Box,
+    format,
+    string::{String, ToString},
+    vec,
+    vec::Vec,
+};
+use core::fmt;
+use core::ops::Range;
+
+use crate::evaluator::{self, precedence, Literal};
+use crate::pn::{self, Expr, Token, MAX_DEPTH};
+use crate::{
+    EvalError,
+    Evaluator,
+    Operation,
+    Width,
+};
+
+/// how an expression is written
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Notation {
+    /// `(0x13 << 4) | 1` as in bitwister
+    Infix,
+    /// `| << 0x13 4 1` as in pn_bitwister
+    Prefix,
+    /// `0x13 4 << 1 |` as on the RPN stack
+    Postfix,
+}
+
+impl Notation {
+
+    pub const ALL: [Notation; 3] = [Notation::Infix, Notation::Prefix, Notation::Postfix];
+
+}
+
+impl fmt::Display for Notation {
+
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Notation::Infix => write!(f, "infix"),
+            Notation::Prefix => write!(f, "prefix"),
+            Notation::Postfix => write!(f, "postfix"),
+        }
+    }
+
+}
+
+impl core::str::FromStr for Notation {
+    type Err = ConvertError;
+
+    /// parse `infix`, `prefix` or `pn`, `postfix` or `rpn`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "infix" => Ok(Notation::Infix),
+            "prefix" | "pn" => Ok(Notation::Prefix),
+            "postfix" | "rpn" => Ok(Notation::Postfix),
+            _ => Err(ConvertError::UnknownNotation(s.to_string())),
+        }
+    }
+}
+
+/// why an expression can't be converted
+#[derive(Debug, Clone, PartialEq)]
+pub enum ConvertError {
+    UnknownNotation(String),
+    /// the expression is not valid in its notation, what is wrong and the
+    /// byte range of the problem
+    Parse(String, Range<usize>),
+    /// a variable or custom operation, only bitwister knows them
+    Name(String),
+    /// a width form inside of an expression, e.g. `+ (u8 0x1ff) 1`, only
+    /// prefix notation can switch the width of a part
+    NestedWidth(Width),
+    /// literals of different widths, e.g. `1u8 + 1u16`
+    MixedWidths(Width, Width),
+}
+
+impl fmt::Display for ConvertError {
+
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConvertError::UnknownNotation(name) => {
+                write!(f, "error: unknown notation {name}, expected infix, prefix or postfix")
+            },
+            ConvertError::Parse(msg, span) => write!(f, "error: {msg} at offset {}", span.start),
+            ConvertError::Name(name) => {
+                write!(f, "error: {name} is a variable or custom operation, it can't be converted")
+            },
+            ConvertError::NestedWidth(width) => {
+                write!(f, "error: only a whole expression can be calculated in {width}")
+            },
+            ConvertError::MixedWidths(a, b) => {
+                write!(f, "error: operands of different widths {a} and {b}")
+            },
+        }
+    }
+
+}
+
+impl core::error::Error for ConvertError {}
+
+/// an expression independent of its notation
+#[derive(Debug, Clone, PartialEq)]
+enum Node {
+    /// a number and if it was written in hex
+    Number(u64, bool),
+    Unary(Operation, Box<Node>),
+    /// binary operations applied from left to right, `1 - 2 + 3` is `1`
+    /// followed by `- 2` and `+ 3`
+    Chain(Box<Node>, Vec<(Operation, Node)>),
+}
+
+impl Node {
+
+    /// `lhs op rhs`, a chain on the left grows instead of nesting
+    fn binary(lhs: Node, op: Operation, rhs: Node) -> Node {
+        match lhs {
+            Node::Chain(first, mut rest) => {
+                rest.push((op, rhs));
+                Node::Chain(first, rest)
+            },
+            lhs => Node::Chain(Box::new(lhs), vec![(op, rhs)]),
+        }
+    }
+
+}
+
+/// a parsed expression that can be written in every notation, e.g. to
+/// pretty-print it: spacing and literals are normalised and infix gets
+/// only the parentheses it needs
+#[derive(Debug, Clone, PartialEq)]
+pub struct Expression {
+    /// the width the whole expression is calculated in, if it has one
+    width: Option<Width>,
+    node: Node,
+}
+
+impl Expression {
+
+    pub fn parse(input: &str, notation: Notation) -> Result<Self, ConvertError> {
+        match notation {
+            Notation::Infix => Expression::parse_infix(input),
+            Notation::Prefix => Expression::parse_prefix(input),
+            Notation::Postfix => Expression::parse_postfix(input),
+        }
+    }
+
+    /// the expression in `notation`. every operation of the expression
+    /// has its own node, so a chain like `(+ 1 2 3)` is written as
+    /// `1 + 2 + 3` and `+ + 1 2 3`.
+    pub fn render(&self, notation: Notation) -> String {
+        match (notation, self.width) {
+            (Notation::Infix, _) => self.infix(&self.node).0,
+            (Notation::Prefix, Some(width)) => format!("{width} {}", self.prefix(&self.node)),
+            (Notation::Prefix, None) => self.prefix(&self.node),
+            (Notation::Postfix, Some(width)) => format!("{width} {}", self.postfix(&self.node)),
+            (Notation::Postfix, None) => self.postfix(&self.node),
+        }
+    }
+
+    /// a number, with the width as suffix in infix
+    fn number(&self, n: u64, is_hex: bool, notation: Notation) -> String {
+        let n = if is_hex { format!("{n:#x}") } else { n.to_string() };
+        match (notation, self.width) {
+            (Notation::Infix, Some(width)) => format!("{n}{width}"),
+            _ => n,
+        }
+    }
+
+    /// the node in infix and the precedence of its weakest binary
+    /// operation outside of parentheses
+    fn infix(&self, node: &Node) -> (String, Option<u8>) {
+        match node {
+            Node::Number(n, is_hex) => (self.number(*n, *is_hex, Notation::Infix), None),
+            Node::Unary(op, operand) => {
+                let operand = match self.infix(operand) {
+                    (operand, Some(_)) => format!("({operand})"),
+                    (operand, None) => operand,
+                };
+                // a word needs a space, a symbol doesn't
+                if op.symbol().chars().all(char::is_alphabetic) {
+                    (format!("{} {operand}", op.symbol()), None)
+                } else {
+                    (format!("{}{operand}", op.symbol()), None)
+                }
+            },
+            Node::Chain(first, rest) => {
+                // binary operations are left associative: what is on the
+                // left only needs parentheses if it binds weaker, an operand
+                // on the right also if it binds as strong
+                let (first, mut weakest) = self.infix(first);
+                let mut opens = 0;
+                let mut s = String::new();
+                for (op, operand) in rest {
+                    // SAFETY: every binary operation has a precedence
+                    let prec = precedence(op.symbol()).unwrap();
+                    if weakest.is_some_and(|weakest| weakest < prec) {
+                        opens += 1;
+                        s.push(')');
+                        weakest = None;
+                    }
+                    let operand = match self.infix(operand) {
+                        (operand, Some(inner)) if inner <= prec => format!("({operand})"),
+                        (operand, _) => operand,
+                    };
+                    s.push_str(&format!(" {} {operand}", op.symbol()));
+                    weakest = Some(weakest.map_or(prec, |weakest| weakest.min(prec)));
+                }
+                ("(".repeat(opens) + &first + &s, weakest)
+            },
+        }
+    }
+
+    fn prefix(&self, node: &Node) -> String {
+        match node {
+            Node::Number(n, is_hex) => self.number(*n, *is_hex, Notation::Prefix),
+            Node::Unary(op, operand) => format!("{} {}", op.symbol(), self.prefix(operand)),
+            Node::Chain(first, rest) => {
+                // the last operation comes first
+                let mut words: Vec<String> = rest.iter()
+                    .rev()
+                    .map(|(op, _)| op.symbol().to_string())
+                    .collect();
+                words.push(self.prefix(first));
+                words.extend(rest.iter().map(|(_, operand)| self.prefix(operand)));
+                words.join(" ")
+            },
+        }
+    }
+
+    fn postfix(&self, node: &Node) -> String {
+        match node {
+            Node::Number(n, is_hex) => self.number(*n, *is_hex, Notation::Postfix),
+            Node::Unary(op, operand) => format!("{} {}", self.postfix(operand), op.symbol()),
+            Node::Chain(first, rest) => {
+                let mut words = vec![self.postfix(first)];
+                for (op, operand) in rest {
+                    words.push(self.postfix(operand));
+                    words.push(op.symbol().to_string());
+                }
+                words.join(" ")
+            },
+        }
+    }
+
+    fn parse_prefix(input: &str) -> Result<Self, ConvertError> {
+        let expr = pn::parse(input)
+            .map_err(|err| ConvertError::Parse(err.kind.to_string(), err.span))?;
+        let (width, expr) = match expr {
+            Expr::Width(width, _, expr) => (Some(width), *expr),
+            expr => (None, expr),
+        };
+
+        fn node(input: &str, expr: Expr) -> Result<Node, ConvertError> {
+            match expr {
+                Expr::Number(n, span) => Ok(Node::Number(n, input[span].starts_with("0x"))),
+                Expr::Op(op, _, operands) if op.is_unary() => {
+                    // SAFETY: a unary operation has one operand
+                    let operand = operands.into_iter().next().unwrap();
+                    Ok(Node::Unary(op, Box::new(node(input, operand)?)))
+                },
+                Expr::Op(op, _, operands) => {
+                    let mut operands = operands.into_iter();
+                    // SAFETY: a binary operation has at least two operands
+                    let first = node(input, operands.next().unwrap())?;
+                    operands.try_fold(first, |lhs, operand| {
+                        Ok(Node::binary(lhs, op, node(input, operand)?))
+                    })
+                },
+                Expr::Width(width, ..) => Err(ConvertError::NestedWidth(width)),
+            }
+        }
+        Ok(Expression { width, node: node(input, expr)? })
+    }
+
+    fn parse_postfix(input: &str) -> Result<Self, ConvertError> {
+        let tokens = pn::Tokenizer::new().tokenize(input)
+            .map_err(|err| ConvertError::Parse(err.kind.to_string(), err.span))?;
+        let mut width = None;
+        // the nodes and how deep they are nested
+        let mut stack: Vec<(Node, usize)> = vec![];
+
+        for (i, (token, span)) in tokens.into_iter().enumerate() {
+            match token {
+                Token::Number(n) => {
+                    stack.push((Node::Number(n, input[span].starts_with("0x")), 1));
+                },
+                Token::Op(op) => {
+                    let needed = if op.is_unary() { 1 } else { 2 };
+                    if stack.len() < needed {
+                        return Err(ConvertError::Parse(format!("{} takes {needed} operand{}, \
+                            found {}", op.symbol(), if needed == 1 { "" } else { "s" },
+                            stack.len()), span));
+                    }
+                    // SAFETY: checked above
+                    let (rhs, rhs_depth) = stack.pop().unwrap();
+                    let (node, depth) = match op.is_unary() {
+                        true => (Node::Unary(op, Box::new(rhs)), rhs_depth + 1),
+                        false => {
+                            // SAFETY: checked above
+                            let (lhs, depth) = stack.pop().unwrap();
+                            // a chain on the left grows without nesting
+                            let depth = match lhs {
+                                Node::Chain(..) => depth.max(rhs_depth + 1),
+                                _ => depth.max(rhs_depth) + 1,
+                            };
+                            (Node::binary(lhs, op, rhs), depth)
+                        },
+                    };
+                    if depth > MAX_DEPTH {
+                        return Err(ConvertError::Parse(String::from("nested too deep"), span));
+                    }
+                    stack.push((node, depth));
+                },
+                // a width switches the whole stack, so it has to come first
+                Token::Width(bits) if i == 0 => width = Some(bits),
+                Token::Width(bits) => return Err(ConvertError::NestedWidth(bits)),
+                Token::Open | Token::Close => {
+                    return Err(ConvertError::Parse(
+                        format!("unexpected {}", &input[span.clone()]), span));
+                },
+            }
+        }
+
+        match (stack.pop(), stack.is_empty()) {
+            (Some((node, _)), true) => Ok(Expression { width, node }),
+            (Some(_), false) => Err(ConvertError::Parse(
+                format!("{} operands are left over", stack.len()), 0..input.len())),
+            (None, _) => Err(ConvertError::Parse(String::from("empty expression"),
+                0..input.len())),
+        }
+    }
+
+    /// an infix expression, parsed like `Evaluator` does
+    fn parse_infix(input: &str) -> Result<Self, ConvertError> {
+        let tree = Evaluator::new().parse(input).map_err(|(err, span)| match err {
+            // without variables or custom operations every name is unknown
+            EvalError::UnknownVariable(name) => ConvertError::Name(name),
+            err => ConvertError::Parse(err.to_string().trim_start_matches("error: ").to_string(),
+                span),
+        })?;
+        let mut width = None;
+
+        fn node(tree: evaluator::Node, width: &mut Option<Width>) -> Result<Node, ConvertError> {
+            match tree {
+                evaluator::Node::Value(Literal::Untyped(text, n)) => {
+                    Ok(Node::Number(n, text.starts_with("0x")))
+                },
+                // a suffix sets the width of the whole expression
+                evaluator::Node::Value(Literal::Typed(step)) => {
+                    match *width {
+                        Some(width) if width != step.value.width() => {
+                            return Err(ConvertError::MixedWidths(width, step.value.width()));
+                        },
+                        _ => *width = Some(step.value.width()),
+                    }
+                    Ok(Node::Number(step.value.as_u64(), step.label.starts_with("0x")))
+                },
+                evaluator::Node::Unary(op, _, operand) => {
+                    // SAFETY: without custom operations every operation is built in
+                    let op = Operation::new_from_str(op).unwrap();
+                    Ok(Node::Unary(op, Box::new(node(*operand, width)?)))
+                },
+                evaluator::Node::Chain(first, rest) => {
+                    let first = node(*first, width)?;
+                    rest.into_iter().try_fold(first, |lhs, (op, _, operand)| {
+                        // SAFETY: without custom operations every operation is built in
+                        let op = Operation::new_from_str(op).unwrap();
+                        Ok(Node::binary(lhs, op, node(operand, width)?))
+                    })
+                },
+            }
+        }
+        let node = node(tree, &mut width)?;
+        Ok(Expression { width, node })
+    }
+
+}
+
+/// convert `input` from one notation to another
+pub fn convert(input: &str, from: Notation, to: Notation) -> Result<String, ConvertError> {
+    Ok(Expression::parse(input, from)?.render(to))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pn::{Bits, Calculation};
+    use crate::rpn::Stack;
+
+    #[test]
+    fn test_convert() {
+        let cases = [
+            ("0x13 << 4 | 1", "| << 0x13 4 1", "0x13 4 << 1 |"),
+            ("1 + 2 * 3", "+ 1 * 2 3", "1 2 3 * +"),
+            ("(1 + 2) * 3", "* + 1 2 3", "1 2 + 3 *"),
+            ("10 - 2 - 3", "- - 10 2 3", "10 2 - 3 -"),
+            ("10 - (2 - 3)", "- 10 - 2 3", "10 2 3 - -"),
+            ("!~1 & 0xf0", "& ! ~ 1 0xf0", "1 ~ ! 0xf0 &"),
+            ("!(1 + 2)", "! + 1 2", "1 2 + !"),
+            ("e 0x1234 >> 8", ">> e 0x1234 8", "0x1234 e 8 >>"),
+        ];
+        for (infix, prefix, postfix) in cases {
+            for from in [(infix, Notation::Infix), (prefix, Notation::Prefix),
+                (postfix, Notation::Postfix)] {
+                let expr = Expression::parse(from.0, from.1).unwrap();
+                assert_eq!(expr.render(Notation::Infix), infix, "from {}", from.0);
+                assert_eq!(expr.render(Notation::Prefix), prefix, "from {}", from.0);
+                assert_eq!(expr.render(Notation::Postfix), postfix, "from {}", from.0);
+            }
+        }
+    }
+
+    #[test]
+    fn test_pretty_print() {
+        let pretty = |input, notation| convert(input, notation, notation).unwrap();
+        assert_eq!(pretty("(( 0x00FF)+1)*(2)", Notation::Infix), "(0xff + 1) * 2");
+        assert_eq!(pretty("0xABu8&1", Notation::Infix), "0xabu8 & 1u8");
+        assert_eq!(pretty("  +\t0x0a   1", Notation::Prefix), "+ 0xa 1");
+        assert_eq!(pretty("(+ 1 2 3)", Notation::Prefix), "+ + 1 2 3");
+        assert_eq!(pretty("(1 | 2) + 3 * 4 << 5", Notation::Infix), "(1 | 2) + 3 * 4 << 5");
+        assert_eq!(pretty("((1 | 2) + 3) & 4", Notation::Infix), "(1 | 2) + 3 & 4");
+        assert_eq!(pretty("1 | 2 + 3 | (4 ^ 5)", Notation::Infix), "1 | 2 + 3 | 4 ^ 5");
+        assert_eq!(pretty("1 2 3 + -", Notation::Postfix), "1 2 3 + -");
+        assert_eq!(convert("(| 1 2 4)", Notation::Prefix, Notation::Infix).unwrap(), "1 | 2 | 4");
+
+        // the width of a whole expression is a suffix of every literal in
+        // infix and a width in front of the others
+        assert_eq!(convert("0xffu8 + 1", Notation::Infix, Notation::Prefix).unwrap(),
+            "u8 + 0xff 1");
+        assert_eq!(convert("(u16 (! 0))", Notation::Prefix, Notation::Infix).unwrap(),
+            "!0u16");
+        assert_eq!(convert("u16 0 !", Notation::Postfix, Notation::Prefix).unwrap(),
+            "u16 ! 0");
+    }
+
+    #[test]
+    fn test_errors() {
+        let infix = |input| Expression::parse(input, Notation::Infix).unwrap_err();
+        assert_eq!(infix("1u8 + 1u16"), ConvertError::MixedWidths(Width::U8, Width::U16));
+        assert_eq!(infix("EN | 1"), ConvertError::Name(String::from("EN")));
+        assert_eq!(infix("(1 + 2"),
+            ConvertError::Parse(String::from("unexpected end of expression"), 6..6));
+        assert_eq!(infix("1 ! 2"), ConvertError::Parse(String::from("unexpected !"), 2..3));
+        assert_eq!(infix(""), ConvertError::Parse(String::from("empty expression"), 0..0));
+        assert_eq!(infix(&("~".repeat(100_000) + "1")),
+            ConvertError::Parse(String::from("nested too deep"), 256..257));
+
+        let prefix = |input| Expression::parse(input, Notation::Prefix).unwrap_err();
+        assert_eq!(prefix("+ (u8 1) 2"), ConvertError::NestedWidth(Width::U8));
+        assert!(matches!(prefix("+ 1"), ConvertError::Parse(_, span) if span == (0..1)));
+
+        let postfix = |input| Expression::parse(input, Notation::Postfix).unwrap_err();
+        assert_eq!(postfix("1 u8 +"), ConvertError::NestedWidth(Width::U8));
+        assert_eq!(postfix("1 +"), ConvertError::Parse(
+            String::from("+ takes 2 operands, found 1"), 2..3));
+        assert_eq!(postfix("1 2"), ConvertError::Parse(
+            String::from("1 operands are left over"), 0..3));
+
+        let long = ["1"; MAX_DEPTH + 1].join(" ") + &" +".repeat(MAX_DEPTH);
+        assert_eq!(postfix(&long), ConvertError::Parse(String::from("nested too deep"),
+            1024..1025));
+
+        assert_eq!("x".parse::<Notation>().unwrap_err().to_string(),
+            "error: unknown notation x, expected infix, prefix or postfix");
+    }
+
+    #[test]
+    fn test_same_as_evaluator() {
+        // infix is parsed by the evaluator, so whatever it can't parse
+        // can't be converted either
+        let evaluator = Evaluator::new();
+        for expr in ["(1 + 2) * 3u8", "1 ! 2", "(1", "1u7", "0xfg", "1u8 + 1u16", "1 # 2",
+            "(1u8 + 2) - (3 + 4u16)", ")"] {
+            assert_eq!(Expression::parse(expr, Notation::Infix).is_ok(),
+                evaluator.evaluate(expr).is_ok(), "{expr}");
+        }
+        let long = ["0x1"; 100_000].join(" - ");
+        assert_eq!(convert(&long, Notation::Infix, Notation::Infix).unwrap(), long);
+        assert!(convert(&long, Notation::Infix, Notation::Prefix).unwrap().starts_with("- - "));
+    }
+
+    #[test]
+    fn test_same_in_both_engines() {
+        let exprs = [
+            "(0x13 << 4) | 1",
+            "0xfe + 2 * 3 - 1",
+            "(0xff + 1) | 1",
+            "10 - 2 - 3 - 4",
+            "!~1 & 0xf0 ^ 0x0f",
+            "0x8001 <<< 1 >>> 3",
+            "e 0x1234 + r 0x80",
+            "100 / 7 * 7",
+            "0xffu8 + 1",
+            "0x1ffff + 1",
+            "(1 + 2) * 3u8",
+            "1 + 2 + 3u8",
+            "~1 & 0xffu8",
+            "((1 | 2) + 3) & 4 - 5",
+        ];
+        for bits in [Bits::U8, Bits::U16, Bits::U32, Bits::U64] {
+            let mut evaluator = Evaluator::new();
+            evaluator.set_width(bits);
+            for infix in exprs {
+                let expr = Expression::parse(infix, Notation::Infix).unwrap();
+                let expected = evaluator.evaluate(infix).unwrap();
+
+                let prefix = expr.render(Notation::Prefix);
+                let evaluation = Calculation::new(bits)
+                    .calculate(&pn::parse(&prefix).unwrap())
+                    .unwrap();
+                assert_eq!(evaluation, expected, "{infix} as {prefix} in {bits}");
+
+                let mut stack = Stack::new(bits);
+                let outcome = stack.eval(&expr.render(Notation::Postfix)).unwrap();
+                assert_eq!((stack.entries(), outcome.overflow),
+                    (&[expected.value][..], expected.overflow), "{infix} in {bits}");
+
+                // and back
+                let back = Expression::parse(&prefix, Notation::Prefix).unwrap();
+                assert_eq!(evaluator.evaluate(&back.render(Notation::Infix)), Ok(expected));
+            }
+        }
+    }
+
+}
@@ -0,0 +1,807 @@
+/// an evaluator to embed bitwister: holds the default width, variables and
+/// custom operations, and returns results without printing anything
+/// 0xca7
+use alloc::{
+    boxed::Box,
+    collections::BTreeMap,
+    string::{String, ToString},
+    vec,
+    vec::Vec,
+};
+use core::fmt;
+use core::ops::Range;
+
+use crate::pn::MAX_DEPTH;
+use crate::{
+    Arity,
+    IntType,
+    IntTypeConversionError,
+    Operation,
+    OperationError,
+    OperationInfo,
+    Overflow,
+    Step,
+    Truncation,
+    Width,
+};
+
+/// why an expression could not be evaluated
+#[derive(Debug, Clone, PartialEq)]
+pub enum EvalError {
+    /// the expression is empty
+    Empty,
+    /// a character that is neither part of a number, a name nor an operation
+    InvalidToken(String),
+    /// a number that is not valid, e.g. `0xfg` or `1u7`
+    InvalidLiteral(String),
+    /// a name that is neither a variable nor an operation
+    UnknownVariable(String),
+    /// a variable name that is not a word, or is an operation or a width,
+    /// e.g. `+`, `0x1` or `u8`
+    InvalidName(String),
+    /// the expression ended early, e.g. `1 +`
+    UnexpectedEnd,
+    /// a token in the wrong place, e.g. `1 ! 2` or a `)` too many
+    Unexpected(String),
+    /// operands of different widths, e.g. `1u8 + 1u16`
+    WidthMismatch(Width, Width),
+    /// operations or parentheses are nested deeper than `MAX_DEPTH`, when
+    /// explaining also operations that follow each other
+    TooDeep,
+    DivisionByZero,
+    /// a custom operation failed
+    Operation { name: String, msg: String },
+}
+
+impl fmt::Display for EvalError {
+
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EvalError::Empty => write!(f, "error: empty expression"),
+            EvalError::InvalidToken(token) => write!(f, "error: invalid token {token}"),
+            EvalError::InvalidLiteral(lit) => write!(f, "error: invalid number {lit}"),
+            EvalError::UnknownVariable(name) => write!(f, "error: unknown variable {name}"),
+            EvalError::InvalidName(name) => write!(f, "error: invalid variable name {name}"),
+            EvalError::UnexpectedEnd => write!(f, "error: unexpected end of expression"),
+            EvalError::Unexpected(token) => write!(f, "error: unexpected {token}"),
+            EvalError::WidthMismatch(a, b) => {
+                write!(f, "error: operands of different widths {a} and {b}")
+            },
+            EvalError::TooDeep => write!(f, "error: nested too deep"),
+            EvalError::DivisionByZero => write!(f, "error: division by zero"),
+            EvalError::Operation { name, msg } => write!(f, "error: {name}: {msg}"),
+        }
+    }
+
+}
+
+impl core::error::Error for EvalError {}
+
+/// the result of an evaluation
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Evaluation {
+    pub value: IntType,
+    /// `Occured` if any operation of the expression overflowed
+    pub overflow: Overflow,
+    /// every literal without a suffix that did not fit its width
+    pub truncations: Vec<Truncation>,
+    /// the values the `r` operation was applied to, in order, for the
+    /// caller to show in register view
+    pub register_views: Vec<IntType>,
+}
+
+/// the implementation of a custom operation, gets one operand for a unary
+/// and two for a binary operation, all of the same width
+pub type Implementation = Box<dyn Fn(&[IntType]) -> Result<IntType, String> + Send + Sync>;
+
+struct CustomOperation {
+    info: OperationInfo,
+    implementation: Implementation,
+}
+
+/// an operand as it was entered, either with a `u` suffix that fixes its
+/// width, or without one. untyped literals keep their text for reporting,
+/// typed ones the step that led to them.
+pub(crate) enum Literal {
+    Typed(Step),
+    Untyped(String, u64),
+}
+
+impl Literal {
+
+    pub(crate) fn new_from_str(s: &str) -> Result<Literal, IntTypeConversionError> {
+        if s.contains('u') {
+            return Ok(Literal::Typed(Step::value(s, IntType::new_from_str(s)?)));
+        }
+        let (digits, radix) = match s.strip_prefix("0x") {
+            Some(digits) => (digits, 16),
+            None => (s, 10),
+        };
+        match u64::from_str_radix(digits, radix) {
+            Ok(v) => Ok(Literal::Untyped(s.to_string(), v)),
+            Err(_) => Err(IntTypeConversionError::InvalidInteger),
+        }
+    }
+
+    /// the width of a typed literal
+    fn width(&self) -> Option<Width> {
+        match self {
+            Literal::Typed(step) => Some(step.value.width()),
+            Literal::Untyped(..) => None,
+        }
+    }
+
+    /// the value of the literal, an untyped one gets `width` and is
+    /// truncated if it doesn't fit
+    fn resolve(self, width: Width) -> (Step, Option<Truncation>) {
+        match self {
+            Literal::Typed(step) => (step, None),
+            Literal::Untyped(text, raw) => {
+                let (v, truncated) = IntType::from_u64_truncating(raw, width);
+                let truncation = truncated.then(|| Truncation {
+                    literal: text.clone(),
+                    width,
+                    value: v.as_u64(),
+                });
+                (Step { truncation: truncation.clone(), ..Step::value(&text, v) }, truncation)
+            },
+        }
+    }
+}
+
+/// an expression as parsed. the width of an untyped literal is only known
+/// once the whole expression is: it takes the width of the typed operands
+/// it is combined with, so `1 + 2 + 3u8` is calculated in u8.
+pub(crate) enum Node<'a> {
+    /// a number or the value of a variable
+    Value(Literal),
+    /// a unary operation, the range of its symbol and its operand
+    Unary(&'a str, Range<usize>, Box<Node<'a>>),
+    /// binary operations applied from left to right, `1 - 2 + 3` is `1`
+    /// followed by `- 2` and `+ 3`
+    Chain(Box<Node<'a>>, Vec<(&'a str, Range<usize>, Node<'a>)>),
+}
+
+impl Node<'_> {
+
+    /// the width of the first typed operand
+    fn width(&self) -> Option<Width> {
+        match self {
+            Node::Value(lit) => lit.width(),
+            Node::Unary(_, _, operand) => operand.width(),
+            Node::Chain(first, rest) => first.width()
+                .or_else(|| rest.iter().find_map(|(_, _, operand)| operand.width())),
+        }
+    }
+
+}
+
+/// a token of an expression
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Token<'a> {
+    /// a number, with or without a `u` suffix
+    Lit(&'a str),
+    /// the symbol of an operation
+    Op(&'a str),
+    /// the name of a variable
+    Name(&'a str),
+    Open,
+    Close,
+}
+
+impl fmt::Display for Token<'_> {
+
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Token::Lit(s) | Token::Op(s) | Token::Name(s) => write!(f, "{s}"),
+            Token::Open => write!(f, "("),
+            Token::Close => write!(f, ")"),
+        }
+    }
+
+}
+
+/// a token or an error with the byte range of the expression it stands for
+pub(crate) type Spanned<T> = (T, Range<usize>);
+
+/// the binding strength of a binary operation, as in C. higher binds
+/// tighter, unary operations bind tightest.
+pub(crate) fn precedence(op: &str) -> Option<u8> {
+    match op {
+        "|" => Some(0),
+        "^" => Some(1),
+        "&" => Some(2),
+        "<<" | ">>" | "<<<" | ">>>" => Some(3),
+        "+" | "-" => Some(4),
+        "*" | "/" => Some(5),
+        _ => None,
+    }
+}
+
+/// custom binary operations bind like `*`
+const CUSTOM_PRECEDENCE: u8 = 5;
+
+fn is_word(c: char) -> bool {
+    c.is_ascii_alphanumeric() || c == '_'
+}
+
+/// evaluates expressions with a default width, variables and custom
+/// operations:
+///
+/// ```
+/// use btcore::{Arity, Evaluator, IntType};
+///
+/// let mut evaluator = Evaluator::new();
+/// evaluator.set_var("EN", IntType::U8(1)).unwrap();
+/// evaluator.register_operation("popcnt", Arity::Unary, "count the set bits",
+///     |v| Ok(IntType::from_u64_truncating(v[0].as_u64().count_ones() as u64,
+///         v[0].width()).0)).unwrap();
+///
+/// let evaluation = evaluator.evaluate("popcnt 0xf0u8 | EN").unwrap();
+/// assert_eq!(evaluation.value, IntType::U8(5));
+/// ```
+#[derive(Default)]
+pub struct Evaluator {
+    /// width of literals without a `u` suffix if it can't be inferred from
+    /// the other operand
+    width: Width,
+    vars: BTreeMap<String, IntType>,
+    operations: Vec<CustomOperation>,
+}
+
+impl Evaluator {
+
+    pub fn new() -> Evaluator {
+        Evaluator::default()
+    }
+
+    /// the default width of literals without a suffix
+    pub fn width(&self) -> Width {
+        self.width
+    }
+
+    pub fn set_width(&mut self, width: Width) {
+        self.width = width;
+    }
+
+    pub fn var(&self, name: &str) -> Option<IntType> {
+        self.vars.get(name).copied()
+    }
+
+    pub fn vars(&self) -> &BTreeMap<String, IntType> {
+        &self.vars
+    }
+
+    /// set the variable `name`, names are words that are not an operation
+    /// or a width
+    pub fn set_var(&mut self, name: &str, value: IntType) -> Result<(), EvalError> {
+        let valid = name.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+            && name.chars().all(is_word)
+            && !self.is_operation(name)
+            && name.parse::<Width>().is_err();
+        if !valid {
+            return Err(EvalError::InvalidName(name.to_string()));
+        }
+        self.vars.insert(name.to_string(), value);
+        Ok(())
+    }
+
+    pub fn remove_var(&mut self, name: &str) -> Option<IntType> {
+        self.vars.remove(name)
+    }
+
+    /// add an operation called `name`, a word like `popcnt` or a single
+    /// symbol like `%`. custom binary operations bind like `*`.
+    pub fn register_operation<F>(&mut self, name: &str, arity: Arity, description: &str,
+        implementation: F) -> Result<(), OperationError>
+        where F: Fn(&[IntType]) -> Result<IntType, String> + Send + Sync + 'static {
+
+        let mut chars = name.chars();
+        let valid = match (chars.next(), chars.next()) {
+            (Some(c), _) if c.is_ascii_alphabetic() || c == '_' => name.chars().all(is_word),
+            (Some(c), None) => c.is_ascii_punctuation() && !"()$".contains(c),
+            _ => false,
+        };
+        if self.is_operation(name) {
+            return Err(OperationError::AlreadyDefined(name.to_string()));
+        }
+        if !valid {
+            return Err(OperationError::InvalidName(name.to_string()));
+        }
+
+        self.operations.push(CustomOperation {
+            info: OperationInfo {
+                symbol: name.to_string(),
+                arity,
+                description: description.to_string(),
+                example: None,
+            },
+            implementation: Box::new(implementation),
+        });
+        Ok(())
+    }
+
+    /// every operation, the built-in ones first
+    pub fn operations(&self) -> Vec<OperationInfo> {
+        let mut infos = Operation::infos();
+        infos.extend(self.operations.iter().map(|op| op.info.clone()));
+        infos
+    }
+
+    fn custom(&self, name: &str) -> Option<&CustomOperation> {
+        self.operations.iter().find(|op| op.info.symbol == name)
+    }
+
+    fn is_operation(&self, name: &str) -> bool {
+        Operation::new_from_str(name).is_ok() || self.custom(name).is_some()
+    }
+
+    /// split an expression into tokens with their byte range, spaces
+    /// between them are optional except around operations that are words
+    fn tokenize<'a>(&self, s: &'a str)
+        -> Result<Vec<Spanned<Token<'a>>>, Spanned<EvalError>> {
+        let mut tokens = vec![];
+        let mut rest = s;
+        loop {
+            rest = rest.trim_start();
+            let start = s.len() - rest.len();
+            let c = match rest.chars().next() {
+                Some(c) => c,
+                None => return Ok(tokens),
+            };
+            let len = match c {
+                '(' | ')' => 1,
+                '<' | '>' => rest.chars().take(3).take_while(|d| *d == c).count(),
+                c if is_word(c) => rest.find(|d: char| !is_word(d)).unwrap_or(rest.len()),
+                c => c.len_utf8(),
+            };
+            let (token, tail) = rest.split_at(len);
+            let span = start..start + len;
+            tokens.push((match token {
+                "(" => Token::Open,
+                ")" => Token::Close,
+                _ if self.is_operation(token) => Token::Op(token),
+                _ if c.is_ascii_digit() => Token::Lit(token),
+                _ if is_word(c) => Token::Name(token),
+                _ => return Err((EvalError::InvalidToken(token.to_string()), span)),
+            }, span));
+            rest = tail;
+        }
+    }
+
+    /// evaluate `expr`. an expression combines numbers and variables with
+    /// operations, binary operations bind as in C and parentheses group,
+    /// e.g. `(0x13u32 << 4) | EN`.
+    pub fn evaluate(&self, expr: &str) -> Result<Evaluation, EvalError> {
+        self.evaluate_with_span(expr).map_err(|(error, _)| error)
+    }
+
+    /// like `evaluate`, a failure comes with the byte range of `expr` that
+    /// caused it: the offending token, the operation that failed, or the
+    /// empty range at the end if the expression ended early
+    pub fn evaluate_with_span(&self, expr: &str)
+        -> Result<Evaluation, (EvalError, Range<usize>)> {
+        self.run(expr, false).map(|(evaluation, _)| evaluation)
+    }
+
+    /// like `evaluate`, with every step that led to the result
+    pub fn explain(&self, expr: &str) -> Result<(Evaluation, Step), EvalError> {
+        self.run(expr, true).map_err(|(error, _)| error)
+    }
+
+    /// evaluate `expr`, the step of the result only has operands if
+    /// `explain` is set
+    fn run(&self, expr: &str, explain: bool)
+        -> Result<(Evaluation, Step), (EvalError, Range<usize>)> {
+        let mut parser = self.parser(expr, explain)?;
+        parser.parse().map_err(|error| (error, parser.span.clone()))
+    }
+
+    /// parse `expr` without evaluating it, e.g. to write it in another
+    /// notation
+    pub(crate) fn parse<'a>(&self, expr: &'a str) -> Result<Node<'a>, Spanned<EvalError>> {
+        let mut parser = self.parser(expr, false)?;
+        parser.tree().map_err(|error| (error, parser.span.clone()))
+    }
+
+    fn parser<'a>(&self, expr: &'a str, explain: bool)
+        -> Result<Parser<'a, '_>, Spanned<EvalError>> {
+        let tokens = self.tokenize(expr)?;
+        if tokens.is_empty() {
+            return Err((EvalError::Empty, 0..expr.len()));
+        }
+        Ok(Parser {
+            evaluator: self,
+            tokens,
+            pos: 0,
+            end: expr.len(),
+            span: 0..0,
+            depth: 0,
+            explain,
+            overflow: Overflow::NotPossible,
+            truncations: vec![],
+            register_views: vec![],
+        })
+    }
+
+    /// evaluate `expr` and assign the result to the variable `name`
+    pub fn assign(&mut self, name: &str, expr: &str) -> Result<Evaluation, EvalError> {
+        let evaluation = self.evaluate(expr)?;
+        self.set_var(name, evaluation.value)?;
+        Ok(evaluation)
+    }
+}
+
+/// a recursive descent parser, the expression is evaluated once it is
+/// parsed completely
+struct Parser<'a, 'e> {
+    evaluator: &'e Evaluator,
+    tokens: Vec<Spanned<Token<'a>>>,
+    pos: usize,
+    /// length of the expression
+    end: usize,
+    /// the part of the expression that is worked on, blamed if it fails
+    span: Range<usize>,
+    /// unary operations and parentheses the parser is in
+    depth: usize,
+    /// keep the steps of every operation
+    explain: bool,
+    overflow: Overflow,
+    truncations: Vec<Truncation>,
+    register_views: Vec<IntType>,
+}
+
+impl<'a> Parser<'a, '_> {
+
+    /// the whole expression, nothing may follow it
+    fn tree(&mut self) -> Result<Node<'a>, EvalError> {
+        let node = self.binary(0)?;
+        if let Some(token) = self.peek() {
+            let token = token.to_string();
+            self.span = self.tokens[self.pos].1.clone();
+            return Err(EvalError::Unexpected(token));
+        }
+        Ok(node)
+    }
+
+    /// evaluate the whole expression
+    fn parse(&mut self) -> Result<(Evaluation, Step), EvalError> {
+        let node = self.tree()?;
+        let (step, _) = self.eval(node, self.evaluator.width)?;
+        Ok((Evaluation {
+            value: step.value,
+            overflow: self.overflow,
+            truncations: core::mem::take(&mut self.truncations),
+            register_views: core::mem::take(&mut self.register_views),
+        }, step))
+    }
+
+    fn peek(&self) -> Option<&Token<'a>> {
+        self.tokens.get(self.pos).map(|(token, _)| token)
+    }
+
+    fn next(&mut self) -> Result<Token<'a>, EvalError> {
+        self.pos += 1;
+        match self.tokens.get(self.pos - 1) {
+            Some((token, span)) => {
+                self.span = span.clone();
+                Ok(*token)
+            },
+            None => {
+                self.span = self.end..self.end;
+                Err(EvalError::UnexpectedEnd)
+            },
+        }
+    }
+
+    fn add_overflow(&mut self, overflow: Overflow) {
+        self.overflow = self.overflow.combine(overflow);
+    }
+
+    /// the binding strength of `op` if it is a binary operation
+    fn binary_precedence(&self, op: &str) -> Option<u8> {
+        match self.evaluator.custom(op) {
+            Some(custom) if custom.info.arity == Arity::Binary => Some(CUSTOM_PRECEDENCE),
+            Some(_) => None,
+            None => precedence(op),
+        }
+    }
+
+    /// binary operations binding at least as tight as `min`
+    fn binary(&mut self, min: u8) -> Result<Node<'a>, EvalError> {
+        let first = self.unary()?;
+        let mut rest = vec![];
+        while let Some(Token::Op(op)) = self.peek() {
+            let op = *op;
+            let prec = match self.binary_precedence(op) {
+                Some(prec) if prec >= min => prec,
+                _ => break,
+            };
+            let span = self.tokens[self.pos].1.clone();
+            self.pos += 1;
+            // left associative: the right side binds tighter
+            rest.push((op, span, self.binary(prec + 1)?));
+        }
+        if rest.is_empty() {
+            return Ok(first);
+        }
+        Ok(Node::Chain(Box::new(first), rest))
+    }
+
+    /// a unary operation or a value
+    fn unary(&mut self) -> Result<Node<'a>, EvalError> {
+        let token = self.next()?;
+        if let Token::Lit(_) | Token::Name(_) = token {
+            return self.operand(token);
+        }
+        if self.depth == MAX_DEPTH {
+            return Err(EvalError::TooDeep);
+        }
+        self.depth += 1;
+        let node = self.operand(token)?;
+        self.depth -= 1;
+        Ok(node)
+    }
+
+    /// the operand starting with `token`
+    fn operand(&mut self, token: Token<'a>) -> Result<Node<'a>, EvalError> {
+        match token {
+            Token::Op(op) => {
+                let span = self.span.clone();
+                let unary = match self.evaluator.custom(op) {
+                    Some(custom) => custom.info.arity == Arity::Unary,
+                    // SAFETY: only operations are tokenized as `Op`
+                    None => Operation::new_from_str(op).unwrap().is_unary(),
+                };
+                if !unary {
+                    return Err(EvalError::Unexpected(op.to_string()));
+                }
+                Ok(Node::Unary(op, span, Box::new(self.unary()?)))
+            },
+            Token::Lit(lit) => Literal::new_from_str(lit)
+                .map(Node::Value)
+                .map_err(|_| EvalError::InvalidLiteral(lit.to_string())),
+            Token::Name(name) => self.evaluator.var(name)
+                .map(|value| Node::Value(Literal::Typed(Step::value(name, value))))
+                .ok_or_else(|| EvalError::UnknownVariable(name.to_string())),
+            Token::Open => {
+                let node = self.binary(0)?;
+                match self.next()? {
+                    Token::Close => Ok(node),
+                    token => Err(EvalError::Unexpected(token.to_string())),
+                }
+            },
+            Token::Close => Err(EvalError::Unexpected(String::from(")"))),
+        }
+    }
+
+    /// calculate `node`, untyped literals get the width of the typed
+    /// operands they are combined with, or `width` if there are none.
+    /// returns the step and the depth of its tree.
+    fn eval(&mut self, node: Node<'a>, width: Width) -> Result<(Step, usize), EvalError> {
+        let width = node.width().unwrap_or(width);
+        match node {
+            Node::Value(lit) => {
+                let (step, truncation) = lit.resolve(width);
+                self.truncations.extend(truncation);
+                Ok((step, 1))
+            },
+            Node::Unary(op, span, operand) => {
+                let (value, depth) = self.eval(*operand, width)?;
+                self.span = span;
+                Ok((self.apply(op, vec![value])?, depth + 1))
+            },
+            Node::Chain(first, rest) => {
+                let (mut lhs, mut depth) = self.eval(*first, width)?;
+                for (op, span, operand) in rest {
+                    let (rhs, rhs_depth) = self.eval(operand, width)?;
+                    self.span = span;
+                    // operands of different widths can't be combined
+                    if lhs.value.width() != rhs.value.width() {
+                        return Err(EvalError::WidthMismatch(lhs.value.width(),
+                            rhs.value.width()));
+                    }
+                    // each operation of a chain is a level of the tree
+                    depth = depth.max(rhs_depth) + 1;
+                    if self.explain && depth > MAX_DEPTH {
+                        return Err(EvalError::TooDeep);
+                    }
+                    lhs = self.apply(op, vec![lhs, rhs])?;
+                }
+                Ok((lhs, depth))
+            },
+        }
+    }
+
+    /// apply `op` to `operands`, which have the same width
+    fn apply(&mut self, op: &str, operands: Vec<Step>) -> Result<Step, EvalError> {
+        let values: Vec<IntType> = operands.iter().map(|step| step.value).collect();
+        let (value, overflow) = match self.evaluator.custom(op) {
+            Some(custom) => (custom.implementation)(&values)
+                .map(|value| (value, Overflow::NotPossible))
+                .map_err(|msg| EvalError::Operation { name: op.to_string(), msg })?,
+            None => {
+                // SAFETY: only operations are tokenized as `Op`
+                let operation = Operation::new_from_str(op).unwrap();
+                if let (Operation::Reg, [v]) = (operation, values.as_slice()) {
+                    self.register_views.push(*v);
+                }
+                operation.apply(&values)?
+            },
+        };
+        self.add_overflow(overflow);
+        // without explaining, a long chain would build a tree as deep as
+        // the chain is long only to drop it
+        Ok(Step {
+            label: op.to_string(),
+            value,
+            overflow,
+            truncation: None,
+            operands: if self.explain { operands } else { Vec::new() },
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_evaluate() {
+        let mut evaluator = Evaluator::new();
+        evaluator.set_width(Width::U16);
+        evaluator.set_var("CLK_DIV", IntType::U16(0x13)).unwrap();
+
+        let evaluation = evaluator.evaluate("(CLK_DIV << 4) | 0x1ffff").unwrap();
+        assert_eq!(evaluation.value, IntType::U16(0xffff));
+        assert_eq!(evaluation.overflow, Overflow::NotPossible);
+        assert_eq!(evaluation.truncations.len(), 1);
+
+        let evaluation = evaluator.assign("x", "r 0xffu8 + 1").unwrap();
+        assert_eq!(evaluation.overflow, Overflow::Occured);
+        assert_eq!(evaluation.register_views, vec![IntType::U8(0xff)]);
+        assert_eq!(evaluator.var("x"), Some(IntType::U8(0)));
+    }
+
+    #[test]
+    fn test_var_names() {
+        let mut evaluator = Evaluator::new();
+        for name in ["+", "0x1", "u8", "", "r", "a b", "x-1"] {
+            assert_eq!(evaluator.set_var(name, IntType::U8(1)),
+                Err(EvalError::InvalidName(name.to_string())));
+        }
+        assert_eq!(evaluator.assign("u16", "1"), Err(EvalError::InvalidName(String::from("u16"))));
+        assert!(evaluator.vars().is_empty());
+        assert_eq!(evaluator.set_var("_RCC_CR2", IntType::U8(1)), Ok(()));
+        assert_eq!(evaluator.evaluate("_RCC_CR2 + 1").unwrap().value, IntType::U8(2));
+    }
+
+    #[test]
+    fn test_width_inference() {
+        let evaluator = Evaluator::new();
+        let evaluate = |expr| evaluator.evaluate(expr).unwrap().value;
+        // untyped parts take the width of the typed operand they meet
+        assert_eq!(evaluate("1 + 2 + 3u8"), IntType::U8(6));
+        assert_eq!(evaluate("~1 & 0xffu8"), IntType::U8(0xff));
+        assert_eq!(evaluate("(1 << 4) | 1u8"), IntType::U8(0x11));
+        assert_eq!(evaluate("(0xff + 1) + 0u8"), IntType::U8(0));
+        assert_eq!(evaluate("1 + 2 + 3"), IntType::U32(6));
+        assert_eq!(evaluator.evaluate("(1 + 1u8) + (1 + 1u16)"),
+            Err(EvalError::WidthMismatch(Width::U8, Width::U16)));
+    }
+
+    #[test]
+    fn test_errors() {
+        let evaluator = Evaluator::new();
+        assert_eq!(evaluator.evaluate(" "), Err(EvalError::Empty));
+        assert_eq!(evaluator.evaluate("1 +"), Err(EvalError::UnexpectedEnd));
+        assert_eq!(evaluator.evaluate("1 # 2"), Err(EvalError::InvalidToken(String::from("#"))));
+        assert_eq!(evaluator.evaluate("0xfg"), Err(EvalError::InvalidLiteral(String::from("0xfg"))));
+        assert_eq!(evaluator.evaluate("x + 1"), Err(EvalError::UnknownVariable(String::from("x"))));
+        assert_eq!(evaluator.evaluate("1 ! 2"), Err(EvalError::Unexpected(String::from("!"))));
+        assert_eq!(evaluator.evaluate("(1 + 2"), Err(EvalError::UnexpectedEnd));
+        assert_eq!(evaluator.evaluate("1 + 2)"), Err(EvalError::Unexpected(String::from(")"))));
+        assert_eq!(evaluator.evaluate("1u8 + 1u16"),
+            Err(EvalError::WidthMismatch(Width::U8, Width::U16)));
+        assert_eq!(evaluator.evaluate("1 / 0"), Err(EvalError::DivisionByZero));
+    }
+
+    #[test]
+    fn test_too_deep() {
+        let evaluator = Evaluator::new();
+        let deep = "(".repeat(MAX_DEPTH + 1) + "1" + &")".repeat(MAX_DEPTH + 1);
+        assert_eq!(evaluator.evaluate_with_span(&deep), Err((EvalError::TooDeep, 256..257)));
+        let deep = "~".repeat(100_000) + "1";
+        assert_eq!(evaluator.evaluate(&deep), Err(EvalError::TooDeep));
+
+        let deep = "(".repeat(MAX_DEPTH) + "1" + &")".repeat(MAX_DEPTH);
+        assert_eq!(evaluator.evaluate(&deep).unwrap().value, IntType::U32(1));
+
+        // a long chain doesn't nest, but its steps do
+        let long = ["1"; 100_000].join(" + ");
+        assert_eq!(evaluator.evaluate(&long).unwrap().value, IntType::U32(100_000));
+        assert_eq!(evaluator.explain(&long), Err(EvalError::TooDeep));
+        let long = ["1"; MAX_DEPTH].join(" + ");
+        assert_eq!(evaluator.explain(&long).unwrap().0.value, IntType::U32(MAX_DEPTH as u32));
+    }
+
+    #[test]
+    fn test_error_spans() {
+        let evaluator = Evaluator::new();
+        let span = |expr| evaluator.evaluate_with_span(expr).unwrap_err().1;
+        assert_eq!(span("1 + # 2"), 4..5);
+        assert_eq!(span("1 + 0xfg"), 4..8);
+        assert_eq!(span("(1 + 2"), 6..6);
+        assert_eq!(span("(1)) + 2"), 3..4);
+        assert_eq!(span("1u8 + (2u16 * 3)"), 4..5);
+        assert_eq!(span("~1u8 / 0u8"), 5..6);
+        assert_eq!(span("2 <<< 1 ! 3"), 8..9);
+        assert_eq!(span("  "), 0..2);
+    }
+
+    #[test]
+    fn test_explain() {
+        let mut evaluator = Evaluator::new();
+        evaluator.set_var("EN", IntType::U8(0x80)).unwrap();
+        let (evaluation, step) = evaluator.explain("(0xff + 1u8) | EN | 0x101").unwrap();
+        assert_eq!(evaluation.value, IntType::U8(0x81));
+        assert_eq!(step.to_string(), "\
+| = 0x81 0b10000001 (u8)
+  | = 0x80 0b10000000 (u8)
+    + = 0x00 0b00000000 (u8, overflow (carry out))
+      0xff = 0xff 0b11111111 (u8)
+      1u8 = 0x01 0b00000001 (u8)
+    EN = 0x80 0b10000000 (u8)
+  0x101 = 0x01 0b00000001 (u8, truncated from 0x101)");
+
+        let (_, step) = evaluator.explain("0x13").unwrap();
+        assert_eq!(step, Step::value("0x13", IntType::U32(0x13)));
+        assert_eq!(evaluator.explain("1 / 0").unwrap_err(), EvalError::DivisionByZero);
+    }
+
+    #[test]
+    fn test_custom_operations() {
+        let mut evaluator = Evaluator::new();
+        evaluator.register_operation("%", Arity::Binary, "remainder", |v| {
+            let (a, b) = (v[0].as_u64(), v[1].as_u64());
+            let r = a.checked_rem(b).ok_or_else(|| String::from("remainder of zero"))?;
+            Ok(IntType::from_u64_truncating(r, v[0].width()).0)
+        }).unwrap();
+        evaluator.register_operation("clz", Arity::Unary, "count leading zeros", |v| {
+            let zeros = v[0].as_u64().leading_zeros() as usize - (64 - v[0].width().bits());
+            Ok(IntType::from_u64_truncating(zeros as u64, v[0].width()).0)
+        }).unwrap();
+
+        assert_eq!(evaluator.evaluate("1 + 17 % 5").unwrap().value, IntType::U32(3));
+        assert_eq!(evaluator.evaluate("clz 0x0fu8").unwrap().value, IntType::U8(4));
+        assert_eq!(evaluator.evaluate("clz(1u16 << 8)").unwrap().value, IntType::U16(7));
+        assert_eq!(evaluator.evaluate("1 % 0"), Err(EvalError::Operation {
+            name: String::from("%"),
+            msg: String::from("remainder of zero"),
+        }));
+        assert_eq!(evaluator.evaluate("% 1"), Err(EvalError::Unexpected(String::from("%"))));
+
+        assert_eq!(evaluator.register_operation("<<", Arity::Binary, "", |v| Ok(v[0])),
+            Err(OperationError::AlreadyDefined(String::from("<<"))));
+        assert_eq!(evaluator.register_operation("2x", Arity::Unary, "", |v| Ok(v[0])),
+            Err(OperationError::InvalidName(String::from("2x"))));
+        assert_eq!(evaluator.register_operation("(", Arity::Unary, "", |v| Ok(v[0])),
+            Err(OperationError::InvalidName(String::from("("))));
+
+        let infos = evaluator.operations();
+        assert_eq!(infos.len(), Operation::infos().len() + 2);
+        assert_eq!(infos.last().unwrap().symbol, "clz");
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_serialize() {
+        let evaluation = Evaluator::new().evaluate("0x1ff + 0xffffffffu32").unwrap();
+        let json = serde_json::to_string(&evaluation).unwrap();
+        assert_eq!(json, "{\"value\":\"0x000001feu32\",\"overflow\":\"occurred\",\
+            \"truncations\":[],\"register_views\":[]}");
+        assert_eq!(serde_json::from_str::<Evaluation>(&json).unwrap(), evaluation);
+    }
+
+}
@@ -0,0 +1,1184 @@
+//! the arithmetic core of bitwister and pn_bitwister: integers of a fixed
+//! width and their operations, register definitions and the prefix and
+//! RPN calculators. `no_std`, with `alloc` for names and rendered text, so
+//! the same engine runs on a target.
+//! 0xca7
+#![cfg_attr(not(test), no_std)]
+
+extern crate alloc;
+
+use alloc::{
+    format,
+    string::{String, ToString},
+    vec::Vec,
+};
+use core::fmt;
+use core::ops;
+
+pub mod convert;
+pub mod evaluator;
+pub mod pn;
+pub mod register;
+pub mod rpn;
+pub mod trace;
+#[cfg(feature = "serde")]
+mod serialize;
+
+pub use evaluator::{
+    EvalError,
+    Evaluation,
+    Evaluator,
+};
+pub use trace::Step;
+
+
+/// when an invalid operation is encountered, or a custom operation can't
+/// be registered
+#[derive(Debug, PartialEq)]
+pub enum OperationError {
+    UnknownOperation,
+    /// not a word or a single symbol, or the symbol is taken by `(` or `)`
+    InvalidName(String),
+    /// an operation of this name already exists
+    AlreadyDefined(String),
+}
+
+impl fmt::Display for OperationError {
+
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            OperationError::UnknownOperation => write!(f, "error: unknown operation"),
+            OperationError::InvalidName(name) => {
+                write!(f, "error: invalid operation name {name}")
+            },
+            OperationError::AlreadyDefined(name) => {
+                write!(f, "error: operation {name} is already defined")
+            },
+        }
+    }
+
+}
+
+impl core::error::Error for OperationError {}
+
+/// the number of operands of an operation
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Arity {
+    Unary,
+    Binary,
+}
+
+/// what an operation does, for help texts and completion
+#[derive(Debug, Clone, PartialEq)]
+pub struct OperationInfo {
+    /// the symbol or name used in expressions, e.g. `<<`
+    pub symbol: String,
+    pub arity: Arity,
+    pub description: String,
+    /// an expression using the operation
+    pub example: Option<String>,
+}
+
+/// symbol, description and example of every built-in operation
+const OPERATIONS: [(&str, &str, &str); 15] = [
+    ("+", "addition", "0x1u8 + 0x1u8"),
+    ("-", "subraction", "0x1u8 - 0x1u8"),
+    ("*", "multiplciation", "0x1u8 * 0x2u8"),
+    ("/", "divide", "0x4u8 / 0x2u8"),
+    ("&", "bitwise AND", "0xdeadu16 & 0x03u16"),
+    ("|", "bitwise OR", "0x03u8 | 0x4u8"),
+    ("^", "bitwise XOR", "0xdeadbeefu32 ^ 0xbaadu32"),
+    ("<<", "shift left", "1u32 << 10u32"),
+    (">>", "shift right", "10000u32 >> 10u32"),
+    ("<<<", "rotate left", "1u8 <<< 16u8"),
+    (">>>", "rotate right", "1u8 >>> 1u8"),
+    ("~", "negate", "~ 0x1u8"),
+    ("!", "bitwise NOT", "! 0xdeadbeefu32"),
+    ("r", "regshow", "r 0xdeadbeefc0cac01au64"),
+    ("e", "endian swap", "e 0xdeadbeefc0cac01au64"),
+];
+
+/// a specific calculation operation, for example add: adds two numbers
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Operation {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    And,
+    Or,
+    Xor,
+    Shl,
+    Shr,
+    Rol,
+    Ror,
+    Neg,
+    Not,
+    Reg, // register view
+    End, // swap endianess
+}
+
+impl Operation {
+
+    /// create an Operation from a string, check for errors 
+    pub fn new_from_str(s: &str) -> Result<Operation, OperationError> {
+        match s {
+            "+" => Ok(Operation::Add),
+            "-" => Ok(Operation::Sub),
+            "*" => Ok(Operation::Mul),
+            "/" => Ok(Operation::Div),
+            "&" => Ok(Operation::And),
+            "|" => Ok(Operation::Or),
+            "^" => Ok(Operation::Xor),
+            "<<" => Ok(Operation::Shl),
+            ">>" => Ok(Operation::Shr),
+            "<<<" => Ok(Operation::Rol),
+            ">>>" => Ok(Operation::Ror),
+            "~" => Ok(Operation::Neg),
+            "!" => Ok(Operation::Not),
+            "r" => Ok(Operation::Reg),
+            "e" => Ok(Operation::End),
+            _ => Err(OperationError::UnknownOperation)
+        }
+    }
+
+    /// the symbol of the operation, as accepted by `new_from_str`
+    pub fn symbol(&self) -> &'static str {
+        match self {
+            Operation::Add => "+",
+            Operation::Sub => "-",
+            Operation::Mul => "*",
+            Operation::Div => "/",
+            Operation::And => "&",
+            Operation::Or => "|",
+            Operation::Xor => "^",
+            Operation::Shl => "<<",
+            Operation::Shr => ">>",
+            Operation::Rol => "<<<",
+            Operation::Ror => ">>>",
+            Operation::Neg => "~",
+            Operation::Not => "!",
+            Operation::Reg => "r",
+            Operation::End => "e",
+        }
+    }
+
+    /// the symbols of all operations, as accepted by `new_from_str`
+    pub fn symbols() -> &'static [&'static str] {
+        &["+", "-", "*", "/", "&", "|", "^", "<<", ">>", "<<<", ">>>",
+          "~", "!", "r", "e"]
+    }
+
+    /// the built-in operations, with an example each
+    pub fn infos() -> Vec<OperationInfo> {
+        OPERATIONS.iter()
+            .map(|(symbol, description, example)| OperationInfo {
+                symbol: symbol.to_string(),
+                // SAFETY: every symbol of the table is an operation
+                arity: Operation::new_from_str(symbol).unwrap().arity(),
+                description: description.to_string(),
+                example: Some(example.to_string()),
+            })
+            .collect()
+    }
+
+    /// the number of operands this operation takes
+    pub fn arity(&self) -> Arity {
+        if self.is_unary() {
+            Arity::Unary
+        } else {
+            Arity::Binary
+        }
+    }
+
+    /// check if an operation is unary or not
+    pub fn is_unary(&self) -> bool {
+        matches!(self, Operation::Neg | Operation::Not
+            | Operation::Reg | Operation::End)
+    }
+
+    /// if the operands of a chain can be grouped in any way, e.g.
+    /// `(a + b) + c == a + (b + c)`, so it can take any number of them
+    pub fn is_associative(&self) -> bool {
+        matches!(self, Operation::Add | Operation::Mul
+            | Operation::And | Operation::Or | Operation::Xor)
+    }
+
+    /// apply the operation to one operand if it is unary, to two of the
+    /// same width if it is binary. every notation evaluates through this,
+    /// so they agree on every result.
+    pub fn apply(&self, operands: &[IntType]) -> Result<(IntType, Overflow), EvalError> {
+        match operands {
+            [v] => v.calculate_unary(*self),
+            [v0, v1] if v0.width() != v1.width() => {
+                return Err(EvalError::WidthMismatch(v0.width(), v1.width()));
+            },
+            [_, v1] if *self == Operation::Div && v1.as_u64() == 0 => {
+                return Err(EvalError::DivisionByZero);
+            },
+            [v0, v1] => v0.calculate_binary(*v1, *self),
+            _ => None,
+        }.ok_or_else(|| EvalError::Unexpected(self.symbol().to_string()))
+    }
+
+}
+
+
+
+
+/// errors which can arise when converting integers
+#[derive(Debug)]
+pub enum IntTypeConversionError {
+    InvalidInteger,
+    InvalidBitwidth,
+    UnsupportedBitwidth,
+    /// the value does not fit the primitive type it is converted to
+    TooLarge,
+}
+
+impl fmt::Display for IntTypeConversionError {
+
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+
+        match self {
+            IntTypeConversionError::InvalidInteger => { 
+                write!(f, "error: integer value is invalid")
+            },
+            IntTypeConversionError::InvalidBitwidth => { 
+                write!(f, "error: integer bitwidth value is invalid")
+            },
+            IntTypeConversionError::UnsupportedBitwidth => { 
+                write!(f, "error: integer bitwidth is not 8, 16, 32 or 64")
+            },
+            IntTypeConversionError::TooLarge => {
+                write!(f, "error: integer value does not fit the type")
+            },
+        }
+
+    } // fmt
+
+} // impl Display
+
+impl core::error::Error for IntTypeConversionError {}
+
+/// the bitwidth of an integer, used as the default width for literals
+/// without a `u` suffix
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Width {
+    U8,
+    U16,
+    U32,
+    U64,
+}
+
+impl Width {
+
+    /// number of bits of this width
+    pub fn bits(&self) -> usize {
+        match self {
+            Width::U8 => 8,
+            Width::U16 => 16,
+            Width::U32 => 32,
+            Width::U64 => 64,
+        }
+    }
+}
+
+impl Default for Width {
+
+    /// the width of unsuffixed literals if nothing else is configured
+    fn default() -> Self {
+        Width::U32
+    }
+}
+
+impl core::str::FromStr for Width {
+    type Err = IntTypeConversionError;
+
+    /// parse a width from `u8`, `u16`, `u32`, `u64` or the plain bit count
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim_start_matches('u') {
+            "8" => Ok(Width::U8),
+            "16" => Ok(Width::U16),
+            "32" => Ok(Width::U32),
+            "64" => Ok(Width::U64),
+            "" => Err(IntTypeConversionError::InvalidBitwidth),
+            bits if bits.parse::<usize>().is_err() => {
+                Err(IntTypeConversionError::InvalidBitwidth)
+            },
+            _ => Err(IntTypeConversionError::UnsupportedBitwidth),
+        }
+    }
+}
+
+impl fmt::Display for Width {
+
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "u{}", self.bits())
+    }
+
+}
+
+/// a literal without a suffix that did not fit the width it was given
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Truncation {
+    /// the literal as it was entered
+    pub literal: String,
+    /// the width the literal was truncated to
+    pub width: Width,
+    /// the truncated value
+    #[cfg_attr(feature = "serde", serde(with = "serialize::hex"))]
+    pub value: u64,
+}
+
+impl fmt::Display for Truncation {
+
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "literal {} does not fit {}, truncated to 0x{:x}",
+            self.literal, self.width, self.value)
+    }
+
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum Overflow {
+    NotPossible, // some operations can't overflow
+    NoOverflow,  // didn't overflow
+    #[cfg_attr(feature = "serde", serde(rename = "occurred"))]
+    Occured,     // overflow occured
+}
+
+impl Overflow {
+
+    /// the overflow of two operations combined
+    pub fn combine(self, other: Overflow) -> Overflow {
+        match (self, other) {
+            (Overflow::Occured, _) | (_, Overflow::Occured) => Overflow::Occured,
+            (Overflow::NoOverflow, _) | (_, Overflow::NoOverflow) => Overflow::NoOverflow,
+            _ => Overflow::NotPossible,
+        }
+    }
+}
+
+impl fmt::Display for Overflow {
+
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+
+        match self {
+            Overflow::Occured => write!(f, "[overflow occured]"),
+            Overflow::NoOverflow => write!(f, "[no overflow]"),
+            Overflow::NotPossible => write!(f, ""),
+        }
+
+    } // fmt
+
+} // impl Display
+
+/// a type that describes different integer types used in this program
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum IntType {
+    U8(u8),
+    U16(u16),
+    U32(u32),
+    U64(u64),
+}
+
+/// apply `$body` to the value of an IntType, the result has the same
+/// width. `checked` bodies return an Option.
+macro_rules! map {
+    ($a:expr, |$v:ident| $body:expr) => {
+        match $a {
+            IntType::U8($v) => IntType::U8($body),
+            IntType::U16($v) => IntType::U16($body),
+            IntType::U32($v) => IntType::U32($body),
+            IntType::U64($v) => IntType::U64($body),
+        }
+    };
+    (checked $a:expr, |$v:ident| $body:expr) => {
+        match $a {
+            IntType::U8($v) => $body.map(IntType::U8),
+            IntType::U16($v) => $body.map(IntType::U16),
+            IntType::U32($v) => $body.map(IntType::U32),
+            IntType::U64($v) => $body.map(IntType::U64),
+        }
+    };
+}
+
+/// apply `$body` to the values of two IntTypes of the same width, None if
+/// the widths differ. `checked` bodies return an Option, `overflowing`
+/// bodies a value and an overflow flag.
+macro_rules! zip {
+    ($a:expr, $b:expr, |$v:ident, $u:ident| $body:expr) => {
+        match ($a, $b) {
+            (IntType::U8($v), IntType::U8($u)) => Some(IntType::U8($body)),
+            (IntType::U16($v), IntType::U16($u)) => Some(IntType::U16($body)),
+            (IntType::U32($v), IntType::U32($u)) => Some(IntType::U32($body)),
+            (IntType::U64($v), IntType::U64($u)) => Some(IntType::U64($body)),
+            _ => None,
+        }
+    };
+    (checked $a:expr, $b:expr, |$v:ident, $u:ident| $body:expr) => {
+        match ($a, $b) {
+            (IntType::U8($v), IntType::U8($u)) => $body.map(IntType::U8),
+            (IntType::U16($v), IntType::U16($u)) => $body.map(IntType::U16),
+            (IntType::U32($v), IntType::U32($u)) => $body.map(IntType::U32),
+            (IntType::U64($v), IntType::U64($u)) => $body.map(IntType::U64),
+            _ => None,
+        }
+    };
+    (overflowing $a:expr, $b:expr, |$v:ident, $u:ident| $body:expr) => {
+        match ($a, $b) {
+            (IntType::U8($v), IntType::U8($u)) => {
+                let (res, overflow) = $body;
+                Some((IntType::U8(res), overflow))
+            },
+            (IntType::U16($v), IntType::U16($u)) => {
+                let (res, overflow) = $body;
+                Some((IntType::U16(res), overflow))
+            },
+            (IntType::U32($v), IntType::U32($u)) => {
+                let (res, overflow) = $body;
+                Some((IntType::U32(res), overflow))
+            },
+            (IntType::U64($v), IntType::U64($u)) => {
+                let (res, overflow) = $body;
+                Some((IntType::U64(res), overflow))
+            },
+            _ => None,
+        }
+    };
+}
+
+impl IntType {
+
+
+    /// take a string and parse it to an IntType, if the parsing fails, an
+    /// error is returned
+    pub fn new_from_str(s: &str) -> Result<IntType, IntTypeConversionError> {
+        IntType::new_from_str_with_width(s, None)
+    }
+
+    /// like `new_from_str`, but a literal without a `u` suffix is parsed
+    /// with the `default` width instead of failing. a suffix always wins.
+    pub fn new_from_str_with_width(s: &str, default: Option<Width>)
+        -> Result<IntType, IntTypeConversionError> {
+
+        // check if decimal or hex
+        let (s, is_hex) = if s.starts_with("0x") {
+            (s.trim_start_matches("0x"), true)
+        } else {
+            (s, false)   
+        };
+
+        // we need each value entered to be of a specific type, either by
+        // its suffix or by the default width
+        let (digits, bits) = if !s.contains('u') {
+            match default {
+                Some(width) => (s, width.bits()),
+                None => return Err(IntTypeConversionError::InvalidInteger),
+            }
+        } else {
+
+            // if we have something like 1u8 we need to separate the two terms
+            // into 1 and 8
+            let vs: Vec<&str> = s.split('u')
+                .collect();
+
+            // an integer in this program always consists of two parts after split
+            if vs.len() != 2 {
+                return Err(IntTypeConversionError::InvalidInteger);
+            }
+
+            // get the width of the integer
+            let bits = vs[1].parse::<usize>();
+
+            // check conversion result
+            match bits {
+                Ok(v) => (vs[0], v),
+                Err(_) => return Err(IntTypeConversionError::InvalidBitwidth),
+            }
+        };
+
+        match bits {
+                8 => match u8::from_str_radix(digits, 
+                        if is_hex { 16 } else { 10 }) {
+                            Ok(num) => Ok(IntType::U8(num)),
+                            Err(_) => Err(IntTypeConversionError::InvalidInteger),
+                        },
+
+                16 => match u16::from_str_radix(digits, 
+                        if is_hex { 16 } else { 10 }) {
+                            Ok(num) => Ok(IntType::U16(num)),
+                            Err(_) => Err(IntTypeConversionError::InvalidInteger),
+                        },
+
+                32 => match u32::from_str_radix(digits, 
+                        if is_hex { 16 } else { 10 }) {
+                            Ok(num) => Ok(IntType::U32(num)),
+                            Err(_) => Err(IntTypeConversionError::InvalidInteger),
+                        },
+
+                64 => match u64::from_str_radix(digits, 
+                        if is_hex { 16 } else { 10 }) {
+                            Ok(num) => Ok(IntType::U64(num)),
+                            Err(_) => Err(IntTypeConversionError::InvalidInteger),
+                        },
+                _ => Err(IntTypeConversionError::UnsupportedBitwidth)
+            }
+    }
+
+    // calculation of a binary operation, given two inttypes and an operation.
+    // None if the widths differ, the operation is unary or on a division by
+    // zero
+    pub fn calculate_binary(self, other: IntType, op: Operation) -> Option<(IntType, Overflow)> {
+
+        if self.width() != other.width() {
+            return None;
+        }
+        let bits = self.width().bits() as u64;
+        // shifting by the width or more shifts out every bit
+        let shift = u32::try_from(other.as_u64()).unwrap_or(u32::MAX);
+        let rotate = (other.as_u64() % bits) as u32;
+
+        // the result and if an overflow occured, None if it can't
+        let (res, overflow) = match op {
+            Operation::Add => self.overflowing_add(other).map(|(v, o)| (v, Some(o)))?,
+            Operation::Sub => self.overflowing_sub(other).map(|(v, o)| (v, Some(o)))?,
+            Operation::Mul => self.overflowing_mul(other).map(|(v, o)| (v, Some(o)))?,
+            Operation::Div => (self.checked_div(other)?, Some(false)),
+            Operation::And => (zip!(self, other, |v, u| v & u)?, None),
+            Operation::Or => (zip!(self, other, |v, u| v | u)?, None),
+            Operation::Xor => (zip!(self, other, |v, u| v ^ u)?, None),
+            Operation::Shl => (map!(self, |v| v.checked_shl(shift).unwrap_or(0)), None),
+            Operation::Shr => (map!(self, |v| v.checked_shr(shift).unwrap_or(0)), None),
+            Operation::Rol => (map!(self, |v| v.rotate_left(rotate)), None),
+            Operation::Ror => (map!(self, |v| v.rotate_right(rotate)), None),
+            Operation::Neg | Operation::Not | Operation::Reg | Operation::End => return None,
+        };
+
+        let overflow = match overflow {
+            Some(true) => Overflow::Occured,
+            Some(false) => Overflow::NoOverflow,
+            None => Overflow::NotPossible,
+        };
+        Some((res, overflow))
+    }
+
+    /// calculate an unary operation given an IntType and an operation, None
+    /// if the operation is binary
+    pub fn calculate_unary(self, op: Operation) -> Option<(IntType, Overflow)> {
+
+        let res = match op {
+            Operation::Neg => map!(self, |v| v.wrapping_neg()),
+            Operation::Not => map!(self, |v| !v),
+            // the register view is shown by the caller, see `Evaluation`
+            Operation::Reg => self,
+            Operation::End => map!(self, |v| v.swap_bytes()),
+            _ => return None,
+        };
+
+        Some((res, Overflow::NotPossible))
+    }
+
+    /// `self + rhs`, None if the widths differ or on overflow
+    pub fn checked_add(self, rhs: IntType) -> Option<IntType> {
+        zip!(checked self, rhs, |v, u| v.checked_add(u))
+    }
+
+    /// `self - rhs`, None if the widths differ or on overflow
+    pub fn checked_sub(self, rhs: IntType) -> Option<IntType> {
+        zip!(checked self, rhs, |v, u| v.checked_sub(u))
+    }
+
+    /// `self * rhs`, None if the widths differ or on overflow
+    pub fn checked_mul(self, rhs: IntType) -> Option<IntType> {
+        zip!(checked self, rhs, |v, u| v.checked_mul(u))
+    }
+
+    /// `self / rhs`, None if the widths differ or `rhs` is zero
+    pub fn checked_div(self, rhs: IntType) -> Option<IntType> {
+        zip!(checked self, rhs, |v, u| v.checked_div(u))
+    }
+
+    /// `self << n`, None if `n` is not less than the width
+    pub fn checked_shl(self, n: u32) -> Option<IntType> {
+        map!(checked self, |v| v.checked_shl(n))
+    }
+
+    /// `self >> n`, None if `n` is not less than the width
+    pub fn checked_shr(self, n: u32) -> Option<IntType> {
+        map!(checked self, |v| v.checked_shr(n))
+    }
+
+    /// the wrapped `self + rhs` and whether it overflowed, None if the
+    /// widths differ
+    pub fn overflowing_add(self, rhs: IntType) -> Option<(IntType, bool)> {
+        zip!(overflowing self, rhs, |v, u| v.overflowing_add(u))
+    }
+
+    /// the wrapped `self - rhs` and whether it overflowed, None if the
+    /// widths differ
+    pub fn overflowing_sub(self, rhs: IntType) -> Option<(IntType, bool)> {
+        zip!(overflowing self, rhs, |v, u| v.overflowing_sub(u))
+    }
+
+    /// the wrapped `self * rhs` and whether it overflowed, None if the
+    /// widths differ
+    pub fn overflowing_mul(self, rhs: IntType) -> Option<(IntType, bool)> {
+        zip!(overflowing self, rhs, |v, u| v.overflowing_mul(u))
+    }
+
+    /// `op` for the operator traits, which panic like the primitive types
+    fn operator(self, rhs: IntType, op: Operation) -> IntType {
+        assert!(self.width() == rhs.width(), "operands of different widths {} and {}",
+            self.width(), rhs.width());
+        match self.calculate_binary(rhs, op) {
+            Some((res, _)) => res,
+            None => panic!("attempt to divide by zero"),
+        }
+    }
+
+    /// cut `v` down to `width`, the flag is set if bits were lost
+    pub fn from_u64_truncating(v: u64, width: Width) -> (IntType, bool) {
+        match width {
+            Width::U8 => (IntType::U8(v as u8), v > u8::MAX as u64),
+            Width::U16 => (IntType::U16(v as u16), v > u16::MAX as u64),
+            Width::U32 => (IntType::U32(v as u32), v > u32::MAX as u64),
+            Width::U64 => (IntType::U64(v), false),
+        }
+    }
+
+    /// the width of this integer
+    pub fn width(&self) -> Width {
+        match self {
+            IntType::U8(_) => Width::U8,
+            IntType::U16(_) => Width::U16,
+            IntType::U32(_) => Width::U32,
+            IntType::U64(_) => Width::U64,
+        }
+    }
+
+    /// the value zero-extended to 64 bit
+    pub fn as_u64(&self) -> u64 {
+        match self {
+            IntType::U8(v) => *v as u64,
+            IntType::U16(v) => *v as u64,
+            IntType::U32(v) => *v as u64,
+            IntType::U64(v) => *v,
+        }
+    }
+
+    /// the value interpreted as a two's complement signed integer
+    pub fn as_i64(&self) -> i64 {
+        match self {
+            IntType::U8(v) => *v as i8 as i64,
+            IntType::U16(v) => *v as i16 as i64,
+            IntType::U32(v) => *v as i32 as i64,
+            IntType::U64(v) => *v as i64,
+        }
+    }
+
+    /// the integer as a literal that parses back to the same value,
+    /// e.g. `0x02u8`
+    pub fn to_literal(&self) -> String {
+        let bits = self.width().bits();
+        format!("0x{:0w$x}u{bits}", self.as_u64(), w = bits / 4)
+    }
+
+    /// the register view of this integer, see `r`
+    pub fn register_view(&self) -> String {
+        regstring(self.as_u64(), self.width().bits())
+    }
+
+    // this should return as string or &str
+    pub fn to_ascii(&self) -> String {
+
+        let mut res = String::new();
+
+        let (inner, len) = match self {
+            IntType::U8(v) => (*v as u64, 1),
+            IntType::U16(v) => (*v as u64, 2),
+            IntType::U32(v) => (*v as u64, 4),
+            IntType::U64(v) => (*v, 8),
+        };
+
+        for i in (0..len).rev() {
+            let byte = ((inner >> (i*8)) & 0xff) as u8;
+            res.push_str(&check_ascii(byte));
+        }
+        res
+    }
+
+}
+
+impl fmt::Display for IntType {
+
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+
+        match self {
+            IntType::U8(v) => {
+                writeln!(f, "hex:   0x{:02x}", v)?;
+                writeln!(f, "        bin:   b{:08b}", v)?;
+                writeln!(f, "        dec:   {}", v)?;
+                writeln!(f, "        ascii: {}", self.to_ascii())
+            },
+            IntType::U16(v) => {
+
+                writeln!(f, "hex:   0x{:04x}", v)?;
+                writeln!(f, "        bin:   b{:016b}", v)?;
+                writeln!(f, "        dec:   {}", v)?;
+                writeln!(f, "        ascii: {}", self.to_ascii())
+            },
+            IntType::U32(v) => {
+                writeln!(f, "hex:   0x{:08x}", v)?;
+                writeln!(f, "        bin:   b{:032b}", v)?;
+                writeln!(f, "        dec:   {}", v)?;
+                writeln!(f, "        ascii: {}", self.to_ascii())
+            },
+            IntType::U64(v) => {
+                writeln!(f, "hex:   0x{:016x}", v)?;
+                writeln!(f, "        bin:   b{:064b}", v)?;
+                writeln!(f, "        dec:   {}", v)?;
+                writeln!(f, "        ascii: {}", self.to_ascii())
+            },
+        }
+
+    } // fmt
+
+} // impl Display
+
+impl core::str::FromStr for IntType {
+    type Err = IntTypeConversionError;
+
+    /// parse a literal with a width suffix, e.g. `0xcafeu16`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        IntType::new_from_str(s)
+    }
+}
+
+impl From<u8> for IntType {
+    fn from(v: u8) -> Self {
+        IntType::U8(v)
+    }
+}
+
+impl From<u16> for IntType {
+    fn from(v: u16) -> Self {
+        IntType::U16(v)
+    }
+}
+
+impl From<u32> for IntType {
+    fn from(v: u32) -> Self {
+        IntType::U32(v)
+    }
+}
+
+impl From<u64> for IntType {
+    fn from(v: u64) -> Self {
+        IntType::U64(v)
+    }
+}
+
+impl From<IntType> for u64 {
+    fn from(v: IntType) -> Self {
+        v.as_u64()
+    }
+}
+
+impl TryFrom<IntType> for u8 {
+    type Error = IntTypeConversionError;
+
+    /// the value if it fits, whatever the width
+    fn try_from(v: IntType) -> Result<Self, Self::Error> {
+        u8::try_from(v.as_u64()).map_err(|_| IntTypeConversionError::TooLarge)
+    }
+}
+
+impl TryFrom<IntType> for u16 {
+    type Error = IntTypeConversionError;
+
+    /// the value if it fits, whatever the width
+    fn try_from(v: IntType) -> Result<Self, Self::Error> {
+        u16::try_from(v.as_u64()).map_err(|_| IntTypeConversionError::TooLarge)
+    }
+}
+
+impl TryFrom<IntType> for u32 {
+    type Error = IntTypeConversionError;
+
+    /// the value if it fits, whatever the width
+    fn try_from(v: IntType) -> Result<Self, Self::Error> {
+        u32::try_from(v.as_u64()).map_err(|_| IntTypeConversionError::TooLarge)
+    }
+}
+
+/// the operators wrap around on overflow like the calculator does, and
+/// panic if the widths of the operands differ or on a division by zero
+macro_rules! operator {
+    ($trait:ident, $method:ident, $assign_trait:ident, $assign:ident, $op:expr) => {
+        impl ops::$trait for IntType {
+            type Output = IntType;
+
+            fn $method(self, rhs: IntType) -> IntType {
+                self.operator(rhs, $op)
+            }
+        }
+
+        impl ops::$assign_trait for IntType {
+            fn $assign(&mut self, rhs: IntType) {
+                *self = self.operator(rhs, $op);
+            }
+        }
+    };
+}
+
+operator!(Add, add, AddAssign, add_assign, Operation::Add);
+operator!(Sub, sub, SubAssign, sub_assign, Operation::Sub);
+operator!(Mul, mul, MulAssign, mul_assign, Operation::Mul);
+operator!(Div, div, DivAssign, div_assign, Operation::Div);
+operator!(BitAnd, bitand, BitAndAssign, bitand_assign, Operation::And);
+operator!(BitOr, bitor, BitOrAssign, bitor_assign, Operation::Or);
+operator!(BitXor, bitxor, BitXorAssign, bitxor_assign, Operation::Xor);
+
+/// shifting by the width or more gives zero, as `<<` in the calculator
+impl ops::Shl<u32> for IntType {
+    type Output = IntType;
+
+    fn shl(self, n: u32) -> IntType {
+        map!(self, |v| v.checked_shl(n).unwrap_or(0))
+    }
+}
+
+impl ops::ShlAssign<u32> for IntType {
+    fn shl_assign(&mut self, n: u32) {
+        *self = *self << n;
+    }
+}
+
+/// shifting by the width or more gives zero, as `>>` in the calculator
+impl ops::Shr<u32> for IntType {
+    type Output = IntType;
+
+    fn shr(self, n: u32) -> IntType {
+        map!(self, |v| v.checked_shr(n).unwrap_or(0))
+    }
+}
+
+impl ops::ShrAssign<u32> for IntType {
+    fn shr_assign(&mut self, n: u32) {
+        *self = *self >> n;
+    }
+}
+
+/// bitwise NOT, as `!` in the calculator
+impl ops::Not for IntType {
+    type Output = IntType;
+
+    fn not(self) -> IntType {
+        map!(self, |v| !v)
+    }
+}
+
+/// two's complement negation, as `~` in the calculator
+impl ops::Neg for IntType {
+    type Output = IntType;
+
+    fn neg(self) -> IntType {
+        map!(self, |v| v.wrapping_neg())
+    }
+}
+
+impl fmt::LowerHex for IntType {
+
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            IntType::U8(v) => fmt::LowerHex::fmt(v, f),
+            IntType::U16(v) => fmt::LowerHex::fmt(v, f),
+            IntType::U32(v) => fmt::LowerHex::fmt(v, f),
+            IntType::U64(v) => fmt::LowerHex::fmt(v, f),
+        }
+    }
+
+}
+
+impl fmt::UpperHex for IntType {
+
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            IntType::U8(v) => fmt::UpperHex::fmt(v, f),
+            IntType::U16(v) => fmt::UpperHex::fmt(v, f),
+            IntType::U32(v) => fmt::UpperHex::fmt(v, f),
+            IntType::U64(v) => fmt::UpperHex::fmt(v, f),
+        }
+    }
+
+}
+
+impl fmt::Binary for IntType {
+
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            IntType::U8(v) => fmt::Binary::fmt(v, f),
+            IntType::U16(v) => fmt::Binary::fmt(v, f),
+            IntType::U32(v) => fmt::Binary::fmt(v, f),
+            IntType::U64(v) => fmt::Binary::fmt(v, f),
+        }
+    }
+
+}
+
+impl fmt::Octal for IntType {
+
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            IntType::U8(v) => fmt::Octal::fmt(v, f),
+            IntType::U16(v) => fmt::Octal::fmt(v, f),
+            IntType::U32(v) => fmt::Octal::fmt(v, f),
+            IntType::U64(v) => fmt::Octal::fmt(v, f),
+        }
+    }
+
+}
+
+
+fn check_ascii(v: u8) -> String {
+    if (0x20..=0x7e).contains(&v) {
+        String::from(v as char)
+    } else {
+        String::from(".")
+    }
+}
+
+/// render a `value` as if it were a value in an `iter_max`-bit register.
+fn regstring(value: u64, iter_max: usize) -> String {
+
+    let mut s = String::new();
+
+    if iter_max == 64 {
+        for i in (32..iter_max).rev() {
+            s.push_str(&format!("{i} "));
+        }
+        s.push('\n');
+        // print value
+        for i in (32..iter_max).rev() {
+            let bit = (value >> i) & 0x01;
+            if i >= 10 {
+                s.push_str(&format!("{bit}  "));
+            } else {
+                s.push_str(&format!("{bit} "));
+            }
+        }
+        s.push('\n');
+        for i in (0..32).rev() {
+            s.push_str(&format!("{i} "));
+        }
+        s.push('\n');
+        // print value
+        for i in (0..32).rev() {
+            let bit = (value >> i) & 0x01;
+            if i >= 10 {
+                s.push_str(&format!("{bit}  "));
+            } else {
+                s.push_str(&format!("{bit} "));
+            }
+        }
+    } else {
+        for i in (0..iter_max).rev() {
+            s.push_str(&format!("{i} "));
+        }
+        s.push('\n');
+        // print value
+        for i in (0..iter_max).rev() {
+            let bit = (value >> i) & 0x01;
+            if i >= 10 {
+                s.push_str(&format!("{bit}  "));
+            } else {
+                s.push_str(&format!("{bit} "));
+            }
+        }
+    }
+    s.push('\n');
+    s
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn inttype_new_from_str() {
+
+        assert!(IntType::new_from_str("1u8").is_ok());
+        assert!(IntType::new_from_str("1u16").is_ok());
+        assert!(IntType::new_from_str("1u32").is_ok());
+        assert!(IntType::new_from_str("1u64").is_ok());
+
+        assert!(IntType::new_from_str("1").is_err());
+        assert!(IntType::new_from_str("a").is_err());
+        assert!(IntType::new_from_str("au8").is_err());
+        assert!(IntType::new_from_str("1u").is_err());
+        assert!(IntType::new_from_str("1u33").is_err());
+
+    }
+
+    #[test]
+    fn operation_new_from_str() {
+
+        assert!(Operation::new_from_str("+").is_ok());
+        assert!(Operation::new_from_str("-").is_ok());
+        assert!(Operation::new_from_str("*").is_ok());
+        assert!(Operation::new_from_str("&").is_ok());
+        assert!(Operation::new_from_str("|").is_ok());
+        assert!(Operation::new_from_str("^").is_ok());
+        assert!(Operation::new_from_str("<<").is_ok());
+        assert!(Operation::new_from_str(">>").is_ok());
+        assert!(Operation::new_from_str("<<<").is_ok());
+        assert!(Operation::new_from_str(">>>").is_ok());
+        assert!(Operation::new_from_str("~").is_ok());
+        assert!(Operation::new_from_str("!").is_ok());
+        assert!(Operation::new_from_str("r").is_ok());
+
+        assert!(Operation::new_from_str("x").is_err());
+
+    }
+
+    #[test]
+    fn operation_symbols() {
+        for symbol in Operation::symbols() {
+            assert!(Operation::new_from_str(symbol).is_ok());
+        }
+    }
+
+    #[test]
+    fn operation_is_unary() {
+
+        assert!(!Operation::new_from_str("+").unwrap().is_unary());
+        assert!(!Operation::new_from_str("-").unwrap().is_unary());
+        assert!(!Operation::new_from_str("*").unwrap().is_unary());
+        assert!(!Operation::new_from_str("&").unwrap().is_unary());
+        assert!(!Operation::new_from_str("|").unwrap().is_unary());
+        assert!(!Operation::new_from_str("^").unwrap().is_unary());
+        assert!(!Operation::new_from_str("<<").unwrap().is_unary());
+        assert!(!Operation::new_from_str(">>").unwrap().is_unary());
+        assert!(!Operation::new_from_str("<<<").unwrap().is_unary());
+        assert!(!Operation::new_from_str(">>>").unwrap().is_unary());
+        assert!(Operation::new_from_str("~").unwrap().is_unary());
+        assert!(Operation::new_from_str("!").unwrap().is_unary());
+        assert!(Operation::new_from_str("r").unwrap().is_unary());
+
+        assert!(Operation::new_from_str("x").is_err());
+
+    }
+
+    #[test]
+    fn test_ascii() {
+        let int = IntType::new_from_str("0x41u8").unwrap();
+        println!("res: {}", int);
+        assert_eq!(int.to_ascii(), "A");
+        let int = IntType::new_from_str("0x4142u16").unwrap();
+        println!("res: {}", int);
+        assert_eq!(int.to_ascii(), "AB");
+        let int = IntType::new_from_str("0x41424344u32").unwrap();
+        println!("res: {}", int);
+        assert_eq!(int.to_ascii(), "ABCD");
+        let int = IntType::new_from_str("0x4142434445464748u64")
+            .unwrap();
+        println!("res: {}", int);
+        assert_eq!(int.to_ascii(), "ABCDEFGH");
+
+        let int = IntType::new_from_str("0x41ff42ff43ff44ffu64")
+            .unwrap();
+        println!("res: {}", int);
+        assert_eq!(int.to_ascii(), "A.B.C.D.");
+
+        let int = IntType::new_from_str("0xffu8")
+            .unwrap();
+        println!("res: {}", int);
+        assert_eq!(int.to_ascii(), ".");
+
+    }
+
+    #[test]
+    fn test_to_literal() {
+        let int = IntType::new_from_str("0x2u8").unwrap();
+        assert_eq!(int.to_literal(), "0x02u8");
+        let int = IntType::new_from_str("51966u16").unwrap();
+        assert_eq!(int.to_literal(), "0xcafeu16");
+        assert_eq!(IntType::new_from_str(&int.to_literal()).unwrap(), int);
+
+    }
+
+    #[test]
+    fn test_operators() {
+        let a = IntType::U8(0xf0);
+        let b: IntType = "0x0fu8".parse().unwrap();
+        assert_eq!(a | b, IntType::U8(0xff));
+        assert_eq!(a & b, IntType::U8(0));
+        assert_eq!(a ^ 0xffu8.into(), IntType::U8(0x0f));
+        assert_eq!(a + a, IntType::U8(0xe0));
+        assert_eq!(b - a, IntType::U8(0x1f));
+        assert_eq!(b * IntType::U8(2), IntType::U8(0x1e));
+        assert_eq!(a / b, IntType::U8(16));
+        assert_eq!(b << 4, a);
+        assert_eq!(a >> 8, IntType::U8(0));
+        assert_eq!(!a, b);
+        assert_eq!(-IntType::U16(1), IntType::U16(0xffff));
+
+        let mut c = IntType::U32(1);
+        c <<= 4;
+        c |= IntType::U32(1);
+        assert_eq!(c, IntType::U32(0x11));
+    }
+
+    #[test]
+    #[should_panic(expected = "operands of different widths u8 and u16")]
+    fn test_operator_widths() {
+        let _ = IntType::U8(1) + IntType::U16(1);
+    }
+
+    #[test]
+    fn test_checked() {
+        assert_eq!(IntType::U8(0xff).checked_add(IntType::U8(1)), None);
+        assert_eq!(IntType::U8(0xfe).checked_add(IntType::U8(1)), Some(IntType::U8(0xff)));
+        assert_eq!(IntType::U8(1).checked_add(IntType::U16(1)), None);
+        assert_eq!(IntType::U8(0).checked_sub(IntType::U8(1)), None);
+        assert_eq!(IntType::U16(0x100).checked_mul(IntType::U16(0x100)), None);
+        assert_eq!(IntType::U32(1).checked_div(IntType::U32(0)), None);
+        assert_eq!(IntType::U8(1).checked_shl(7), Some(IntType::U8(0x80)));
+        assert_eq!(IntType::U8(1).checked_shl(8), None);
+        assert_eq!(IntType::U64(2).checked_shr(1), Some(IntType::U64(1)));
+
+        assert_eq!(IntType::U8(0xff).overflowing_add(IntType::U8(1)),
+            Some((IntType::U8(0), true)));
+        assert_eq!(IntType::U16(0).overflowing_sub(IntType::U16(1)),
+            Some((IntType::U16(0xffff), true)));
+        assert_eq!(IntType::U32(3).overflowing_mul(IntType::U32(3)),
+            Some((IntType::U32(9), false)));
+        assert_eq!(IntType::U32(3).overflowing_mul(IntType::U64(3)), None);
+    }
+
+    #[test]
+    fn test_conversions() {
+        assert_eq!(IntType::from(7u16), IntType::U16(7));
+        assert_eq!(u64::from(IntType::U8(7)), 7);
+        assert_eq!(u8::try_from(IntType::U32(0xff)).unwrap(), 0xff);
+        assert!(u8::try_from(IntType::U32(0x100)).is_err());
+        assert_eq!(u32::try_from(IntType::U64(1 << 31)).unwrap(), 1 << 31);
+        assert!("1u7".parse::<IntType>().is_err());
+    }
+
+    #[test]
+    fn test_format_traits() {
+        let v = IntType::U16(0xcafe);
+        assert_eq!(format!("{v:#x}"), "0xcafe");
+        assert_eq!(format!("{v:X}"), "CAFE");
+        assert_eq!(format!("{:#010x}", IntType::U8(1)), "0x00000001");
+        assert_eq!(format!("{:b}", IntType::U8(5)), "101");
+        assert_eq!(format!("{:o}", IntType::U8(8)), "10");
+    }
+
+}
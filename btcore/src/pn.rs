@@ -0,0 +1,705 @@
+/// the prefix calculator of pn_bitwister, e.g. `+ 0xdead 0xbeef` or the
+/// s-expression `(| (<< 1 4) (<< 1 7) 0x3)`
+/// 0xca7
+use alloc::{
+    boxed::Box,
+    format,
+    string::{String, ToString},
+    vec,
+    vec::Vec,
+};
+use core::fmt;
+use core::ops::Range;
+
+use crate::{
+    EvalError,
+    Evaluation,
+    IntType,
+    Operation,
+    Overflow,
+    Step,
+    Truncation,
+};
+
+/// the width of a calculation
+pub use crate::Width as Bits;
+
+/// operations nested deeper than this are rejected, so a long chain of
+/// `~ ~ ~ ...` can't overflow the stack
+pub(crate) const MAX_DEPTH: usize = 256;
+
+/// what is wrong with an expression
+#[derive(Debug, Clone, PartialEq)]
+pub enum ParseErrorKind {
+    /// the expression is empty
+    Empty,
+    /// neither a number nor an operation
+    InvalidToken(String),
+    /// a number that is not valid, e.g. `0xfg` or too large for 64 bits
+    InvalidNumber(String),
+    /// an operation has fewer operands than it takes
+    MissingOperand { op: Operation, expected: usize, found: usize },
+    /// a token after a complete expression, e.g. the `3` of `+ 1 2 3`, or
+    /// one operand too many in a form, e.g. the `3` of `(~ 1 3)`
+    ExtraOperand(String),
+    /// a `(` without its `)`
+    Unclosed,
+    /// a token where it can't be, e.g. a `)` without `(` or a number
+    /// right after `(`
+    Unexpected(String),
+    /// operations are nested deeper than `MAX_DEPTH`
+    TooDeep,
+}
+
+impl fmt::Display for ParseErrorKind {
+
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParseErrorKind::Empty => write!(f, "empty expression"),
+            ParseErrorKind::InvalidToken(token) => write!(f, "invalid token {token}"),
+            ParseErrorKind::InvalidNumber(number) => write!(f, "invalid number {number}"),
+            ParseErrorKind::MissingOperand { op, expected, found } => {
+                write!(f, "{} takes {expected} operand{}, found {found}", op.symbol(),
+                    if *expected == 1 { "" } else { "s" })
+            },
+            ParseErrorKind::ExtraOperand(token) => {
+                write!(f, "{token} follows a complete expression")
+            },
+            ParseErrorKind::Unclosed => write!(f, "( is never closed"),
+            ParseErrorKind::Unexpected(token) => write!(f, "unexpected {token}"),
+            ParseErrorKind::TooDeep => write!(f, "nested too deep"),
+        }
+    }
+
+}
+
+/// why an expression could not be parsed, with the byte range of the
+/// problem: the bad token, or the operation that lacks operands
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParseError {
+    pub kind: ParseErrorKind,
+    pub span: Range<usize>,
+}
+
+impl fmt::Display for ParseError {
+
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "error: {} at offset {}", self.kind, self.span.start)
+    }
+
+}
+
+impl core::error::Error for ParseError {}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Token {
+    Number(u64),
+    Op(Operation),
+    /// `u8`, `u16`, `u32` or `u64`
+    Width(Bits),
+    Open,
+    Close,
+}
+
+pub struct Tokenizer;
+
+impl Default for Tokenizer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Tokenizer {
+
+    pub fn new() -> Self {
+        Tokenizer {}
+    }
+
+    /// split `input` at any whitespace and around parentheses into tokens
+    /// with their byte range, in the order they were written
+    pub fn tokenize(&self, input: &str) -> Result<Vec<(Token, Range<usize>)>, ParseError> {
+
+        let mut tokens = vec![];
+        let mut rest = input;
+
+        loop {
+            rest = rest.trim_start();
+            if rest.is_empty() {
+                return Ok(tokens);
+            }
+            let start = input.len() - rest.len();
+            let len = match rest.find(|c: char| c.is_whitespace() || c == '(' || c == ')') {
+                Some(0) => 1,
+                Some(len) => len,
+                None => rest.len(),
+            };
+            let (token, tail) = rest.split_at(len);
+            let span = start..start + len;
+
+            if token == "(" {
+                tokens.push((Token::Open, span));
+            } else if token == ")" {
+                tokens.push((Token::Close, span));
+            } else if let Some(width) = Tokenizer::parse_width(token) {
+                tokens.push((Token::Width(width), span));
+            } else if Tokenizer::is_number(token) {
+                match Tokenizer::parse_number(token) {
+                    Ok(n) => tokens.push((Token::Number(n), span)),
+                    Err(_) => return Err(ParseError {
+                        kind: ParseErrorKind::InvalidNumber(token.to_string()),
+                        span,
+                    }),
+                };
+            } else if let Ok(operation) = Operation::new_from_str(token) {
+                tokens.push((Token::Op(operation), span));
+            } else {
+                return Err(ParseError {
+                    kind: ParseErrorKind::InvalidToken(token.to_string()),
+                    span,
+                });
+            }
+            rest = tail;
+        }
+    }
+
+    fn parse_width(token: &str) -> Option<Bits> {
+        match token {
+            "u8" => Some(Bits::U8),
+            "u16" => Some(Bits::U16),
+            "u32" => Some(Bits::U32),
+            "u64" => Some(Bits::U64),
+            _ => None,
+        }
+    }
+
+    /// parse a number, it's either hex or a decimal
+    fn parse_number(token: &str) -> Result<u64, core::num::ParseIntError> {
+        match token.strip_prefix("0x") {
+            Some(hex) => u64::from_str_radix(hex, 16),
+            None => token.parse::<u64>(),
+        }
+    }
+
+    /// if `input` looks like a number, it may still be too large
+    fn is_number(input: &str) -> bool {
+        if input.starts_with("0x") {
+            // SAFETY: prefix exists
+            let input = input.strip_prefix("0x").unwrap(); 
+            if input.is_empty() { return false; }
+            input.chars().all(|c| c.is_ascii_hexdigit())
+        } else {
+            input.chars().all(|c| c.is_ascii_digit())
+        }
+    }
+
+    pub fn is_operand(input: &str) -> bool {
+        Operation::new_from_str(input).is_ok()
+    }
+
+}
+
+/// a parsed prefix expression, every node knows where it was written
+#[derive(Debug, Clone, PartialEq)]
+pub enum Expr {
+    Number(u64, Range<usize>),
+    /// an operation and its operands, as many as it takes or two and more
+    /// if it is associative
+    Op(Operation, Range<usize>, Vec<Expr>),
+    /// an expression calculated in another width, `(u16 ...)`
+    Width(Bits, Range<usize>, Box<Expr>),
+}
+
+impl Expr {
+
+    /// the byte range of the number, the operation symbol or the width
+    pub fn span(&self) -> Range<usize> {
+        match self {
+            Expr::Number(_, span) | Expr::Op(_, span, _) | Expr::Width(_, span, _) => {
+                span.clone()
+            },
+        }
+    }
+}
+
+/// a recursive descent parser: an operation is followed by as many
+/// expressions as it takes operands, a form in parentheses by any number
+/// of them up to its `)`
+struct Parser<'a> {
+    input: &'a str,
+    tokens: Vec<(Token, Range<usize>)>,
+    pos: usize,
+    depth: usize,
+}
+
+impl Parser<'_> {
+
+    fn error(&self, kind: ParseErrorKind, span: &Range<usize>) -> ParseError {
+        ParseError { kind, span: span.clone() }
+    }
+
+    /// the error for the token at `span` being where it is
+    fn unexpected(&self, span: &Range<usize>) -> ParseError {
+        self.error(ParseErrorKind::Unexpected(self.input[span.clone()].to_string()), span)
+    }
+
+    fn next(&mut self) -> Option<(Token, Range<usize>)> {
+        let token = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        token
+    }
+
+    fn expr(&mut self) -> Result<Option<Expr>, ParseError> {
+        if let Some((Token::Close, _)) | None = self.tokens.get(self.pos) {
+            return Ok(None);
+        }
+        // SAFETY: checked above
+        let (token, span) = self.next().unwrap();
+
+        if let Token::Number(n) = token {
+            return Ok(Some(Expr::Number(n, span)));
+        }
+        if self.depth == MAX_DEPTH {
+            return Err(self.error(ParseErrorKind::TooDeep, &span));
+        }
+        self.depth += 1;
+        let expr = match token {
+            Token::Op(op) => self.operation(op, span)?,
+            Token::Width(bits) => match self.expr()? {
+                Some(operand) => Expr::Width(bits, span, Box::new(operand)),
+                None => return Err(self.error(ParseErrorKind::Empty, &span)),
+            },
+            _ => self.form(span)?,
+        };
+        self.depth -= 1;
+        Ok(Some(expr))
+    }
+
+    /// a bare operation and exactly the operands it takes
+    fn operation(&mut self, op: Operation, span: Range<usize>) -> Result<Expr, ParseError> {
+        let expected = if op.is_unary() { 1 } else { 2 };
+        let mut operands = Vec::with_capacity(expected);
+        while operands.len() < expected {
+            match self.expr()? {
+                Some(operand) => operands.push(operand),
+                None => return Err(self.error(ParseErrorKind::MissingOperand {
+                    op, expected, found: operands.len()
+                }, &span)),
+            }
+        }
+        Ok(Expr::Op(op, span, operands))
+    }
+
+    /// the rest of a form after its `(` at `open`: an operation or a width
+    /// and its operands up to the `)`. associative operations take two or
+    /// more operands, the others as many as without parentheses.
+    fn form(&mut self, open: Range<usize>) -> Result<Expr, ParseError> {
+        let (head, span) = self.next()
+            .ok_or(self.error(ParseErrorKind::Unclosed, &open))?;
+        let (min, max) = match head {
+            Token::Op(op) if op.is_unary() => (1, 1),
+            Token::Op(op) if op.is_associative() => (2, usize::MAX),
+            Token::Op(_) => (2, 2),
+            Token::Width(_) => (1, 1),
+            _ => return Err(self.unexpected(&span)),
+        };
+
+        let mut operands = vec![];
+        while let Some(operand) = self.expr()? {
+            if operands.len() == max {
+                return Err(self.error(ParseErrorKind::ExtraOperand(
+                    self.input[operand.span()].to_string()), &operand.span()));
+            }
+            operands.push(operand);
+        }
+        match self.next() {
+            Some((Token::Close, _)) => (),
+            _ => return Err(self.error(ParseErrorKind::Unclosed, &open)),
+        }
+
+        match head {
+            Token::Op(op) if operands.len() < min => {
+                Err(self.error(ParseErrorKind::MissingOperand {
+                    op, expected: min, found: operands.len()
+                }, &span))
+            },
+            Token::Op(op) => Ok(Expr::Op(op, span, operands)),
+            Token::Width(bits) => match operands.pop() {
+                Some(operand) => Ok(Expr::Width(bits, span, Box::new(operand))),
+                None => Err(self.error(ParseErrorKind::Empty, &span)),
+            },
+            _ => unreachable!("a form starts with an operation or a width"),
+        }
+    }
+}
+
+/// parse a prefix expression like `* 2 + 0xdead 0xbeef` or the same as an
+/// s-expression `(* 2 (+ 0xdead 0xbeef))`, tokens are separated by any
+/// whitespace. a width like `u16` calculates the expression after it in
+/// that width, e.g. `(+ (u8 0x1ff) 1)`.
+pub fn parse(input: &str) -> Result<Expr, ParseError> {
+    let mut parser = Parser {
+        input,
+        tokens: Tokenizer::new().tokenize(input)?,
+        pos: 0,
+        depth: 0,
+    };
+    let expr = match parser.expr()? {
+        Some(expr) => expr,
+        None => match parser.tokens.first() {
+            Some((_, span)) => return Err(parser.unexpected(span)),
+            None => return Err(parser.error(ParseErrorKind::Empty, &(0..input.len()))),
+        },
+    };
+    match parser.tokens.get(parser.pos) {
+        Some((Token::Close, span)) => return Err(parser.unexpected(span)),
+        Some((_, span)) => return Err(parser.error(
+            ParseErrorKind::ExtraOperand(input[span.clone()].to_string()), span)),
+        None => (),
+    }
+    Ok(expr)
+}
+
+/// a calculation in the width of the selected `Bits`. numbers are
+/// truncated to it and the operations are the ones of bitwister, so both
+/// calculators give the same result for the same expression.
+pub struct Calculation(Bits);
+
+/// what an evaluation collects besides the value
+struct Collected {
+    overflow: Overflow,
+    truncations: Vec<Truncation>,
+    register_views: Vec<IntType>,
+}
+
+impl Calculation {
+
+    pub fn new(bits: Bits) -> Self {
+        Calculation(bits)
+    }
+
+    pub fn bits(&self) -> Bits {
+        self.0
+    }
+
+    /// evaluate an expression of `parse`, in the width of the calculation
+    /// unless the whole expression is in a width form like `(u16 ...)`
+    pub fn calculate(&self, expr: &Expr) -> Result<Evaluation, EvalError> {
+        self.explain(expr).map(|(evaluation, _)| evaluation)
+    }
+
+    /// like `calculate`, with every step that led to the result
+    pub fn explain(&self, expr: &Expr) -> Result<(Evaluation, Step), EvalError> {
+        let mut collected = Collected {
+            overflow: Overflow::NotPossible,
+            truncations: vec![],
+            register_views: vec![],
+        };
+        // a width around everything is the width of the result
+        let step = match expr {
+            Expr::Width(bits, _, expr) => self.eval(expr, *bits, &mut collected)?,
+            expr => self.eval(expr, self.0, &mut collected)?,
+        };
+        Ok((Evaluation {
+            value: step.value,
+            overflow: collected.overflow,
+            truncations: collected.truncations,
+            register_views: collected.register_views,
+        }, step))
+    }
+
+    /// evaluate `expr` in the width `bits`, the result of a `(u16 ...)` is
+    /// zero extended or truncated to the width around it
+    fn eval(&self, expr: &Expr, bits: Bits, collected: &mut Collected)
+        -> Result<Step, EvalError> {
+        match expr {
+            Expr::Number(n, _) => {
+                Ok(Calculation::truncate(&format!("{n:#x}"), *n, bits, collected, vec![]))
+            },
+            Expr::Width(width, _, expr) => {
+                let operand = self.eval(expr, *width, collected)?;
+                Ok(Calculation::truncate(&width.to_string(), operand.value.as_u64(), bits,
+                    collected, vec![operand]))
+            },
+            Expr::Op(op, _, operands) => {
+                let operands = operands.iter()
+                    .map(|operand| self.eval(operand, bits, collected))
+                    .collect::<Result<Vec<_>, _>>()?;
+                let values: Vec<IntType> = operands.iter().map(|step| step.value).collect();
+                if *op == Operation::Reg {
+                    collected.register_views.push(values[0]);
+                }
+                let (value, overflow) = if op.is_unary() {
+                    op.apply(&values)?
+                } else {
+                    // a chain of an associative operation is applied from the left
+                    let (first, rest) = values.split_first()
+                        .ok_or(EvalError::Unexpected(op.symbol().to_string()))?;
+                    rest.iter().try_fold((*first, Overflow::NotPossible), |(acc, overflow), value| {
+                        let (value, next) = op.apply(&[acc, *value])?;
+                        Ok::<_, EvalError>((value, overflow.combine(next)))
+                    })?
+                };
+                collected.overflow = collected.overflow.combine(overflow);
+                Ok(Step {
+                    label: op.symbol().to_string(),
+                    value,
+                    overflow,
+                    truncation: None,
+                    operands,
+                })
+            },
+        }
+    }
+
+    /// the step of `n` in the width `bits`, recording a truncation if it
+    /// doesn't fit
+    fn truncate(label: &str, n: u64, bits: Bits, collected: &mut Collected,
+        operands: Vec<Step>) -> Step {
+        let (value, truncated) = IntType::from_u64_truncating(n, bits);
+        let truncation = truncated.then(|| Truncation {
+            literal: format!("{n:#x}"),
+            width: bits,
+            value: value.as_u64(),
+        });
+        collected.truncations.extend(truncation.clone());
+        Step { truncation, operands, ..Step::value(label, value) }
+    }
+
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Evaluator;
+
+    fn calculate(bits: Bits, expr: &str) -> Result<Evaluation, EvalError> {
+        Calculation::new(bits).calculate(&parse(expr).unwrap())
+    }
+
+    #[test]
+    fn test_parse_number() {
+        assert!(Tokenizer::parse_number("0xdeadbeef").is_ok());
+        assert!(Tokenizer::parse_number("1234").is_ok());
+        assert!(Tokenizer::parse_number("abcd").is_err());
+        assert!(Tokenizer::parse_number("0xgggg").is_err());
+        assert!(Tokenizer::parse_number("1a30").is_err());
+    }
+
+    #[test]
+    fn test_is_number() {
+        assert!(Tokenizer::is_number("0xdeadbeef"));
+        assert!(Tokenizer::is_number("1234"));
+        assert!(!Tokenizer::is_number("1ddddddddd"));
+        assert!(!Tokenizer::is_number("0x"));
+    }
+
+    #[test]
+    fn test_operator_symbols() {
+        for symbol in Operation::symbols() {
+            assert!(Tokenizer::is_operand(symbol));
+        }
+        assert!(!Tokenizer::is_operand("x"));
+    }
+
+    #[test]
+    fn test_tokenize() {
+        let tokens = Tokenizer::new().tokenize(" +\t2  0x10\n").unwrap();
+        assert_eq!(tokens, vec![
+            (Token::Op(Operation::Add), 1..2),
+            (Token::Number(2), 3..4),
+            (Token::Number(0x10), 6..10),
+        ]);
+    }
+
+    #[test]
+    fn test_parse() {
+        let expr = parse("! + 1 2").unwrap();
+        assert_eq!(expr, Expr::Op(Operation::Not, 0..1, vec![
+            Expr::Op(Operation::Add, 2..3, vec![
+                Expr::Number(1, 4..5),
+                Expr::Number(2, 6..7),
+            ]),
+        ]));
+
+        let res = calculate(Bits::U64, "+ 2 + 0xdead 0xbeef");
+        assert_eq!(res.unwrap().value, IntType::U64(0x19d9e));
+        let res = calculate(Bits::U64, "! + 0xdead 0xbeef");
+        assert_eq!(res.unwrap().value, IntType::U64(!0x19d9c));
+        let res = calculate(Bits::U8, "+ * 2 3 4");
+        assert_eq!(res.unwrap().value, IntType::U8(10));
+        let res = calculate(Bits::U8, "\t+   1\n\t2 ");
+        assert_eq!(res.unwrap().value, IntType::U8(3));
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        let kind = |expr| parse(expr).map_err(|err| (err.kind, err.span));
+
+        assert_eq!(kind(""), Err((ParseErrorKind::Empty, 0..0)));
+        assert_eq!(kind("  "), Err((ParseErrorKind::Empty, 0..2)));
+        assert_eq!(kind("+ 1"), Err((ParseErrorKind::MissingOperand {
+            op: Operation::Add, expected: 2, found: 1 }, 0..1)));
+        assert_eq!(kind("+ 1 ~"), Err((ParseErrorKind::MissingOperand {
+            op: Operation::Neg, expected: 1, found: 0 }, 4..5)));
+        assert_eq!(kind("+ 1 2 3"), Err((ParseErrorKind::ExtraOperand("3".into()), 6..7)));
+        assert_eq!(kind("1 2"), Err((ParseErrorKind::ExtraOperand("2".into()), 2..3)));
+        assert_eq!(kind("+ 1 x"), Err((ParseErrorKind::InvalidToken("x".into()), 4..5)));
+        assert_eq!(kind("+ 0xfg 1"), Err((ParseErrorKind::InvalidToken("0xfg".into()), 2..6)));
+        assert_eq!(kind("+ 0x1ffffffffffffffff 1"),
+            Err((ParseErrorKind::InvalidNumber("0x1ffffffffffffffff".into()), 2..21)));
+
+        let deep = "~ ".repeat(MAX_DEPTH + 1) + "1";
+        assert_eq!(parse(&deep).unwrap_err().kind, ParseErrorKind::TooDeep);
+        let deep = "~ ".repeat(MAX_DEPTH) + "1";
+        assert!(parse(&deep).is_ok());
+
+        assert_eq!(parse("+ 1").unwrap_err().to_string(),
+            "error: + takes 2 operands, found 1 at offset 0");
+    }
+
+    #[test]
+    fn test_calculations() {
+        let res = calculate(Bits::U8, "+ 0xde 1").unwrap();
+        assert_eq!((res.value, res.overflow), (IntType::U8(0xdf), Overflow::NoOverflow));
+        let res = calculate(Bits::U8, "+ 1 0xff").unwrap();
+        assert_eq!((res.value, res.overflow), (IntType::U8(0), Overflow::Occured));
+        let res = calculate(Bits::U16, "- 0xdead 0xbeef").unwrap();
+        assert_eq!(res.value, IntType::U16(0x1fbe));
+        let res = calculate(Bits::U32, "+ 1 0xffffffff").unwrap();
+        assert_eq!(res.overflow, Overflow::Occured);
+        let res = calculate(Bits::U64, "+ 1 0xffffffffffffffff").unwrap();
+        assert_eq!(res.value, IntType::U64(0));
+
+        // the overflow of every operation counts, not only the last one
+        let res = calculate(Bits::U8, "| + 0xff 1 0").unwrap();
+        assert_eq!(res.overflow, Overflow::Occured);
+    }
+
+    #[test]
+    fn test_div_by_zero() {
+        assert_eq!(calculate(Bits::U8, "/ 4 0x100").unwrap_err(), EvalError::DivisionByZero);
+        let res = calculate(Bits::U16, "/ 4 0x10000");
+        assert_eq!(res.unwrap_err(), EvalError::DivisionByZero);
+    }
+
+    #[test]
+    fn test_truncation() {
+        let res = calculate(Bits::U8, "+ 0x1ff 1").unwrap();
+        assert_eq!(res.value, IntType::U8(0));
+        assert_eq!(res.truncations[0].literal, "0x1ff");
+    }
+
+    #[test]
+    fn test_s_expressions() {
+        let res = calculate(Bits::U32, "(| (<< 1 4) (<< 1 7) 0x3)");
+        assert_eq!(res.unwrap().value, IntType::U32(0x93));
+        let res = calculate(Bits::U8, "(+(* 2 3)4)");
+        assert_eq!(res.unwrap().value, IntType::U8(10));
+        let res = calculate(Bits::U8, "(+ 1 2 3 4)");
+        assert_eq!(res.unwrap().value, IntType::U8(10));
+        let res = calculate(Bits::U8, "(^ 0xff 0x0f 0xf0 1)");
+        assert_eq!(res.unwrap().value, IntType::U8(1));
+
+        // both forms mix and give the same result
+        let res = calculate(Bits::U16, "(* 2 + 0xdead 0xbeef)");
+        assert_eq!(res, calculate(Bits::U16, "* 2 + 0xdead 0xbeef"));
+        let res = calculate(Bits::U16, "! (+ 1 2)");
+        assert_eq!(res, calculate(Bits::U16, "(! + 1 2)"));
+
+        // the overflow of any step of a chain counts
+        let res = calculate(Bits::U8, "(+ 0xff 1 0)").unwrap();
+        assert_eq!(res.overflow, Overflow::Occured);
+    }
+
+    #[test]
+    fn test_width_forms() {
+        let res = calculate(Bits::U32, "(u16 (+ 0xffff 1))").unwrap();
+        assert_eq!((res.value, res.overflow), (IntType::U16(0), Overflow::Occured));
+        let res = calculate(Bits::U32, "u8 ! 0").unwrap();
+        assert_eq!(res.value, IntType::U8(0xff));
+
+        // a sub-expression in another width is extended to the outer one
+        let res = calculate(Bits::U32, "(+ (u8 (! 0)) 1)").unwrap();
+        assert_eq!(res.value, IntType::U32(0x100));
+        assert!(res.truncations.is_empty());
+
+        // and truncated if it doesn't fit
+        let res = calculate(Bits::U8, "(+ (u16 0x1ff) 1)").unwrap();
+        assert_eq!(res.value, IntType::U8(0));
+        assert_eq!(res.truncations[0], Truncation {
+            literal: "0x1ff".into(),
+            width: Bits::U8,
+            value: 0xff,
+        });
+    }
+
+    #[test]
+    fn test_form_errors() {
+        let kind = |expr| parse(expr).map_err(|err| (err.kind, err.span));
+
+        assert_eq!(kind("(+ 1 2"), Err((ParseErrorKind::Unclosed, 0..1)));
+        assert_eq!(kind("(+ 1 (~ 2)"), Err((ParseErrorKind::Unclosed, 0..1)));
+        assert_eq!(kind("("), Err((ParseErrorKind::Unclosed, 0..1)));
+        assert_eq!(kind("(+ 1 2))"), Err((ParseErrorKind::Unexpected(")".into()), 7..8)));
+        assert_eq!(kind(")"), Err((ParseErrorKind::Unexpected(")".into()), 0..1)));
+        assert_eq!(kind("(1 2)"), Err((ParseErrorKind::Unexpected("1".into()), 1..2)));
+        assert_eq!(kind("(+ 1)"), Err((ParseErrorKind::MissingOperand {
+            op: Operation::Add, expected: 2, found: 1 }, 1..2)));
+        assert_eq!(kind("(<< 1 2 3)"), Err((ParseErrorKind::ExtraOperand("3".into()), 8..9)));
+        assert_eq!(kind("(~ 1 2)"), Err((ParseErrorKind::ExtraOperand("2".into()), 5..6)));
+        assert_eq!(kind("(u16)"), Err((ParseErrorKind::Empty, 1..4)));
+        assert_eq!(kind("(u16 1 2)"), Err((ParseErrorKind::ExtraOperand("2".into()), 7..8)));
+        assert_eq!(kind("+ 1 )"), Err((ParseErrorKind::MissingOperand {
+            op: Operation::Add, expected: 2, found: 1 }, 0..1)));
+
+        let deep = "(~ ".repeat(MAX_DEPTH + 1) + "1" + &")".repeat(MAX_DEPTH + 1);
+        assert_eq!(parse(&deep).unwrap_err().kind, ParseErrorKind::TooDeep);
+    }
+
+    #[test]
+    fn test_explain() {
+        let expr = parse("(^ (+ 0xff 1) (u16 0x1ff) ~ 2)").unwrap();
+        let (evaluation, step) = Calculation::new(Bits::U8).explain(&expr).unwrap();
+        assert_eq!(evaluation.value, IntType::U8(0x01));
+        assert_eq!(evaluation.overflow, Overflow::Occured);
+        assert_eq!(step.to_string(), "\
+^ = 0x01 0b00000001 (u8)
+  + = 0x00 0b00000000 (u8, overflow (carry out))
+    0xff = 0xff 0b11111111 (u8)
+    0x1 = 0x01 0b00000001 (u8)
+  u16 = 0xff 0b11111111 (u8, truncated from 0x1ff)
+    0x1ff = 0x01ff 0b0000000111111111 (u16)
+  ~ = 0xfe 0b11111110 (u8)
+    0x2 = 0x02 0b00000010 (u8)");
+
+        // the overflow of a chain is the one of any of its steps
+        let expr = parse("(+ 0xff 1 1)").unwrap();
+        let (_, step) = Calculation::new(Bits::U8).explain(&expr).unwrap();
+        assert_eq!(step.overflow, Overflow::Occured);
+    }
+
+    #[test]
+    fn test_same_as_infix() {
+        let exprs = [
+            ("+ 0xfe 2", "0xfe + 2"),
+            ("<< 1 9", "1 << 9"),
+            ("<<< 0x81 1", "0x81 <<< 1"),
+            ("~ 1", "~1"),
+            ("! 0x0f", "!0x0f"),
+            ("e 0x1234", "e 0x1234"),
+            ("r 0x80", "r 0x80"),
+            ("* - 10 3 >> 0x80 4", "(10 - 3) * (0x80 >> 4)"),
+            ("(| (<< 1 4) (<< 1 7) 0x3)", "(1 << 4) | (1 << 7) | 0x3"),
+            ("(* 3 5 7)", "3 * 5 * 7"),
+        ];
+        for bits in [Bits::U8, Bits::U16, Bits::U32, Bits::U64] {
+            let mut evaluator = Evaluator::new();
+            evaluator.set_width(bits);
+            for (prefix, infix) in exprs {
+                assert_eq!(calculate(bits, prefix), evaluator.evaluate(infix),
+                    "{prefix} and {infix} as {bits}");
+            }
+        }
+    }
+
+}
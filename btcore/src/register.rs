@@ -0,0 +1,258 @@
+/// register definitions: named registers made of named bit fields, read
+/// from a small text format
+/// 0xca7
+use alloc::{
+    format,
+    string::{String, ToString},
+    vec,
+    vec::Vec,
+};
+use core::fmt;
+
+use crate::{
+    IntType,
+    Width,
+};
+
+/// why a register definition could not be parsed, with its line number
+#[derive(Debug, PartialEq)]
+pub struct RegisterError {
+    /// 1-based line of the definition
+    pub line: usize,
+    pub msg: String,
+}
+
+impl fmt::Display for RegisterError {
+
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.msg)
+    }
+
+}
+
+impl core::error::Error for RegisterError {}
+
+/// a range of bits inside a register
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Field {
+    pub name: String,
+    /// highest bit of the field
+    pub msb: u32,
+    /// lowest bit of the field
+    pub lsb: u32,
+    pub description: Option<String>,
+}
+
+impl Field {
+
+    /// number of bits of the field
+    pub fn bits(&self) -> u32 {
+        self.msb - self.lsb + 1
+    }
+
+    /// the bits of the field, in place
+    pub fn mask(&self) -> u64 {
+        (u64::MAX >> (64 - self.bits())) << self.lsb
+    }
+
+    /// the value of the field in `value`, shifted down to bit 0
+    pub fn extract(&self, value: u64) -> u64 {
+        (value & self.mask()) >> self.lsb
+    }
+
+    /// the bit range as in a datasheet, `[3]` or `[7:4]`
+    pub fn range(&self) -> String {
+        if self.msb == self.lsb {
+            format!("[{}]", self.lsb)
+        } else {
+            format!("[{}:{}]", self.msb, self.lsb)
+        }
+    }
+}
+
+/// a register with its fields, ordered as they were defined
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Register {
+    pub name: String,
+    pub width: Width,
+    #[cfg_attr(feature = "serde", serde(with = "crate::serialize::hex_option", default))]
+    pub address: Option<u64>,
+    pub fields: Vec<Field>,
+}
+
+impl Register {
+
+    /// the field called `name`
+    pub fn field(&self, name: &str) -> Option<&Field> {
+        self.fields.iter().find(|f| f.name == name)
+    }
+
+    /// the value of every field in `value`
+    pub fn decode(&self, value: &IntType) -> Vec<(&Field, u64)> {
+        self.fields.iter()
+            .map(|field| (field, field.extract(value.as_u64())))
+            .collect()
+    }
+
+    /// one line per field: name, bit range and value
+    pub fn describe(&self, value: &IntType) -> String {
+        let name_width = self.fields.iter()
+            .map(|f| f.name.len())
+            .max()
+            .unwrap_or(0);
+        let mut s = String::new();
+        for (field, v) in self.decode(value) {
+            s.push_str(&format!("{:<name_width$} {:<7} = 0x{:x} ({})",
+                field.name, field.range(), v, v));
+            if let Some(description) = &field.description {
+                s.push_str(&format!("  {description}"));
+            }
+            s.push('\n');
+        }
+        s
+    }
+}
+
+/// parse a `u32`-style number, hex with `0x`
+fn parse_u64(s: &str) -> Option<u64> {
+    match s.strip_prefix("0x") {
+        Some(hex) => u64::from_str_radix(hex, 16).ok(),
+        None => s.parse::<u64>().ok(),
+    }
+}
+
+/// parse `7:4` or `3`
+fn parse_range(s: &str) -> Option<(u32, u32)> {
+    match s.split_once(':') {
+        Some((msb, lsb)) => Some((msb.parse().ok()?, lsb.parse().ok()?)),
+        None => {
+            let bit = s.parse().ok()?;
+            Some((bit, bit))
+        },
+    }
+}
+
+/// parse register definitions. blank lines and everything after a `#` are
+/// ignored, a register is followed by its fields:
+///
+/// ```text
+/// register RCC_CR u32 0x40021000
+///     field HSION   0       internal oscillator enable
+///     field HSITRIM 7:3
+/// ```
+///
+/// the address and the field descriptions are optional.
+pub fn parse_registers(input: &str) -> Result<Vec<Register>, RegisterError> {
+
+    let mut registers: Vec<Register> = vec![];
+
+    for (idx, line) in input.lines().enumerate() {
+        let err = |msg: String| RegisterError { line: idx + 1, msg };
+        let line = match line.split_once('#') {
+            Some((line, _)) => line,
+            None => line,
+        };
+        let words: Vec<&str> = line.split_whitespace().collect();
+
+        match words.as_slice() {
+            [] => (),
+            ["register", name, width, rest @ ..] => {
+                if registers.iter().any(|r| r.name == *name) {
+                    return Err(err(format!("register {name} is defined twice")));
+                }
+                let width = width.parse::<Width>()
+                    .map_err(|_| err(format!("invalid width {width}")))?;
+                let address = match rest {
+                    [] => None,
+                    [address] => Some(parse_u64(address)
+                        .ok_or_else(|| err(format!("invalid address {address}")))?),
+                    _ => return Err(err(String::from("expected register NAME WIDTH [ADDRESS]"))),
+                };
+                registers.push(Register {
+                    name: name.to_string(),
+                    width,
+                    address,
+                    fields: vec![],
+                });
+            },
+            ["field", name, range, description @ ..] => {
+                let register = registers.last_mut()
+                    .ok_or_else(|| err(format!("field {name} outside of a register")))?;
+                let (msb, lsb) = parse_range(range)
+                    .ok_or_else(|| err(format!("invalid bit range {range}")))?;
+                if msb < lsb {
+                    return Err(err(format!("bit range {range} is reversed")));
+                }
+                if msb as usize >= register.width.bits() {
+                    return Err(err(format!("field {name} does not fit {}", register.width)));
+                }
+                let field = Field {
+                    name: name.to_string(),
+                    msb,
+                    lsb,
+                    description: if description.is_empty() {
+                        None
+                    } else {
+                        Some(description.join(" "))
+                    },
+                };
+                if let Some(other) = register.fields.iter()
+                    .find(|f| f.name == field.name || f.mask() & field.mask() != 0) {
+                    return Err(err(format!("field {name} overlaps field {}", other.name)));
+                }
+                register.fields.push(field);
+            },
+            [keyword, ..] => return Err(err(format!("expected register or field, got {keyword}"))),
+        }
+    }
+
+    Ok(registers)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RCC: &str = "\
+# clock control
+register RCC_CR u32 0x40021000
+    field HSION   0     internal oscillator enable
+    field HSITRIM 7:3
+register STATUS u8
+    field BUSY 7
+";
+
+    #[test]
+    fn test_parse_registers() {
+        let registers = parse_registers(RCC).unwrap();
+        assert_eq!(registers.len(), 2);
+        assert_eq!(registers[0].address, Some(0x40021000));
+        assert_eq!(registers[0].fields[0].description.as_deref(),
+            Some("internal oscillator enable"));
+        assert_eq!(registers[0].field("HSITRIM").unwrap().mask(), 0xf8);
+        assert_eq!(registers[1].width, Width::U8);
+    }
+
+    #[test]
+    fn test_decode() {
+        let registers = parse_registers(RCC).unwrap();
+        let decoded = registers[0].decode(&IntType::U32(0x83));
+        assert_eq!(decoded[0].1, 1);
+        assert_eq!(decoded[1].1, 0x10);
+        assert_eq!(registers[1].describe(&IntType::U8(0x80)), "BUSY [7]     = 0x1 (1)\n");
+    }
+
+    #[test]
+    fn test_register_errors() {
+        let err = |input: &str| parse_registers(input).unwrap_err().to_string();
+        assert_eq!(err("field A 0"), "line 1: field A outside of a register");
+        assert_eq!(err("register A u7"), "line 1: invalid width u7");
+        assert_eq!(err("register A u8\nfield B 8"), "line 2: field B does not fit u8");
+        assert_eq!(err("register A u8\nfield B 3:0\nfield C 4:3"),
+            "line 3: field C overlaps field B");
+        assert_eq!(err("register A u8\nfield B 0:3"), "line 2: bit range 0:3 is reversed");
+    }
+
+}
@@ -0,0 +1,445 @@
+/// an RPN stack calculator like the HP ones: numbers are pushed, operations
+/// take their operands from the stack and push the result
+/// 0xca7
+use alloc::{
+    format,
+    string::{String, ToString},
+    vec,
+    vec::Vec,
+};
+use core::fmt;
+use core::ops::Range;
+
+use crate::pn::{
+    Bits,
+    Calculation,
+    Expr,
+    ParseErrorKind,
+    Token,
+    Tokenizer,
+};
+use crate::{
+    EvalError,
+    IntType,
+    Overflow,
+    Truncation,
+};
+
+/// how many lines `undo` can go back
+const UNDO_LEVELS: usize = 64;
+
+/// the words that manipulate the stack itself
+pub const COMMANDS: [&str; 7] = ["dup", "swap", "drop", "rot", "clear", "undo", "stack"];
+
+/// what is wrong with a word of a line
+#[derive(Debug, Clone, PartialEq)]
+pub enum StackErrorKind {
+    /// neither a number, an operation, a width nor a command
+    InvalidToken(String),
+    /// a number that is not valid, e.g. `0xfg` or too large for 64 bits
+    InvalidNumber(String),
+    /// the stack has fewer entries than the word takes
+    TooFew { word: String, needed: usize, found: usize },
+    /// no line before to go back to
+    NothingToUndo,
+    Eval(EvalError),
+}
+
+impl fmt::Display for StackErrorKind {
+
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StackErrorKind::InvalidToken(token) => write!(f, "invalid token {token}"),
+            StackErrorKind::InvalidNumber(number) => write!(f, "invalid number {number}"),
+            StackErrorKind::TooFew { word, needed, found } => {
+                write!(f, "{word} takes {needed} entr{} of the stack, found {found}",
+                    if *needed == 1 { "y" } else { "ies" })
+            },
+            StackErrorKind::NothingToUndo => write!(f, "nothing to undo"),
+            StackErrorKind::Eval(err) => {
+                // the message of an `EvalError` has its own prefix
+                let message = err.to_string();
+                write!(f, "{}", message.strip_prefix("error: ").unwrap_or(&message))
+            },
+        }
+    }
+
+}
+
+/// why a line failed and the byte range of the word to blame. the stack is
+/// left as it was before the line.
+#[derive(Debug, Clone, PartialEq)]
+pub struct StackError {
+    pub kind: StackErrorKind,
+    pub span: Range<usize>,
+}
+
+impl fmt::Display for StackError {
+
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "error: {} at offset {}", self.kind, self.span.start)
+    }
+
+}
+
+impl core::error::Error for StackError {}
+
+/// what a line did besides changing the stack
+#[derive(Debug, Clone, PartialEq)]
+pub struct Outcome {
+    /// `Occured` if any operation of the line overflowed
+    pub overflow: Overflow,
+    /// every number that did not fit the width, also entries truncated by
+    /// switching to a smaller width
+    pub truncations: Vec<Truncation>,
+    /// the values the `r` operation was applied to
+    pub register_views: Vec<IntType>,
+}
+
+/// the stack and the width its entries are calculated in
+#[derive(Clone)]
+struct State {
+    bits: Bits,
+    entries: Vec<IntType>,
+}
+
+/// a persistent stack, every line is evaluated word by word:
+///
+/// - a number is pushed in the width of the stack
+/// - an operation takes its operands from the stack, the top one last, so
+///   `10 3 -` is 7
+/// - `u8`, `u16`, `u32` or `u64` switch the width of the stack and its
+///   entries
+/// - `dup`, `swap`, `drop` and `rot` manipulate the top entries, `clear`
+///   empties the stack, `undo` goes back to before the last line and
+///   `stack` does nothing but show it
+#[derive(Clone)]
+pub struct Stack {
+    state: State,
+    undo: Vec<State>,
+}
+
+impl Stack {
+
+    pub fn new(bits: Bits) -> Self {
+        Stack {
+            state: State { bits, entries: vec![] },
+            undo: vec![],
+        }
+    }
+
+    pub fn bits(&self) -> Bits {
+        self.state.bits
+    }
+
+    /// the entries from the bottom to the top of the stack
+    pub fn entries(&self) -> &[IntType] {
+        &self.state.entries
+    }
+
+    /// every entry in hex and decimal, numbered like the levels of an HP
+    /// calculator with level 1 at the bottom. if `signed` is set, the
+    /// decimals are two's complement signed values.
+    pub fn render(&self, signed: bool) -> String {
+        let entries = self.entries();
+        if entries.is_empty() {
+            return format!("    (empty {} stack)", self.bits());
+        }
+        let digits = self.bits().bits() / 4;
+        let levels = entries.len().to_string().len();
+        let lines: Vec<String> = entries.iter()
+            .enumerate()
+            .map(|(i, entry)| {
+                let dec = if signed {
+                    entry.as_i64().to_string()
+                } else {
+                    entry.as_u64().to_string()
+                };
+                format!("{:>levels$}: {:#0w$x}  {dec}", entries.len() - i, entry.as_u64(),
+                    w = digits + 2)
+            })
+            .collect();
+        lines.join("\n")
+    }
+
+    /// evaluate a line, if a word fails the whole line is undone
+    pub fn eval(&mut self, line: &str) -> Result<Outcome, StackError> {
+        let before = (self.state.clone(), self.undo.clone());
+        let mut outcome = Outcome {
+            overflow: Overflow::NotPossible,
+            truncations: vec![],
+            register_views: vec![],
+        };
+        let mut changed = false;
+
+        for (word, span) in Stack::words(line) {
+            let result = self.word(word, span.clone(), &mut outcome);
+            match result {
+                Ok(true) => changed = true,
+                Ok(false) => (),
+                Err(kind) => {
+                    (self.state, self.undo) = before;
+                    return Err(StackError { kind, span });
+                },
+            }
+        }
+
+        // only a line that changed the stack can be undone
+        if changed {
+            self.undo.push(before.0);
+            if self.undo.len() > UNDO_LEVELS {
+                self.undo.remove(0);
+            }
+        }
+        Ok(outcome)
+    }
+
+    /// split `line` at any whitespace into words with their byte range
+    fn words(line: &str) -> impl Iterator<Item = (&str, Range<usize>)> {
+        line.split(char::is_whitespace)
+            .filter(|word| !word.is_empty())
+            .map(move |word| {
+                let start = word.as_ptr() as usize - line.as_ptr() as usize;
+                (word, start..start + word.len())
+            })
+    }
+
+    /// evaluate a word, returns if it changed the stack in a way `undo` can
+    /// go back on
+    fn word(&mut self, word: &str, span: Range<usize>, outcome: &mut Outcome)
+        -> Result<bool, StackErrorKind> {
+        match word {
+            "dup" => {
+                let [a] = self.pop(word)?;
+                self.state.entries.extend([a, a]);
+            },
+            "swap" => {
+                let [a, b] = self.pop(word)?;
+                self.state.entries.extend([b, a]);
+            },
+            "drop" => {
+                self.pop::<1>(word)?;
+            },
+            "rot" => {
+                let [a, b, c] = self.pop(word)?;
+                self.state.entries.extend([b, c, a]);
+            },
+            "clear" => self.state.entries.clear(),
+            "undo" => {
+                self.state = self.undo.pop().ok_or(StackErrorKind::NothingToUndo)?;
+                return Ok(false);
+            },
+            "stack" => return Ok(false),
+            _ => return self.calculate(word, span, outcome).map(|_| true),
+        }
+        Ok(true)
+    }
+
+    /// push a number, apply an operation or switch the width
+    fn calculate(&mut self, word: &str, span: Range<usize>, outcome: &mut Outcome)
+        -> Result<(), StackErrorKind> {
+        let token = match Tokenizer::new().tokenize(word) {
+            Ok(tokens) if tokens.len() == 1 => tokens[0].0,
+            Err(err) if matches!(err.kind, ParseErrorKind::InvalidNumber(_)) => {
+                return Err(StackErrorKind::InvalidNumber(word.to_string()));
+            },
+            _ => return Err(StackErrorKind::InvalidToken(word.to_string())),
+        };
+
+        let expr = match token {
+            Token::Number(n) => Expr::Number(n, span),
+            Token::Op(op) => {
+                let operands = if op.is_unary() {
+                    self.pop::<1>(word)?.to_vec()
+                } else {
+                    self.pop::<2>(word)?.to_vec()
+                };
+                let operands = operands.iter()
+                    .map(|operand| Expr::Number(operand.as_u64(), span.clone()))
+                    .collect();
+                Expr::Op(op, span, operands)
+            },
+            Token::Width(bits) => {
+                self.set_bits(bits, outcome);
+                return Ok(());
+            },
+            Token::Open | Token::Close => {
+                return Err(StackErrorKind::InvalidToken(word.to_string()));
+            },
+        };
+
+        let evaluation = Calculation::new(self.state.bits)
+            .calculate(&expr)
+            .map_err(StackErrorKind::Eval)?;
+        outcome.overflow = outcome.overflow.combine(evaluation.overflow);
+        outcome.truncations.extend(evaluation.truncations);
+        outcome.register_views.extend(evaluation.register_views);
+        self.state.entries.push(evaluation.value);
+        Ok(())
+    }
+
+    /// the top `N` entries, the top one last
+    fn pop<const N: usize>(&mut self, word: &str) -> Result<[IntType; N], StackErrorKind> {
+        let entries = &mut self.state.entries;
+        if entries.len() < N {
+            return Err(StackErrorKind::TooFew {
+                word: word.to_string(),
+                needed: N,
+                found: entries.len(),
+            });
+        }
+        let top = entries.split_off(entries.len() - N);
+        // SAFETY: exactly N entries were split off
+        Ok(top.try_into().unwrap())
+    }
+
+    /// calculate in `bits` from now on, the entries are zero extended or
+    /// truncated to it
+    fn set_bits(&mut self, bits: Bits, outcome: &mut Outcome) {
+        self.state.bits = bits;
+        for entry in self.state.entries.iter_mut() {
+            let (value, truncated) = IntType::from_u64_truncating(entry.as_u64(), bits);
+            if truncated {
+                outcome.truncations.push(Truncation {
+                    literal: format!("{:#x}", entry.as_u64()),
+                    width: bits,
+                    value: value.as_u64(),
+                });
+            }
+            *entry = value;
+        }
+    }
+
+}
+
+impl fmt::Display for Stack {
+
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.render(false))
+    }
+
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn values(stack: &Stack) -> Vec<u64> {
+        stack.entries().iter().map(IntType::as_u64).collect()
+    }
+
+    #[test]
+    fn test_push_and_apply() {
+        let mut stack = Stack::new(Bits::U8);
+        stack.eval("10 3").unwrap();
+        assert_eq!(values(&stack), [10, 3]);
+        stack.eval("-").unwrap();
+        assert_eq!(stack.entries(), [IntType::U8(7)]);
+
+        let outcome = stack.eval("\t0xff  +").unwrap();
+        assert_eq!(outcome.overflow, Overflow::Occured);
+        assert_eq!(values(&stack), [6]);
+        stack.eval("!").unwrap();
+        assert_eq!(values(&stack), [0xf9]);
+
+        let outcome = stack.eval("0x1ff").unwrap();
+        assert_eq!(outcome.truncations[0].value, 0xff);
+        let outcome = stack.eval("r").unwrap();
+        assert_eq!(outcome.register_views, [IntType::U8(0xff)]);
+    }
+
+    #[test]
+    fn test_commands() {
+        let mut stack = Stack::new(Bits::U32);
+        stack.eval("1 2 3").unwrap();
+        stack.eval("rot").unwrap();
+        assert_eq!(values(&stack), [2, 3, 1]);
+        stack.eval("swap").unwrap();
+        assert_eq!(values(&stack), [2, 1, 3]);
+        stack.eval("dup").unwrap();
+        assert_eq!(values(&stack), [2, 1, 3, 3]);
+        stack.eval("drop drop").unwrap();
+        assert_eq!(values(&stack), [2, 1]);
+        stack.eval("stack").unwrap();
+        assert_eq!(values(&stack), [2, 1]);
+        stack.eval("clear").unwrap();
+        assert!(stack.entries().is_empty());
+
+        // undo goes back line by line, `stack` is not a line to undo
+        stack.eval("undo").unwrap();
+        assert_eq!(values(&stack), [2, 1]);
+        stack.eval("undo undo").unwrap();
+        assert_eq!(values(&stack), [2, 1, 3]);
+    }
+
+    #[test]
+    fn test_widths() {
+        let mut stack = Stack::new(Bits::U16);
+        stack.eval("0x1234 1").unwrap();
+        let outcome = stack.eval("u8").unwrap();
+        assert_eq!(stack.entries(), [IntType::U8(0x34), IntType::U8(1)]);
+        assert_eq!(outcome.truncations.len(), 1);
+        stack.eval("u64 <<").unwrap();
+        assert_eq!(stack.entries(), [IntType::U64(0x68)]);
+        stack.eval("undo").unwrap();
+        assert_eq!(stack.entries(), [IntType::U8(0x34), IntType::U8(1)]);
+    }
+
+    #[test]
+    fn test_errors() {
+        let mut stack = Stack::new(Bits::U8);
+        assert_eq!(stack.eval("undo").unwrap_err().kind, StackErrorKind::NothingToUndo);
+
+        // a failing line leaves the stack as it was
+        stack.eval("1").unwrap();
+        let err = stack.eval("2 + +").unwrap_err();
+        assert_eq!(err, StackError {
+            kind: StackErrorKind::TooFew { word: "+".into(), needed: 2, found: 1 },
+            span: 4..5,
+        });
+        assert_eq!(values(&stack), [1]);
+        assert_eq!(err.to_string(), "error: + takes 2 entries of the stack, found 1 at offset 4");
+
+        let err = stack.eval("0 /").unwrap_err();
+        assert_eq!(err.kind, StackErrorKind::Eval(EvalError::DivisionByZero));
+        assert_eq!(err.to_string(), "error: division by zero at offset 2");
+        assert_eq!(stack.eval("rot").unwrap_err().kind, StackErrorKind::TooFew {
+            word: "rot".into(), needed: 3, found: 1 });
+        assert_eq!(stack.eval("x").unwrap_err().kind, StackErrorKind::InvalidToken("x".into()));
+        assert_eq!(stack.eval("(+").unwrap_err().kind, StackErrorKind::InvalidToken("(+".into()));
+        assert_eq!(stack.eval("0x1ffffffffffffffff").unwrap_err().kind,
+            StackErrorKind::InvalidNumber("0x1ffffffffffffffff".into()));
+        assert_eq!(values(&stack), [1]);
+    }
+
+    #[test]
+    fn test_same_as_prefix() {
+        let exprs = [
+            ("0xfe 2 +", "+ 0xfe 2"),
+            ("1 9 <<", "<< 1 9"),
+            ("10 3 - 0x80 4 >> *", "* - 10 3 >> 0x80 4"),
+            ("0x1234 e ~", "~ e 0x1234"),
+        ];
+        for bits in [Bits::U8, Bits::U16, Bits::U32, Bits::U64] {
+            for (rpn, prefix) in exprs {
+                let mut stack = Stack::new(bits);
+                let outcome = stack.eval(rpn).unwrap();
+                let evaluation = Calculation::new(bits)
+                    .calculate(&crate::pn::parse(prefix).unwrap())
+                    .unwrap();
+                assert_eq!((stack.entries()[0], outcome.overflow),
+                    (evaluation.value, evaluation.overflow), "{rpn} and {prefix} as {bits}");
+            }
+        }
+    }
+
+    #[test]
+    fn test_display() {
+        let mut stack = Stack::new(Bits::U16);
+        assert_eq!(stack.to_string(), "    (empty u16 stack)");
+        stack.eval("0xdead 42").unwrap();
+        assert_eq!(stack.to_string(), "2: 0xdead  57005\n1: 0x002a  42");
+        assert_eq!(stack.render(true), "2: 0xdead  -8531\n1: 0x002a  42");
+    }
+
+}
@@ -0,0 +1,164 @@
+/// serde support, enabled with the `serde` feature. numbers are written as
+/// hex strings with their width, e.g. `"0xcafeu16"`, so 64-bit values
+/// survive formats like JSON that store numbers as doubles.
+/// 0xca7
+use serde::{
+    de::Error,
+    Deserialize,
+    Deserializer,
+    Serialize,
+    Serializer,
+};
+
+use alloc::{
+    format,
+    string::String,
+};
+
+use crate::{
+    IntType,
+    Operation,
+    Width,
+};
+
+/// the literal of `value`, e.g. `"0x00ffu16"`
+impl Serialize for IntType {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.to_literal())
+    }
+}
+
+/// a literal with a width suffix, e.g. `"0xcafeu16"` or `"42u8"`
+impl<'de> Deserialize<'de> for IntType {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        s.parse().map_err(|e| D::Error::custom(format!("{s}: {e}")))
+    }
+}
+
+/// `"u8"`, `"u16"`, `"u32"` or `"u64"`
+impl Serialize for Width {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for Width {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        s.parse().map_err(|e| D::Error::custom(format!("{s}: {e}")))
+    }
+}
+
+/// the symbol of the operation, e.g. `"<<"`
+impl Serialize for Operation {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.symbol())
+    }
+}
+
+impl<'de> Deserialize<'de> for Operation {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        Operation::new_from_str(&s)
+            .map_err(|_| D::Error::custom(format!("unknown operation {s}")))
+    }
+}
+
+fn parse_hex<E: Error>(s: &str) -> Result<u64, E> {
+    s.strip_prefix("0x")
+        .and_then(|hex| u64::from_str_radix(hex, 16).ok())
+        .ok_or_else(|| E::custom(format!("invalid hex number {s}")))
+}
+
+/// a `u64` as a hex string, for `#[serde(with = ...)]`
+pub(crate) mod hex {
+    use super::*;
+
+    pub fn serialize<S: Serializer>(value: &u64, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&format!("{value:#x}"))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<u64, D::Error> {
+        parse_hex(&String::deserialize(deserializer)?)
+    }
+}
+
+/// an optional `u64` as a hex string or null
+pub(crate) mod hex_option {
+    use super::*;
+
+    pub fn serialize<S: Serializer>(value: &Option<u64>, serializer: S)
+        -> Result<S::Ok, S::Error> {
+        match value {
+            Some(value) => hex::serialize(value, serializer),
+            None => serializer.serialize_none(),
+        }
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D)
+        -> Result<Option<u64>, D::Error> {
+        Option::<String>::deserialize(deserializer)?
+            .map(|s| parse_hex(&s))
+            .transpose()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        register::parse_registers,
+        Overflow,
+        Truncation,
+    };
+    use super::*;
+
+    #[test]
+    fn test_int_type() {
+        let value = IntType::U64(u64::MAX - 1);
+        let json = serde_json::to_string(&value).unwrap();
+        assert_eq!(json, "\"0xfffffffffffffffeu64\"");
+        assert_eq!(serde_json::from_str::<IntType>(&json).unwrap(), value);
+        assert_eq!(serde_json::from_str::<IntType>("\"42u8\"").unwrap(), IntType::U8(42));
+        assert!(serde_json::from_str::<IntType>("\"0x1ffu8\"").is_err());
+        assert!(serde_json::from_str::<IntType>("42").is_err());
+
+        assert_eq!(serde_json::to_string(&Width::U16).unwrap(), "\"u16\"");
+        assert_eq!(serde_json::from_str::<Width>("\"u64\"").unwrap(), Width::U64);
+    }
+
+    #[test]
+    fn test_operation_overflow() {
+        assert_eq!(serde_json::to_string(&Operation::Rol).unwrap(), "\"<<<\"");
+        assert!(matches!(serde_json::from_str::<Operation>("\"^\"").unwrap(), Operation::Xor));
+        assert!(serde_json::from_str::<Operation>("\"%\"").is_err());
+
+        assert_eq!(serde_json::to_string(&Overflow::Occured).unwrap(), "\"occurred\"");
+        assert_eq!(serde_json::from_str::<Overflow>("\"no_overflow\"").unwrap(),
+            Overflow::NoOverflow);
+    }
+
+    #[test]
+    fn test_truncation() {
+        let truncation = Truncation {
+            literal: String::from("0x1ff"),
+            width: Width::U8,
+            value: 0xff,
+        };
+        let json = serde_json::to_string(&truncation).unwrap();
+        assert_eq!(json, "{\"literal\":\"0x1ff\",\"width\":\"u8\",\"value\":\"0xff\"}");
+        assert_eq!(serde_json::from_str::<Truncation>(&json).unwrap(), truncation);
+    }
+
+    #[test]
+    fn test_register() {
+        let registers = parse_registers("register SR u32 0xffff000040011000\n\
+            field RXNE 5  receive buffer not empty\nfield ERR 3:0\n").unwrap();
+        let json = serde_json::to_string(&registers).unwrap();
+        assert!(json.starts_with("[{\"name\":\"SR\",\"width\":\"u32\",\
+            \"address\":\"0xffff000040011000\",\"fields\":[{\"name\":\"RXNE\",\"msb\":5,"));
+        assert_eq!(serde_json::from_str::<Vec<crate::register::Register>>(&json).unwrap(),
+            registers);
+    }
+
+}
@@ -0,0 +1,118 @@
+/// the steps of an evaluation, for explaining how a result came about
+/// 0xca7
+use alloc::{
+    format,
+    string::String,
+    vec::Vec,
+};
+use core::fmt;
+
+use crate::{
+    IntType,
+    Overflow,
+    Truncation,
+};
+
+/// a value of an expression and how it was calculated: a number or
+/// variable has no operands, an operation has the steps of its operands
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Step {
+    /// the number, variable, operation or width as written, e.g. `0xffu8`,
+    /// `EN`, `+` or `u16`
+    pub label: String,
+    pub value: IntType,
+    /// the overflow of this operation alone, `NotPossible` for a value
+    pub overflow: Overflow,
+    /// set if the value did not fit its width and was truncated
+    pub truncation: Option<Truncation>,
+    pub operands: Vec<Step>,
+}
+
+impl Step {
+
+    /// a number or variable
+    pub fn value(label: &str, value: IntType) -> Self {
+        Step {
+            label: String::from(label),
+            value,
+            overflow: Overflow::NotPossible,
+            truncation: None,
+            operands: Vec::new(),
+        }
+    }
+
+    /// the events of this step, e.g. `overflow (carry out)`
+    fn events(&self) -> Vec<String> {
+        let mut events = Vec::new();
+        if self.overflow == Overflow::Occured {
+            events.push(String::from(match self.label.as_str() {
+                "+" => "overflow (carry out)",
+                "-" => "overflow (borrow)",
+                _ => "overflow",
+            }));
+        }
+        if let Some(truncation) = &self.truncation {
+            events.push(format!("truncated from {}", truncation.literal));
+        }
+        events
+    }
+
+    fn fmt_depth(&self, f: &mut fmt::Formatter<'_>, depth: usize) -> fmt::Result {
+        let value = self.value.as_u64();
+        let bits = self.value.width().bits();
+        write!(f, "{:indent$}{} = {value:#0hex$x} {value:#0bin$b} ({}", "", self.label,
+            self.value.width(), indent = depth * 2, hex = bits / 4 + 2, bin = bits + 2)?;
+        for event in self.events() {
+            write!(f, ", {event}")?;
+        }
+        write!(f, ")")?;
+        for operand in &self.operands {
+            writeln!(f)?;
+            operand.fmt_depth(f, depth + 1)?;
+        }
+        Ok(())
+    }
+
+}
+
+impl fmt::Display for Step {
+
+    /// one line per step, operands indented below their operation
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.fmt_depth(f, 0)
+    }
+
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Width;
+
+    #[test]
+    fn test_display() {
+        let step = Step {
+            label: String::from("+"),
+            value: IntType::U8(0),
+            overflow: Overflow::Occured,
+            truncation: None,
+            operands: vec![
+                Step::value("0xff", IntType::U8(0xff)),
+                Step {
+                    truncation: Some(Truncation {
+                        literal: String::from("0x101"),
+                        width: Width::U8,
+                        value: 1,
+                    }),
+                    ..Step::value("0x101", IntType::U8(1))
+                },
+            ],
+        };
+        assert_eq!(step.to_string(), "\
++ = 0x00 0b00000000 (u8, overflow (carry out))
+  0xff = 0xff 0b11111111 (u8)
+  0x101 = 0x01 0b00000001 (u8, truncated from 0x101)");
+    }
+
+}
@@ -0,0 +1,195 @@
+/// persistent history of both repls: the rustyline history of the input
+/// and a log of the results, stored under the XDG data directory
+/// 0xca7
+use std::env;
+use std::fs::{self, File};
+use std::io::{self, BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+
+/// number of entries kept if nothing else is configured
+pub const DEFAULT_SIZE: usize = 1000;
+
+/// environment variable overriding the history size
+pub const SIZE_VAR: &str = "BITWISTER_HISTORY_SIZE";
+
+/// `$XDG_DATA_HOME/bitwister`, or `~/.local/share/bitwister` if unset
+pub fn data_dir() -> Option<PathBuf> {
+    let base = match env::var_os("XDG_DATA_HOME") {
+        Some(dir) if !dir.is_empty() => PathBuf::from(dir),
+        _ => PathBuf::from(env::var_os("HOME")?).join(".local/share"),
+    };
+    Some(base.join("bitwister"))
+}
+
+/// where and how much history a tool keeps
+pub struct HistoryConfig {
+    /// the rustyline history of the input
+    pub input: PathBuf,
+    /// the results of the input, see `ResultLog`
+    pub results: PathBuf,
+    /// maximum number of entries of both files
+    pub size: usize,
+}
+
+impl HistoryConfig {
+
+    /// the history files of `tool` in the data directory, the size is taken
+    /// from `BITWISTER_HISTORY_SIZE` if set
+    pub fn for_tool(tool: &str) -> Option<HistoryConfig> {
+        let dir = data_dir()?;
+        let size = env::var(SIZE_VAR).ok()
+            .and_then(|size| size.parse::<usize>().ok())
+            .unwrap_or(DEFAULT_SIZE);
+        Some(HistoryConfig {
+            input: dir.join(format!("{tool}.history")),
+            results: dir.join(format!("{tool}.results")),
+            size,
+        })
+    }
+
+    /// create the directory of the history files
+    pub fn create_dir(&self) -> io::Result<()> {
+        match self.input.parent() {
+            Some(dir) => fs::create_dir_all(dir),
+            None => Ok(()),
+        }
+    }
+}
+
+/// an evaluated expression and its result
+#[derive(Debug, Clone, PartialEq)]
+pub struct ResultEntry {
+    pub expr: String,
+    pub result: String,
+}
+
+/// the results of past sessions. the file holds one entry per line, the
+/// expression and the result separated by a tab, oldest first. entries are
+/// numbered from 1 and can be recalled with `$N`.
+#[derive(Clone)]
+pub struct ResultLog {
+    entries: Vec<ResultEntry>,
+    size: usize,
+}
+
+impl Default for ResultLog {
+    fn default() -> Self {
+        Self::new(DEFAULT_SIZE)
+    }
+}
+
+impl ResultLog {
+
+    pub fn new(size: usize) -> Self {
+        ResultLog {
+            entries: vec![],
+            size,
+        }
+    }
+
+    /// read a result log, a missing file is an empty log
+    pub fn load(path: &Path, size: usize) -> io::Result<ResultLog> {
+        let mut log = ResultLog::new(size);
+        let file = match File::open(path) {
+            Ok(file) => file,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(log),
+            Err(e) => return Err(e),
+        };
+        for line in BufReader::new(file).lines() {
+            let line = line?;
+            if let Some((expr, result)) = line.split_once('\t') {
+                log.push(expr, result);
+            }
+        }
+        Ok(log)
+    }
+
+    /// write the newest `size` entries to `path`
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        let mut file = File::create(path)?;
+        for entry in &self.entries {
+            writeln!(file, "{}\t{}", entry.expr, entry.result)?;
+        }
+        Ok(())
+    }
+
+    /// add a result, the oldest entry is dropped if the log is full
+    pub fn push(&mut self, expr: &str, result: &str) {
+        if self.size == 0 {
+            return;
+        }
+        if self.entries.len() == self.size {
+            self.entries.remove(0);
+        }
+        // tabs and newlines would break the file format
+        let clean = |s: &str| s.replace(['\t', '\n'], " ");
+        self.entries.push(ResultEntry {
+            expr: clean(expr),
+            result: clean(result),
+        });
+    }
+
+    pub fn entries(&self) -> &[ResultEntry] {
+        &self.entries
+    }
+
+    /// the entry recalled by `$n`
+    pub fn get(&self, n: usize) -> Option<&ResultEntry> {
+        self.entries.get(n.checked_sub(1)?)
+    }
+
+    /// replace every `$N` token of `line` by the result of entry N, tokens
+    /// of unknown entries are kept as they are
+    pub fn substitute(&self, line: &str) -> String {
+        let tokens: Vec<&str> = line
+            .split(' ')
+            .map(|token| {
+                token.strip_prefix('$')
+                    .and_then(|n| n.parse::<usize>().ok())
+                    .and_then(|n| self.get(n))
+                    .map_or(token, |entry| entry.result.as_str())
+            })
+            .collect();
+        tokens.join(" ")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_result_log() {
+        let mut log = ResultLog::new(2);
+        log.push("1u8 + 1u8", "0x02u8");
+        log.push("x = 2", "0x00000002u32");
+        log.push("! 0u8", "0xffu8");
+
+        assert_eq!(log.entries().len(), 2);
+        assert_eq!(log.get(1).unwrap().expr, "x = 2");
+        assert!(log.get(0).is_none());
+        assert!(log.get(3).is_none());
+
+        assert_eq!(log.substitute("$2 & $1"), "0xffu8 & 0x00000002u32");
+        assert_eq!(log.substitute("$9 + 1"), "$9 + 1");
+    }
+
+    #[test]
+    fn test_result_log_file() {
+        let path = env::temp_dir()
+            .join(format!("btrepl-test-{}.results", std::process::id()));
+        let mut log = ResultLog::new(10);
+        log.push("+ 1\t2", "0x3");
+        log.save(&path).unwrap();
+
+        let loaded = ResultLog::load(&path, 10).unwrap();
+        assert_eq!(loaded.entries(), &[ResultEntry {
+            expr: String::from("+ 1 2"),
+            result: String::from("0x3"),
+        }]);
+        fs::remove_file(&path).unwrap();
+
+        assert!(ResultLog::load(&path, 10).unwrap().entries().is_empty());
+    }
+
+}
@@ -0,0 +1,396 @@
+/// rustyline helper shared by the bitwister and pn_bitwister repls:
+/// tab completion, inline hints, syntax highlighting and persistent history
+/// 0xca7
+use std::borrow::Cow;
+use std::io;
+
+use rustyline::{
+    completion::{Completer, Pair},
+    error::ReadlineError,
+    highlight::Highlighter,
+    hint::Hinter,
+    history::DefaultHistory,
+    validate::Validator,
+    Config,
+    Context,
+    Editor,
+    Helper,
+    Result,
+};
+
+pub mod history;
+use history::HistoryConfig;
+
+const RESET: &str = "\x1b[0m";
+const BOLD: &str = "\x1b[1m";
+const DIM: &str = "\x1b[2m";
+const RED: &str = "\x1b[31m";
+const GREEN: &str = "\x1b[32m";
+const YELLOW: &str = "\x1b[33m";
+const BLUE: &str = "\x1b[34m";
+const MAGENTA: &str = "\x1b[35m";
+const CYAN: &str = "\x1b[36m";
+
+/// what a word of the input is, decides how it is completed and coloured
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum WordKind {
+    /// an operation like `+` or `<<`
+    Operator,
+    /// a builtin or user function
+    Function,
+    /// a `:` meta-command, only completed at the start of a line
+    Command,
+    /// a keyword like the `u8` mode of pn_bitwister
+    Keyword,
+    /// a register or field name from a loaded definition
+    Register,
+    /// a variable of the session
+    Variable,
+    /// a number literal like `0xdeadu16`
+    Literal,
+    /// anything that isn't known, shown as an error
+    Unknown,
+}
+
+impl WordKind {
+    fn color(&self) -> &'static str {
+        match self {
+            WordKind::Operator => MAGENTA,
+            WordKind::Function => BLUE,
+            WordKind::Command => BOLD,
+            WordKind::Keyword => CYAN,
+            WordKind::Register => BLUE,
+            WordKind::Variable => GREEN,
+            WordKind::Literal => YELLOW,
+            WordKind::Unknown => RED,
+        }
+    }
+}
+
+/// compute a hint for the current line, e.g. its live result
+type HintFn = Box<dyn Fn(&str) -> Option<String>>;
+
+/// the rustyline helper of both repls. the front-ends register their
+/// words once and update variables and the hint function as the session
+/// changes.
+pub struct BtHelper {
+    words: Vec<(String, WordKind)>,
+    variables: Vec<String>,
+    hinter: Option<HintFn>,
+    color: bool,
+}
+
+impl Default for BtHelper {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl BtHelper {
+
+    pub fn new() -> Self {
+        BtHelper {
+            words: vec![],
+            variables: vec![],
+            hinter: None,
+            color: true,
+        }
+    }
+
+    /// register `words` of the given `kind` for completion and highlighting
+    pub fn with_words(mut self, kind: WordKind, words: &[&str]) -> Self {
+        self.add_words(kind, words.iter().map(|w| w.to_string()));
+        self
+    }
+
+    /// register more words, e.g. register names of a loaded definition
+    pub fn add_words<I: IntoIterator<Item = String>>(&mut self, kind: WordKind,
+        words: I) {
+        for word in words {
+            if !self.words.iter().any(|(w, k)| *w == word && *k == kind) {
+                self.words.push((word, kind));
+            }
+        }
+    }
+
+    /// replace the known variable names
+    pub fn set_variables<I: IntoIterator<Item = String>>(&mut self, vars: I) {
+        self.variables = vars.into_iter().collect();
+    }
+
+    /// set the function computing the hint of a complete line
+    pub fn set_hinter<F: Fn(&str) -> Option<String> + 'static>(&mut self, f: F) {
+        self.hinter = Some(Box::new(f));
+    }
+
+    /// turn syntax highlighting on or off
+    pub fn set_color(&mut self, color: bool) {
+        self.color = color;
+    }
+
+    /// classify a single word of the input
+    pub fn classify(&self, word: &str) -> WordKind {
+        if let Some((_, kind)) = self.words.iter().find(|(w, _)| w == word) {
+            return *kind;
+        }
+        if self.variables.iter().any(|v| v == word) {
+            return WordKind::Variable;
+        }
+        // `$N` recalls a result from the history
+        if let Some(n) = word.strip_prefix('$') {
+            if !n.is_empty() && n.chars().all(|c| c.is_ascii_digit()) {
+                return WordKind::Variable;
+            }
+        }
+        if is_literal(word) {
+            return WordKind::Literal;
+        }
+        WordKind::Unknown
+    }
+
+    /// all completions of `prefix`, commands only at the start of a line
+    fn candidates(&self, prefix: &str, line_start: bool) -> Vec<Pair> {
+        let words = self.words.iter()
+            .filter(|(_, kind)| line_start || *kind != WordKind::Command)
+            .map(|(w, _)| w)
+            .chain(self.variables.iter());
+
+        let mut candidates: Vec<Pair> = words
+            .filter(|w| w.starts_with(prefix))
+            .map(|w| Pair {
+                display: w.clone(),
+                replacement: w.clone(),
+            })
+            .collect();
+        candidates.sort_by(|a, b| a.display.cmp(&b.display));
+        candidates.dedup_by(|a, b| a.display == b.display);
+        candidates
+    }
+}
+
+/// create the editor of a repl. if `history` is given, the input history
+/// is limited to its size and loaded, see `save_history`.
+pub fn editor(helper: BtHelper, history: Option<&HistoryConfig>)
+    -> Result<Editor<BtHelper, DefaultHistory>> {
+    let mut config = Config::builder();
+    if let Some(history) = history {
+        config = config.max_history_size(history.size)?;
+    }
+    let mut rl = Editor::with_config(config.build())?;
+    rl.set_helper(Some(helper));
+    if let Some(history) = history {
+        match rl.load_history(&history.input) {
+            Ok(()) => (),
+            // a missing file just means there is no history yet
+            Err(ReadlineError::Io(e)) if e.kind() == io::ErrorKind::NotFound => (),
+            Err(e) => eprintln!("[bt]> error loading history: {e}"),
+        }
+    }
+    Ok(rl)
+}
+
+/// write the input history of `rl`, creating the data directory
+pub fn save_history(rl: &mut Editor<BtHelper, DefaultHistory>,
+    history: &HistoryConfig) -> Result<()> {
+    history.create_dir()?;
+    rl.save_history(&history.input)
+}
+
+/// a number as both repls accept it: decimal or 0x-hex, with an optional
+/// `u8`..`u64` suffix
+pub fn is_literal(word: &str) -> bool {
+    let (digits, suffix) = match word.split_once('u') {
+        Some((digits, suffix)) => (digits, Some(suffix)),
+        None => (word, None),
+    };
+    let digits_ok = match digits.strip_prefix("0x") {
+        Some(hex) => !hex.is_empty() && hex.chars().all(|c| c.is_ascii_hexdigit()),
+        None => !digits.is_empty() && digits.chars().all(|c| c.is_ascii_digit()),
+    };
+    let suffix_ok = match suffix {
+        Some(bits) => matches!(bits, "8" | "16" | "32" | "64"),
+        None => true,
+    };
+    digits_ok && suffix_ok
+}
+
+/// characters that end a word, besides whitespace
+fn is_delimiter(c: char) -> bool {
+    c.is_whitespace() || matches!(c, '(' | ')' | ',')
+}
+
+/// start of the word ending at `pos`
+fn word_start(line: &str, pos: usize) -> usize {
+    line[..pos]
+        .rfind(is_delimiter)
+        .map_or(0, |idx| idx + 1)
+}
+
+impl Completer for BtHelper {
+    type Candidate = Pair;
+
+    fn complete(&self, line: &str, pos: usize, _ctx: &Context<'_>)
+        -> Result<(usize, Vec<Pair>)> {
+        let start = word_start(line, pos);
+        let line_start = line[..start].trim().is_empty();
+        Ok((start, self.candidates(&line[start..pos], line_start)))
+    }
+}
+
+impl Hinter for BtHelper {
+    type Hint = String;
+
+    fn hint(&self, line: &str, pos: usize, _ctx: &Context<'_>) -> Option<String> {
+        if pos < line.len() || line.trim().is_empty() {
+            return None;
+        }
+
+        // complete a partially typed word if there is only one candidate
+        let start = word_start(line, pos);
+        let prefix = &line[start..pos];
+        if !prefix.is_empty() && self.classify(prefix) == WordKind::Unknown {
+            let candidates = self.candidates(prefix, line[..start].trim().is_empty());
+            if let [candidate] = candidates.as_slice() {
+                return Some(candidate.replacement[prefix.len()..].to_string());
+            }
+        }
+
+        // the live result of the line
+        let hinter = self.hinter.as_ref()?;
+        hinter(line).map(|result| format!("  ⇒ {result}"))
+    }
+}
+
+impl Highlighter for BtHelper {
+
+    fn highlight<'l>(&self, line: &'l str, _pos: usize) -> Cow<'l, str> {
+        if !self.color {
+            return Cow::Borrowed(line);
+        }
+
+        let mut out = String::with_capacity(line.len() * 2);
+        let mut word = String::new();
+        let flush = |word: &mut String, out: &mut String| {
+            if !word.is_empty() {
+                let color = self.classify(word).color();
+                out.push_str(&format!("{color}{word}{RESET}"));
+                word.clear();
+            }
+        };
+
+        for c in line.chars() {
+            if is_delimiter(c) {
+                flush(&mut word, &mut out);
+                out.push(c);
+            } else {
+                word.push(c);
+            }
+        }
+        flush(&mut word, &mut out);
+
+        Cow::Owned(out)
+    }
+
+    fn highlight_hint<'h>(&self, hint: &'h str) -> Cow<'h, str> {
+        if self.color {
+            Cow::Owned(format!("{DIM}{hint}{RESET}"))
+        } else {
+            Cow::Borrowed(hint)
+        }
+    }
+
+    fn highlight_char(&self, _line: &str, _pos: usize) -> bool {
+        self.color
+    }
+}
+
+impl Validator for BtHelper {}
+
+impl Helper for BtHelper {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rustyline::history::MemHistory;
+
+    fn helper() -> BtHelper {
+        let mut helper = BtHelper::new()
+            .with_words(WordKind::Operator, &["+", "<<", "<<<", "r"])
+            .with_words(WordKind::Command, &[":width", ":vars", ":quit"]);
+        helper.set_variables(vec![String::from("reg_a")]);
+        helper
+    }
+
+    #[test]
+    fn test_is_literal() {
+        assert!(is_literal("0xdeadu16"));
+        assert!(is_literal("0xdead"));
+        assert!(is_literal("1234u8"));
+        assert!(is_literal("1234"));
+        assert!(!is_literal("0x"));
+        assert!(!is_literal("0xdeadu7"));
+        assert!(!is_literal("u8"));
+        assert!(!is_literal("12a"));
+    }
+
+    #[test]
+    fn test_classify() {
+        let helper = helper();
+        assert_eq!(helper.classify("<<"), WordKind::Operator);
+        assert_eq!(helper.classify(":vars"), WordKind::Command);
+        assert_eq!(helper.classify("reg_a"), WordKind::Variable);
+        assert_eq!(helper.classify("$12"), WordKind::Variable);
+        assert_eq!(helper.classify("$"), WordKind::Unknown);
+        assert_eq!(helper.classify("0xffu8"), WordKind::Literal);
+        assert_eq!(helper.classify("foo"), WordKind::Unknown);
+    }
+
+    #[test]
+    fn test_complete() {
+        let helper = helper();
+        let history = MemHistory::new();
+        let ctx = Context::new(&history);
+
+        let (start, candidates) = helper.complete(":w", 2, &ctx).unwrap();
+        assert_eq!(start, 0);
+        assert_eq!(candidates.len(), 1);
+        assert_eq!(candidates[0].replacement, ":width");
+
+        // commands are only completed at the start of a line
+        let (start, candidates) = helper.complete("1u8 :", 5, &ctx).unwrap();
+        assert_eq!(start, 4);
+        assert!(candidates.is_empty());
+
+        let (_, candidates) = helper.complete("1u8 <<", 6, &ctx).unwrap();
+        assert_eq!(candidates.len(), 2);
+
+        let (start, candidates) = helper.complete("1u8 + re", 8, &ctx).unwrap();
+        assert_eq!(start, 6);
+        assert_eq!(candidates[0].replacement, "reg_a");
+    }
+
+    #[test]
+    fn test_hint() {
+        let mut helper = helper();
+        helper.set_hinter(|line| if line == "1 + 1" { Some(String::from("2")) } else { None });
+        let history = MemHistory::new();
+        let ctx = Context::new(&history);
+
+        assert_eq!(helper.hint("1 + 1", 5, &ctx), Some(String::from("  ⇒ 2")));
+        assert_eq!(helper.hint("1 + 1", 2, &ctx), None);
+        assert_eq!(helper.hint(":qu", 3, &ctx), Some(String::from("it")));
+    }
+
+    #[test]
+    fn test_highlight() {
+        let mut helper = helper();
+        let line = "0xffu8 + foo";
+        let highlighted = helper.highlight(line, 0);
+        assert_eq!(highlighted, format!("{YELLOW}0xffu8{RESET} {MAGENTA}+{RESET} \
+            {RED}foo{RESET}"));
+
+        helper.set_color(false);
+        assert_eq!(helper.highlight(line, 0), line);
+    }
+
+}
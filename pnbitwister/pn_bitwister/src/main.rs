@@ -1,42 +1,323 @@
+use std::io::{self, BufRead, IsTerminal};
+use std::ops::Range;
+use std::process::exit;
+
+use clap::Parser;
+
 use rustyline::{
-    DefaultEditor, 
+    Editor,
     Result,
     error::ReadlineError,
+    history::DefaultHistory,
+};
+
+use btrepl::{
+    editor,
+    history::{HistoryConfig, ResultLog},
+    save_history,
+    BtHelper,
+    WordKind,
 };
 
 use pncalc::{
-    Tokenizer,
+    convert::{ConvertError, Expression, Notation},
+    parse,
+    rpn::{self, Stack},
     Calculation,
-    CalculationResult, 
-    Bits
+    Evaluation,
+    Bits,
+    Operation,
+    Overflow,
+    Step,
 };
 
-fn eval(line: &str) -> Option<CalculationResult> {
-
-    let expr: Vec<&str> = line
-        .split(' ')
-        .collect();
-
-    let (mut calc, expr) = match expr[0] {
-        "u8" => (Calculation::new(Bits::U8), line.strip_prefix("u8 ")
-            .unwrap()),
-        "u16" => (Calculation::new(Bits::U16), line.strip_prefix("u16 ")
-            .unwrap()),
-        "u32" => (Calculation::new(Bits::U32), line.strip_prefix("u32 ")
-            .unwrap()),
-        "u64" => (Calculation::new(Bits::U64), line.strip_prefix("u64 ")
-            .unwrap()),
-        _ => (Calculation::new(Bits::U32), line)
-    };
+/// everything evaluated fine
+const EXIT_SUCCESS: i32 = 0;
+/// at least one line failed to evaluate
+const EXIT_EVAL_ERROR: i32 = 1;
+/// invalid arguments or unreadable input, same code clap uses
+const EXIT_USAGE_ERROR: i32 = 2;
+
+#[derive(Parser)]
+#[command(name = "pn_bitwister", version,
+    about = "the bit calculator in prefix notation",
+    after_help = "without an expression and with stdin not a terminal, every line of \
+        stdin is evaluated")]
+struct Cli {
+
+    /// default width: u8, u16, u32 (default) or u64
+    #[arg(short, long)]
+    width: Option<Bits>,
+
+    /// show decimals as two's complement signed values
+    #[arg(short, long)]
+    signed: bool,
+
+    /// print only the value in hex, e.g. for $(...)
+    #[arg(short, long)]
+    quiet: bool,
+
+    /// show every step of the evaluation as a tree, prefix notation only
+    #[arg(short = 'x', long, conflicts_with = "rpn")]
+    explain: bool,
+
+    /// evaluate the words on an RPN stack instead, e.g. `10 3 -`
+    #[arg(long)]
+    rpn: bool,
+
+    /// the expression to evaluate, e.g. `u8 + 0xde 0xad`, options may
+    /// follow it
+    expr: Vec<String>,
+}
+
+/// the defaults of a session, changed with `:mode` and `:signed`
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Mode {
+    bits: Bits,
+    /// show decimals as two's complement signed values
+    signed: bool,
+}
+
+impl Mode {
+
+    /// set the mode from a type name like `u16` or `i16`, a bare width
+    /// like `16` keeps the signedness
+    fn set(&mut self, name: &str) -> std::result::Result<(), String> {
+        let (signed, width) = match name.split_at_checked(1) {
+            Some(("i", width)) => (Some(true), width),
+            Some(("u", width)) => (Some(false), width),
+            _ => (None, name),
+        };
+        self.bits = width.parse::<Bits>()
+            .map_err(|_| format!("error: invalid mode {name}, expected e.g. u16 or i16"))?;
+        self.signed = signed.unwrap_or(self.signed);
+        Ok(())
+    }
+
+    /// the mode as a type name, e.g. `u16` or `i16` if signed
+    fn name(&self) -> String {
+        format!("{}{}", if self.signed { 'i' } else { 'u' }, self.bits.bits())
+    }
+
+}
+
+/// an expression that could not be evaluated, with the byte range of the
+/// line to blame
+struct Failure {
+    message: String,
+    span: Range<usize>,
+}
+
+impl Failure {
+
+    /// the message with a caret under the blamed part of `line`, a span
+    /// past the end of `line` is put at its end
+    fn show(&self, line: &str) -> String {
+        let clamp = |idx: usize| (0..=idx.min(line.len()))
+            .rev()
+            .find(|idx| line.is_char_boundary(*idx))
+            .unwrap_or(0);
+        let start = clamp(self.span.start);
+        let end = clamp(self.span.end).max(start);
+        // keep tabs so the caret lines up with the line above
+        let pad: String = line[..start].chars()
+            .map(|c| if c == '\t' { '\t' } else { ' ' })
+            .collect();
+        let len = line[start..end].chars().count().max(1);
+        format!("{}\n  {line}\n  {pad}{}", self.message, "^".repeat(len))
+    }
 
-    let tok = Tokenizer::new();
-    let tokens = tok.tokenize(expr);
+}
+
+/// `line` written in every notation, one per line
+fn convert(line: &str, from: Notation) -> std::result::Result<String, Failure> {
+    let expr = Expression::parse(line, from).map_err(|err| match err {
+        ConvertError::Parse(msg, span) => Failure { message: format!("error: {msg}"), span },
+        err => Failure { message: err.to_string(), span: 0..line.len() },
+    })?;
+    Ok(Notation::ALL.iter()
+        .map(|notation| format!("{:<8} {}", format!("{notation}:"), expr.render(*notation)))
+        .collect::<Vec<_>>()
+        .join("\n"))
+}
+
+/// evaluate a line, a leading `u8`, `u16`, `u32` or `u64` or a form like
+/// `(u16 ...)` overrides the width `bits` of the session
+fn eval(line: &str, bits: Bits) -> std::result::Result<(Evaluation, Step), Failure> {
+
+    let expr = parse(line).map_err(|err| Failure {
+        message: format!("error: {}", err.kind),
+        span: err.span,
+    })?;
+    Calculation::new(bits).explain(&expr).map_err(|err| Failure {
+        message: err.to_string(),
+        span: 0..line.len(),
+    })
+
+}
+
+/// the steps of evaluating `expr` as shown by `:explain`, `$N` recalls a
+/// result of `results`
+fn explain(expr: &str, results: &ResultLog, bits: Bits) -> String {
+    // the span of a failure is in the line with the results filled in
+    let expr = results.substitute(expr);
+    match eval(&expr, bits) {
+        Ok((_, step)) => format!("{step}\n"),
+        Err(failure) => format!("✗ {}", failure.show(&expr)),
+    }
+}
+
+/// `expr` in every notation as shown by `:convert`, `$N` recalls a result
+/// of `results`
+fn convert_recalled(expr: &str, results: &ResultLog, from: Notation) -> String {
+    let expr = results.substitute(expr);
+    match convert(&expr, from) {
+        Ok(lines) => format!("{lines}\n"),
+        Err(failure) => format!("✗ {}", failure.show(&expr)),
+    }
+}
+
+/// every view of the result: hex, decimal, binary, ascii and the register
+fn show(evaluation: &Evaluation, signed: bool) -> String {
+    let value = evaluation.value;
+    let bits = value.width().bits();
+    let mut s = format!("\n[evaluation] ({bits} bit calculation)\n");
+    if evaluation.overflow == Overflow::Occured {
+        s.push_str("    [overflow occured]\n");
+    }
+    s.push_str(&format!("[hex] {:0w$x}\n", value.as_u64(), w = bits / 4));
+    if signed {
+        s.push_str(&format!("[dec] {}\n", value.as_i64()));
+    } else {
+        s.push_str(&format!("[dec] {}\n", value.as_u64()));
+    }
+    s.push_str(&format!("[bin] {:0bits$b}\n", value.as_u64()));
+    s.push_str(&format!("[ascii] {}\n", value.to_ascii()));
+    s.push_str(&format!("[reg]\n{}\n\n", value.register_view()));
+    s
+}
+
+/// evaluate a line of RPN mode on `stack`, print what happened and the
+/// stack. returns the new top of the stack.
+fn eval_rpn(stack: &mut Stack, line: &str, signed: bool) -> Option<String> {
+    match stack.eval(line) {
+        Ok(outcome) => {
+            for truncation in &outcome.truncations {
+                eprintln!("[note]> {truncation}");
+            }
+            if outcome.overflow == Overflow::Occured {
+                println!("    [overflow occured]");
+            }
+            for value in &outcome.register_views {
+                println!("[reg]\n{}", value.register_view());
+            }
+        },
+        Err(err) => {
+            let failure = Failure { message: format!("error: {}", err.kind), span: err.span };
+            println!("✗ {}", failure.show(line));
+        },
+    }
+    println!("{}\n", stack.render(signed));
+    stack.entries().last().map(|top| format!("0x{:x}", top.as_u64()))
+}
+
+/// evaluate a line outside of the repl, on `stack` if given. the result
+/// goes to stdout, only the value in hex if `quiet`, after the steps that
+/// led to it if `explain` is set. errors go to stderr
+/// after `context`. returns if the line evaluated.
+fn run_line(line: &str, stack: Option<&mut Stack>, mode: Mode, quiet: bool,
+    explain: bool, context: &str) -> bool {
+    let result = match stack {
+        Some(stack) => stack.eval(line)
+            .map(|outcome| {
+                for truncation in &outcome.truncations {
+                    eprintln!("[note]> {truncation}");
+                }
+                match stack.entries().last() {
+                    _ if !quiet => {
+                        let overflow = if outcome.overflow == Overflow::Occured {
+                            "    [overflow occured]\n"
+                        } else {
+                            ""
+                        };
+                        format!("{overflow}{}\n", stack.render(mode.signed))
+                    },
+                    Some(top) => format!("0x{:x}\n", top.as_u64()),
+                    None => String::new(),
+                }
+            })
+            .map_err(|err| Failure { message: format!("error: {}", err.kind), span: err.span }),
+        None => eval(line, mode.bits).map(|(evaluation, step)| {
+            for truncation in &evaluation.truncations {
+                eprintln!("[note]> {truncation}");
+            }
+            let steps = if explain { format!("{step}\n") } else { String::new() };
+            if quiet {
+                format!("{steps}0x{:x}\n", evaluation.value.as_u64())
+            } else {
+                format!("{steps}{}", show(&evaluation, mode.signed).trim_start())
+            }
+        }),
+    };
+    match result {
+        Ok(output) => {
+            print!("{output}");
+            true
+        },
+        Err(failure) => {
+            eprintln!("pn_bitwister: {context}{}", failure.show(line));
+            false
+        },
+    }
+}
 
-    match tokens {
-        Some(mut toks) => calc.calculate(&mut toks),
-        None => None,
+/// evaluate every line of `input`, blank lines are skipped. with `rpn` all
+/// lines work on one stack. returns the exit code.
+fn run_batch<R: BufRead>(input: R, rpn: bool, mode: Mode, quiet: bool, explain: bool) -> i32 {
+    let mut stack = rpn.then(|| Stack::new(mode.bits));
+    let mut code = EXIT_SUCCESS;
+    for (idx, line) in input.lines().enumerate() {
+        let line = match line {
+            Ok(line) => line,
+            Err(e) => {
+                eprintln!("pn_bitwister: error reading stdin: {e}");
+                return EXIT_USAGE_ERROR;
+            },
+        };
+        if line.trim().is_empty() {
+            continue;
+        }
+        let context = format!("line {}: ", idx + 1);
+        if !run_line(&line, stack.as_mut(), mode, quiet, explain, &context) {
+            code = EXIT_EVAL_ERROR;
+        }
     }
+    code
+}
 
+/// show the live result of the line, `$N` recalls a result of `results`.
+/// in RPN mode this is the top of the stack after the line.
+fn set_hinter(rl: &mut Editor<BtHelper, DefaultHistory>, results: &ResultLog,
+    stack: Option<&Stack>, bits: Bits) {
+    let results = results.clone();
+    let stack = stack.cloned();
+    if let Some(helper) = rl.helper_mut() {
+        helper.set_hinter(move |line| {
+            if let Some(stack) = &stack {
+                let mut stack = stack.clone();
+                stack.eval(&results.substitute(line)).ok()?;
+                return stack.entries().last()
+                    .map(|top| format!("0x{:x}", top.as_u64()));
+            }
+            // a bare mode has no expression to evaluate yet
+            if matches!(line.trim(), "u8" | "u16" | "u32" | "u64") {
+                return None;
+            }
+            eval(&results.substitute(line), bits)
+                .ok()
+                .map(|(evaluation, _)| format!("0x{:x}", evaluation.value.as_u64()))
+        });
+    }
 }
 
 fn logo() {
@@ -55,18 +336,54 @@ fn logo() {
 fn usage() {
     println!("[examples]");
     println!("+ 2 2 == 2 + 2");
-    println!("* 2 + 2 2 == 2*(2+2)\n\n");
+    println!("* 2 + 2 2 == 2*(2+2)");
+    println!("(| (<< 1 4) (<< 1 7) 0x3) == (1<<4) | (1<<7) | 0x3");
+    println!(":rpn switches to a stack like on HP calculators, :pn back");
+    println!(":mode u16 or :mode i16 sets the default width, :signed and :unsigned the decimals");
+    println!(":explain <expr> shows every step of an evaluation");
+    println!(":convert <expr> writes it in infix, prefix and postfix notation\n\n");
 }
 
-fn main() -> Result<()> {
+/// the interactive mode, the logo and chatter are only shown if stdout is
+/// a terminal
+fn repl(rpn: bool, mut mode: Mode) -> Result<()> {
 
-    logo();
-    usage();
+    let is_tty = io::stdout().is_terminal();
+    if is_tty {
+        logo();
+        usage();
+    }
 
-    let mut rl = DefaultEditor::new()?;
+    let helper = BtHelper::new()
+        .with_words(WordKind::Operator, Operation::symbols())
+        .with_words(WordKind::Keyword, &["u8", "u16", "u32", "u64"])
+        .with_words(WordKind::Keyword, &rpn::COMMANDS);
+
+    let history = HistoryConfig::for_tool("pn_bitwister")
+        .filter(|history| history.size > 0);
+    let mut results = match &history {
+        Some(history) => ResultLog::load(&history.results, history.size)
+            .unwrap_or_else(|e| {
+                eprintln!("[bt]> error loading results: {e}");
+                ResultLog::new(history.size)
+            }),
+        None => ResultLog::default(),
+    };
+
+    let mut rl = editor(helper, history.as_ref())?;
+
+    // the stack stays when switching back and forth
+    let mut stack = Stack::new(mode.bits);
+    let mut is_rpn = rpn;
 
     loop {
-        let readline = rl.readline("⮕  ");
+        set_hinter(&mut rl, &results, is_rpn.then_some(&stack), mode.bits);
+        let prompt = if is_rpn {
+            format!("rpn {} ⮕  ", mode.name())
+        } else {
+            format!("{} ⮕  ", mode.name())
+        };
+        let readline = rl.readline(&prompt);
         match readline {
             Ok(line) => {
                 match rl.add_history_entry(line.as_str()) {
@@ -75,10 +392,78 @@ fn main() -> Result<()> {
                         eprintln!("[bt]> error adding to history: {e}");
                     },
                 }
-                println!("⚙ evaluating... { }", line);
-                match eval(&line) {
-                    Some(value) => println!("✓ {}", value),
-                    None => println!("✗ error in expression"),
+                let mut words = line.split_whitespace();
+                match (words.next(), words.next(), words.next()) {
+                    (Some(":rpn"), None, _) => {
+                        is_rpn = true;
+                        println!("{}\n", stack.render(mode.signed));
+                        continue;
+                    },
+                    (Some(":pn"), None, _) => {
+                        is_rpn = false;
+                        continue;
+                    },
+                    (Some(":mode"), None, _) => {
+                        println!("{}", mode.name());
+                        continue;
+                    },
+                    (Some(":mode"), Some(name), None) => {
+                        match mode.set(name) {
+                            // the entries of the stack follow the mode
+                            Ok(()) => {
+                                let _ = stack.eval(&format!("u{}", mode.bits.bits()));
+                            },
+                            Err(e) => println!("✗ {e}"),
+                        }
+                        continue;
+                    },
+                    (Some(":signed"), None, _) => {
+                        mode.signed = true;
+                        continue;
+                    },
+                    (Some(":explain"), _, _) => {
+                        let expr = line.trim().trim_start_matches(":explain").trim();
+                        println!("{}", explain(expr, &results, mode.bits));
+                        continue;
+                    },
+                    (Some(":convert"), _, _) => {
+                        let expr = line.trim().trim_start_matches(":convert").trim();
+                        let from = if is_rpn { Notation::Postfix } else { Notation::Prefix };
+                        println!("{}", convert_recalled(expr, &results, from));
+                        continue;
+                    },
+                    (Some(":unsigned"), None, _) => {
+                        mode.signed = false;
+                        continue;
+                    },
+                    (Some(command), _, _) if command.starts_with(':') => {
+                        println!("✗ error: unknown command {}", line.trim());
+                        continue;
+                    },
+                    _ => (),
+                }
+                if is_rpn {
+                    let top = eval_rpn(&mut stack, &results.substitute(&line), mode.signed);
+                    // a width word on the stack changes the mode as well
+                    mode.bits = stack.bits();
+                    if let Some(top) = top {
+                        results.push(&line, &top);
+                    }
+                    continue;
+                }
+                if is_tty {
+                    println!("⚙ evaluating... { }", line);
+                }
+                let expr = results.substitute(&line);
+                match eval(&expr, mode.bits) {
+                    Ok((evaluation, _)) => {
+                        for truncation in &evaluation.truncations {
+                            eprintln!("[note]> {truncation}");
+                        }
+                        println!("✓ {}", show(&evaluation, mode.signed));
+                        results.push(&line, &format!("0x{:x}", evaluation.value.as_u64()));
+                    },
+                    Err(failure) => println!("✗ {}", failure.show(&expr)),
                 }
             },
             Err(ReadlineError::Interrupted) | Err(ReadlineError::Eof) => {
@@ -91,6 +476,86 @@ fn main() -> Result<()> {
         }
     }
 
-    println!(">> goodbye! see you next time :^)\n");
+    if let Some(history) = &history {
+        if let Err(e) = save_history(&mut rl, history) {
+            eprintln!("[bt]> error saving history: {e}");
+        }
+        if let Err(e) = results.save(&history.results) {
+            eprintln!("[bt]> error saving results: {e}");
+        }
+    }
+
+    if is_tty {
+        println!(">> goodbye! see you next time :^)\n");
+    }
     Ok(())
 }
+
+fn main() {
+
+    let cli = Cli::parse();
+    let mode = Mode {
+        bits: cli.width.unwrap_or(Bits::U32),
+        signed: cli.signed,
+    };
+
+    let code = if !cli.expr.is_empty() {
+        let line = cli.expr.join(" ");
+        let mut stack = cli.rpn.then(|| Stack::new(mode.bits));
+        if run_line(&line, stack.as_mut(), mode, cli.quiet, cli.explain, "") {
+            EXIT_SUCCESS
+        } else {
+            EXIT_EVAL_ERROR
+        }
+    } else if !io::stdin().is_terminal() {
+        // piped input, e.g. cat exprs | pn_bitwister
+        run_batch(io::stdin().lock(), cli.rpn, mode, cli.quiet, cli.explain)
+    } else {
+        match repl(cli.rpn, mode) {
+            Ok(()) => EXIT_SUCCESS,
+            Err(e) => {
+                eprintln!("pn_bitwister, exiting with error: {e}");
+                EXIT_EVAL_ERROR
+            },
+        }
+    };
+
+    exit(code);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_explain_recalled() {
+        let mut results = ResultLog::default();
+        results.push("+ 0xdeadbeef 1", "0xdeadbef0");
+        assert_eq!(explain("+ $1 zz", &results, Bits::U32), "\
+✗ error: invalid token zz
+  + 0xdeadbef0 zz
+               ^^");
+        assert!(explain("+ $1 1", &results, Bits::U32).starts_with("+ = 0xdeadbef1"));
+    }
+
+    #[test]
+    fn test_convert_recalled() {
+        let mut results = ResultLog::default();
+        results.push("+ 0xdeadbeef 1", "0xdeadbef0");
+        assert_eq!(convert_recalled("+ $1 zz", &results, Notation::Prefix), "\
+✗ error: invalid token zz
+  + 0xdeadbef0 zz
+               ^^");
+    }
+
+    #[test]
+    fn test_show_clamped() {
+        let failure = Failure { message: String::from("error: x"), span: 4..13 };
+        assert_eq!(failure.show("+ $1 zz"), "error: x\n  + $1 zz\n      ^^^");
+        let failure = Failure { message: String::from("error: x"), span: 20..30 };
+        assert_eq!(failure.show("+ ä"), "error: x\n  + ä\n     ^");
+        let failure = Failure { message: String::from("error: x"), span: 3..4 };
+        assert_eq!(failure.show("+ ä"), "error: x\n  + ä\n    ^");
+    }
+
+}
@@ -0,0 +1,30 @@
+/// exit codes and argument handling of the pn_bitwister command line
+/// 0xca7
+use std::process::{Command, Output};
+
+fn pn_bitwister(args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_pn_bitwister"))
+        .args(args)
+        .output()
+        .unwrap()
+}
+
+#[test]
+fn test_options_after_expression() {
+    let output = pn_bitwister(&["+", "0xff", "1", "-w", "u8", "-q"]);
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "0x0\n");
+
+    // `-` is an operation, not an option
+    let output = pn_bitwister(&["-q", "-", "10", "3"]);
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "0x7\n");
+}
+
+#[test]
+fn test_usage_errors() {
+    assert_eq!(pn_bitwister(&["--bogus"]).status.code(), Some(2));
+    // explaining works on prefix expressions only
+    let output = pn_bitwister(&["-x", "--rpn", "1", "2", "+"]);
+    assert_eq!(output.status.code(), Some(2));
+    assert!(String::from_utf8_lossy(&output.stderr).contains("--rpn"));
+}